@@ -3,17 +3,32 @@ use std::path::Path;
 use fontdue::{Font, FontSettings};
 
 use crate::media::track::Track;
-use crate::media::video::TimeStamp;
-use crate::text::Text;
+use crate::media::video::{Sequence, TimeStamp};
+use crate::text::{Align, Outline, Shadow, Text, TextLayout};
 use crate::filter::{Filter,AudioFilter,Effect, Instruction};
-use crate::io::io::{self, IOError};
+use crate::io::io::{self, AnimationOptions, IOError, ImageFormatKind, ImageOptions, LoadOptions, MetadataPolicy};
 // use crate::io::video_io::{Video, VideoEncoder};
-use crate::media::frame::{Color, Frame, Pos};
+use crate::media::compare;
+use crate::media::frame::{BitDepth, BlendMode, Color, Frame, FrameError, PixelFormat, Pos};
+use crate::media::generate::{self, Gradient, GradientStop, Noise, NoiseKind};
+use crate::media::metadata::{ExifValue, ImageMetadata};
+use crate::media::montage::{Captions, Montage};
+use crate::media::pyramid;
 use crate::parser::{
     BinOp, Channel, ChannelAssign, Expr,EffectDecl,AudioFilterDecl, FilterDecl, Import, Item, Program, Statement,
 };
+use crate::pipeline::crop::Saliency;
+use crate::pipeline::denoise::Denoiser;
+use crate::pipeline::draw::{self, Drawing, Shape};
+use crate::pipeline::edge::EdgeDetector;
 use crate::pipeline::kernel::Kernel;
+use crate::pipeline::key::Keyer;
+use crate::pipeline::palette::{Dither, Palette, PaletteMethod, Quantize, QuantizeTarget};
+use crate::pipeline::biquad::{Biquad, BiquadKind};
+use crate::pipeline::delay::DelayEffect;
+use crate::pipeline::dynamics::Dynamics;
 use crate::pipeline::pipeline::{AudioPipeline, EffectPipeline, Operation,AudioOperation, Pipeline, PipelineError};
+use crate::pipeline::tiled::DEFAULT_TILE;
 use crate::range::{Mask, Rect, StepRange};
 use std::cell::RefCell;
 use std::rc::Rc;
@@ -36,6 +51,9 @@ use std::rc::Rc;
 //     }
 // }
 
+/// Frames with more pixels than this run a tileable pipeline tile by tile.
+const TILED_PIXELS: u64 = 16_000_000;
+
 #[derive(Debug, Clone)]
 pub enum Value {
     //Video(VideoHandle),
@@ -43,6 +61,8 @@ pub enum Value {
     Track(Track),
     Number(f64),
     String(String),
+    /// Frames with timing, from `frames()`.
+    Sequence(Sequence),
 }
 
 #[derive(Debug)]
@@ -67,6 +87,95 @@ impl From<IOError> for EngineError {
     }
 }
 
+fn as_number(value: Value) -> Result<f64, EngineError> {
+    match value {
+        Value::Number(n) => Ok(n),
+        Value::Frame(_) => Err(EngineError::Eval("expected a number, got a frame".into())),
+        Value::String(_) => Err(EngineError::Eval("expectd a number found string".into())),
+        Value::Track(_) => Err(EngineError::Eval(("expected a number found track".into()))),
+        Value::Sequence(_) => Err(EngineError::Eval("expected a number, got a sequence".into())),
+    }
+}
+
+/// `value.name` for the properties scripts can read. Durations are in seconds.
+fn property(value: &Value, name: &str) -> Result<f64, EngineError> {
+    let found = match (value, name) {
+        (Value::Frame(frame), "width") => Some(frame.width() as f64),
+        (Value::Frame(frame), "height") => Some(frame.height() as f64),
+        (Value::Track(track), "duration") => Some(track.duration().to_seconds()),
+        (Value::Track(track), "sample_rate") => Some(track.sample_rate() as f64),
+        (Value::Track(track), "channels") => Some(track.channels() as f64),
+        (Value::Sequence(seq), "width") => Some(seq.frames.first().map_or(0, |f| f.frame.width()) as f64),
+        (Value::Sequence(seq), "height") => Some(seq.frames.first().map_or(0, |f| f.frame.height()) as f64),
+        (Value::Sequence(seq), "frames") => Some(seq.len() as f64),
+        (Value::Sequence(seq), "duration") => Some(seq.duration_seconds()),
+        (Value::String(s), "length") => Some(s.chars().count() as f64),
+        _ => None,
+    };
+    found.ok_or_else(|| {
+        let (kind, known) = match value {
+            Value::Frame(_) => ("a frame", "width, height"),
+            Value::Track(_) => ("a track", "duration, sample_rate, channels"),
+            Value::Sequence(_) => ("a sequence", "width, height, frames, duration"),
+            Value::String(_) => ("a string", "length"),
+            Value::Number(_) => ("a number", "none"),
+        };
+        EngineError::Eval(format!("{kind} has no property '{name}' (it has: {known})"))
+    })
+}
+
+/// A `"#rgb"`, `"#rrggbb"` or `"#rrggbbaa"` string as an RGBA colour.
+fn parse_color(text: &str) -> Result<Color, EngineError> {
+    let hex = text.trim_start_matches('#');
+    let invalid = || EngineError::Eval(format!("invalid colour '{text}' (expected #rgb, #rrggbb or #rrggbbaa)"));
+    let digits: Vec<u8> = hex
+        .chars()
+        .map(|c| c.to_digit(16).map(|d| d as u8))
+        .collect::<Option<_>>()
+        .ok_or_else(invalid)?;
+    let channels: Vec<u8> = match digits.len() {
+        3 => digits.iter().map(|d| d * 17).collect(),
+        6 | 8 => digits.chunks(2).map(|pair| pair[0] * 16 + pair[1]).collect(),
+        _ => return Err(invalid()),
+    };
+    Ok(Color::RGBA(channels[0], channels[1], channels[2], channels.get(3).copied().unwrap_or(255)))
+}
+
+/// A number or string as `print` writes it: whole numbers without a fraction.
+fn display_value(value: Value) -> Result<String, EngineError> {
+    match value {
+        Value::Number(n) => {
+            if n.fract() == 0.0 {
+                Ok((n as i64).to_string())
+            } else {
+                Ok(n.to_string())
+            }
+        }
+        Value::String(s) => Ok(s),
+        Value::Frame(_) => Err(EngineError::Eval("cannot print a Frame".into())),
+        Value::Track(_) => Err(EngineError::Eval("cannot print a Track".into())),
+        Value::Sequence(_) => Err(EngineError::Eval("cannot print a Sequence".into())),
+    }
+}
+
+/// Splits call arguments into positional ones and `name: value` options, each in order.
+fn split_args(args: &[Expr]) -> (Vec<&Expr>, Vec<(&str, &Expr)>) {
+    let mut positional = Vec::new();
+    let mut named = Vec::new();
+    for arg in args {
+        match arg {
+            Expr::NamedArg { name, value } => named.push((name.as_str(), value.as_ref())),
+            other => positional.push(other),
+        }
+    }
+    (positional, named)
+}
+
+/// The error message for an option `call` has no use for.
+fn unknown_option(call: &str, option: &str) -> String {
+    format!("{call} does not take a '{option}:' option")
+}
+
 fn compile_expr(
     expr: &Expr,
     params: &[String],
@@ -96,6 +205,22 @@ fn compile_stmts_for_channel(
                 local_scope.push(name.clone());
             }
 
+            // The first value is set once per run, see `AudioFilter::initial_state`.
+            Statement::State { .. } => {}
+
+            Statement::Assign { name, value } => {
+                let CompileContext::Audio(states) = context else {
+                    return Err(EngineError::Compile(format!("cannot assign to '{name}' here")));
+                };
+                let Some(index) = states.iter().position(|s| s == name) else {
+                    return Err(EngineError::Compile(format!(
+                        "cannot assign to '{name}': only l, r and state variables can be assigned in an audio filter"
+                    )));
+                };
+                compile_into(value, local_scope, param_count, out, context)?;
+                out.push(Instruction::StoreState(index));
+            }
+
             Statement::Channel(ChannelAssign { channel, value }) => {
                 // Only emit instructions for the channel we're currently building.
                 if channel == target {
@@ -104,6 +229,14 @@ fn compile_stmts_for_channel(
             }
 
             Statement::IfElse { cond, true_branch, false_branch } => {
+                if let Some(Statement::State { name, .. }) =
+                    true_branch.iter().chain(false_branch).find(|s| matches!(s, Statement::State { .. }))
+                {
+                    return Err(EngineError::Compile(format!(
+                        "state '{name}' must be declared outside if/else blocks"
+                    )));
+                }
+
                 // Emit the condition expression.
                 compile_into(cond, local_scope, param_count, out,context)?;
 
@@ -151,7 +284,7 @@ fn compile_channel_program(
     compile_stmts_for_channel(body, &target, params, param_count, &mut local_scope, &mut out,context)?;
 
     // If the channel was never assigned, pass the original value through.
-    if out.is_empty() {
+    if !assigns_channel(body, &target) {
         out.push(match target {
             Channel::R => Instruction::LoadR,
             Channel::G => Instruction::LoadG,
@@ -164,6 +297,16 @@ fn compile_channel_program(
     Ok(out)
 }
 
+fn assigns_channel(body: &[Statement], target: &Channel) -> bool {
+    body.iter().any(|stmt| match stmt {
+        Statement::Channel(ChannelAssign { channel, .. }) => channel == target,
+        Statement::IfElse { true_branch, false_branch, .. } => {
+            assigns_channel(true_branch, target) || assigns_channel(false_branch, target)
+        }
+        _ => false,
+    })
+}
+
 fn compile_into(
     expr: &Expr,
     params: &[String],
@@ -205,7 +348,7 @@ fn compile_into(
                         }
                     }
 
-                    CompileContext::Audio => {
+                    CompileContext::Audio(states) => {
                         match name.as_str() {
                             "l" => out.push(Instruction::LoadL),
                             "r" => out.push(Instruction::LoadR),
@@ -220,6 +363,8 @@ fn compile_into(
                                     } else {
                                         out.push(Instruction::LoadLocal(idx - param_count));
                                     }
+                                } else if let Some(idx) = states.iter().position(|s| s == other) {
+                                    out.push(Instruction::LoadState(idx));
                                 } else {
                                     return Err(EngineError::Compile(format!(
                                         "unknown identifier '{other}' in audio filter"
@@ -378,17 +523,34 @@ fn compile_into(
 /// Compiler Context
 enum CompileContext {
     Image,
-    Audio,
+    /// Audio filter bodies, with the names of their `state` variables.
+    Audio(Vec<String>),
     Effect,
     
 }
 pub fn compile_audiofilter_decl(decl: &AudioFilterDecl) -> Result<AudioFilter, EngineError> {
     let param_count = decl.params.len();
+
+    let mut states = Vec::new();
+    let mut state_init = Vec::new();
+    for stmt in &decl.body {
+        let Statement::State { name, value } = stmt else { continue };
+        if states.contains(name) || decl.params.contains(name) {
+            return Err(EngineError::Compile(format!("'{name}' is declared twice in audio filter '{}'", decl.name)));
+        }
+        // Initial values see the parameters and `sr`, not other states.
+        state_init.push(compile_expr(value, &decl.params, param_count, CompileContext::Audio(Vec::new()))?);
+        states.push(name.clone());
+    }
+
+    let context = CompileContext::Audio(states.clone());
     Ok(AudioFilter {
         name: decl.name.clone(),
         params: decl.params.clone(),
-        l_program: compile_channel_program(&decl.body, Channel::L, &decl.params, param_count, &CompileContext::Audio)?,
-        r_program: compile_channel_program(&decl.body, Channel::R, &decl.params, param_count,&CompileContext::Audio)?,
+        l_program: compile_channel_program(&decl.body, Channel::L, &decl.params, param_count, &context)?,
+        r_program: compile_channel_program(&decl.body, Channel::R, &decl.params, param_count, &context)?,
+        states,
+        state_init,
     })
 }
 
@@ -540,6 +702,10 @@ pub struct Engine {
     kernels: HashMap<String, Kernel>,
     imported_files: HashSet<String>,
     effects: HashMap<String, Effect>,
+    /// Parsed fonts by file path, so each file is read and parsed once per run.
+    fonts: HashMap<String, Rc<Font>>,
+    /// Families registered with `font()`: the primary font followed by its fallbacks.
+    font_families: HashMap<String, Vec<Rc<Font>>>,
 }
 
 impl Engine {
@@ -551,6 +717,8 @@ impl Engine {
             effects: HashMap::new(),
             afilters: HashMap::new(),
             imported_files: HashSet::new(),
+            fonts: HashMap::new(),
+            font_families: HashMap::new(),
         }
     }
     
@@ -647,53 +815,12 @@ impl Engine {
                 Ok(())
             }
             Item::Print { args } => {
-                let len = args.len();
-
-                let mut str = match &args[0] {
-                    Expr::Str(string) => string.clone(),
-                    _ => {
-                        return Err(EngineError::Eval(
-                            "First argument to print must be a String!!".to_string(),
-                        ));
-                    }
-                };
-
-                let mut arg_index = 1;
-
-                while let Some(placeholder_pos) = str.find("{}") {
-                    if arg_index >= len {
-                        return Err(EngineError::Eval(
-                            "Not enough arguments provided for format string!".to_string(),
-                        ));
-                    }
-
-                    let replacement = match self.eval(&args[arg_index])? {
-                        Value::Number(n) => {
-                            
-                            if n.fract() == 0.0 {
-                                (n as i64).to_string()
-                            } else {
-                                n.to_string()
-                            }
-                        }
-                        Value::String(s) => s,
-                        Value::Frame(_) => return Err(EngineError::Eval(
-                            "cannot print a Frame".into(),
-                        )),
-                        Value::Track(_) => return Err(EngineError::Eval("cannot print a Track".into())),
-                    };
-
-                    str.replace_range(placeholder_pos..placeholder_pos + 2, &replacement);
-
-                    arg_index += 1;
-                }
-
-                if arg_index < len {
+                if !matches!(args.first(), Some(Expr::Str(_))) {
                     return Err(EngineError::Eval(
-                        "Too many arguments provided for format string!".to_string(),
+                        "First argument to print must be a String!!".to_string(),
                     ));
                 }
-
+                let str = self.format_args(args)?;
                 println!("{}", str);
                 Ok(())
             }
@@ -710,7 +837,7 @@ impl Engine {
                 Ok(())
             }
 
-            Item::Export { value, path } => {
+            Item::Export { value, path, options } => {
                 let value = self.eval_export(value)?;
                 let path_str = match path {
                     Expr::Str(s) => s.clone(),
@@ -720,8 +847,29 @@ impl Engine {
                         ));
                     }
                 };
+                if !options.is_empty() && matches!(value, Value::Track(_)) {
+                    return Err(EngineError::Eval("export options only apply to images and sequences".into()));
+                }
                 match value{
-                    Value::Frame(f) => {io::encode_image(&f, &path_str).map_err(|_| EngineError::Eval("Image Export Failed! Either Frame is Empty or invalid".into()))?;}
+                    Value::Frame(f) => {
+                        let image_options = self.eval_image_options(options)?;
+                        io::encode_image_with(&f, &path_str, &image_options).map_err(|e| EngineError::Eval(format!("Image Export Failed! {e}")))?;
+                    }
+                    Value::Sequence(seq) => {
+                        let animation_options = self.eval_animation_options(options)?;
+                        let ext = Path::new(&path_str).extension().and_then(|e| e.to_str()).map(str::to_lowercase);
+                        let result = match ext.as_deref() {
+                            Some("gif") => io::encode_gif(&seq, &path_str, &animation_options),
+                            Some("png" | "apng") => io::encode_apng(&seq, &path_str, &animation_options),
+                            other => {
+                                return Err(EngineError::Eval(format!(
+                                    "sequences export as .gif or .png (APNG), got '{}'",
+                                    other.unwrap_or("")
+                                )));
+                            }
+                        };
+                        result.map_err(|e| EngineError::Eval(format!("Animation Export Failed! {e}")))?;
+                    }
                     Value::Track(t) => {
                         let path = Path::new(&path_str);
                         io::encode_wav(&t, path).expect("Encoding Audio failed! Invalid Track");
@@ -762,8 +910,14 @@ impl Engine {
             Expr::Str(s) => Ok(Value::String(s.clone())),
 
             Expr::BinOp { op, lhs, rhs } => {
-                let l = self.eval_number(lhs)?;
-                let r = self.eval_number(rhs)?;
+                let l = self.eval(lhs)?;
+                let r = self.eval(rhs)?;
+                // `+` joins strings; a number on the other side is written as print() would.
+                if matches!(op, BinOp::Add) && (matches!(l, Value::String(_)) || matches!(r, Value::String(_))) {
+                    return Ok(Value::String(display_value(l)? + &display_value(r)?));
+                }
+                let l = as_number(l)?;
+                let r = as_number(r)?;
                 Ok(Value::Number(match op {
                     BinOp::Add => l + r,
                     BinOp::Sub => l - r,
@@ -837,16 +991,38 @@ impl Engine {
             Expr::Call { path, args } => self.eval_call(path, args),
 
             Expr::Pipe { base, stages } => {
-                
+                if let Expr::Call { path, args } = base.as_ref() {
+                    if path.len() == 1 && path[0] == "stream" {
+                        return self.eval_stream(args, stages);
+                    }
+                }
+
                 match self.eval(base)? {
                     Value::Frame(mut frame) => {
                         let mut pipeline = EffectPipeline { operations: Vec::new() };
                         for stage in stages {
                             pipeline.operations.push(self.compile_stage(stage)?);
                         }
-                        pipeline.execute(&mut frame)?;
+                        // Big frames go tile by tile so no stage copies the whole frame.
+                        let pixels = frame.width() as u64 * frame.height() as u64;
+                        if pixels > TILED_PIXELS && pipeline.is_tileable() {
+                            frame = pipeline.execute_tiled(&frame, DEFAULT_TILE)?;
+                        } else {
+                            pipeline.execute(&mut frame)?;
+                        }
                         Ok(Value::Frame(frame))
                     }
+                    Value::Sequence(mut seq) => {
+                        // One compiled pipeline runs on every frame; timing is untouched.
+                        let mut pipeline = EffectPipeline { operations: Vec::new() };
+                        for stage in stages {
+                            pipeline.operations.push(self.compile_stage(stage)?);
+                        }
+                        for vf in &mut seq.frames {
+                            pipeline.execute(&mut vf.frame)?;
+                        }
+                        Ok(Value::Sequence(seq))
+                    }
                     Value::Track(mut track) => {
                         let mut pipeline = AudioPipeline { operations: Vec::new() };
                         for stage in stages {
@@ -856,30 +1032,190 @@ impl Engine {
                         Ok(Value::Track(track))
                     }
                     _ => Err(EngineError::Eval(
-                        "Piping '->' is only supported on Frames and Tracks!".into(),
+                        "Piping '->' is only supported on Frames, Sequences and Tracks!".into(),
                     )),
                 }
             }
 
+            Expr::NamedArg { name, .. } => Err(EngineError::Eval(format!(
+                "named argument '{name}:' is not accepted here"
+            ))),
+
+            Expr::Member { base, name } => {
+                let value = self.eval(base)?;
+                property(&value, name).map(Value::Number)
+            }
+
             other => Err(EngineError::Eval(format!(
                 "cannot evaluate expression: {other:?}"
             ))),
         }
     }
 
+    /// `stream("in.png", "out.png", tile: n) -> stages...`: runs the stages over the
+    /// file a band of rows at a time and returns the output path.
+    fn eval_stream(&mut self, args: &[Expr], stages: &[crate::parser::PipeStage]) -> Result<Value, EngineError> {
+        let (positional, named) = split_args(args);
+        if positional.len() != 2 {
+            return Err(EngineError::Eval("stream() takes an input and an output PNG path".into()));
+        }
+        let input = self.eval_string(positional[0])?;
+        let output = self.eval_string(positional[1])?;
+        let mut tile = DEFAULT_TILE;
+        for (option, value) in named {
+            match option {
+                "tile" => tile = self.eval_number(value)?.max(1.0) as u32,
+                _ => return Err(EngineError::Eval(unknown_option("stream()", option))),
+            }
+        }
+        for path in [&input, &output] {
+            if ImageFormatKind::from_path(path)? != ImageFormatKind::Png {
+                return Err(EngineError::Eval(format!("stream() reads and writes PNG files, not '{path}'")));
+            }
+        }
+
+        let mut pipeline = EffectPipeline { operations: Vec::new() };
+        for stage in stages {
+            pipeline.operations.push(self.compile_stage(stage)?);
+        }
+        if !pipeline.is_tileable() {
+            return Err(EngineError::Eval(
+                "stream() stages must work on small neighbourhoods; resize, crop, adaptive palettes, error diffusion, canny and harris need the whole image".into(),
+            ));
+        }
+        io::stream_png(&input, &output, &pipeline, tile)?;
+        Ok(Value::String(output))
+    }
+
+    fn load_font(&mut self, path: &str) -> Result<Rc<Font>, EngineError> {
+        if let Some(font) = self.fonts.get(path) {
+            return Ok(Rc::clone(font));
+        }
+        let bytes = std::fs::read(path)
+            .map_err(|e| EngineError::Eval(format!("cannot read font '{path}': {e}")))?;
+        let font = Font::from_bytes(bytes, FontSettings::default())
+            .map_err(|e| EngineError::Eval(format!("'{path}' is not a usable font: {e}")))?;
+        let font = Rc::new(font);
+        self.fonts.insert(path.to_string(), Rc::clone(&font));
+        Ok(font)
+    }
+
+    /// A registered family name resolves to its whole chain; anything else is a font file.
+    fn resolve_font(&mut self, name: &str) -> Result<Vec<Rc<Font>>, EngineError> {
+        match self.font_families.get(name) {
+            Some(chain) => Ok(chain.clone()),
+            None => Ok(vec![self.load_font(name)?]),
+        }
+    }
+
+    /// Fills the `{}` placeholders of the format string in `args[0]` with the rest of `args`.
+    fn format_args(&mut self, args: &[Expr]) -> Result<String, EngineError> {
+        let len = args.len();
+        let mut str = match args.first() {
+            Some(first) => self.eval_string(first)?,
+            None => return Err(EngineError::Eval("missing format string".into())),
+        };
+
+        let mut arg_index = 1;
+        let mut search_from = 0;
+
+        while let Some(found) = str[search_from..].find("{}") {
+            let placeholder_pos = search_from + found;
+            if arg_index >= len {
+                return Err(EngineError::Eval(
+                    "Not enough arguments provided for format string!".to_string(),
+                ));
+            }
+
+            let replacement = display_value(self.eval(&args[arg_index])?)?;
+            str.replace_range(placeholder_pos..placeholder_pos + 2, &replacement);
+            // Continue after the substituted text, so a `{}` inside it is left alone.
+            search_from = placeholder_pos + replacement.len();
+
+            arg_index += 1;
+        }
+
+        if arg_index < len {
+            return Err(EngineError::Eval(
+                "Too many arguments provided for format string!".to_string(),
+            ));
+        }
+        Ok(str)
+    }
+
     fn eval_call(&mut self, path: &[String], args: &[Expr]) -> Result<Value, EngineError> {
         let name = path.last().map(String::as_str).unwrap_or("");
         match name {
+            "stream" => Err(EngineError::Eval(
+                "stream() needs its stages piped on: stream(\"in.png\", \"out.png\") -> blur(3)".into(),
+            )),
             "frame" => {
-                let path_str = match args.first() {
-                    Some(Expr::Str(s)) => s.clone(),
-                    _ => return Err(EngineError::Eval("load() requires a string path".into())),
+                let (positional, named) = split_args(args);
+                let path_str = match positional.as_slice() {
+                    [Expr::Str(s)] => s.clone(),
+                    [] | [_] => return Err(EngineError::Eval("load() requires a string path".into())),
+                    _ => return Err(EngineError::Eval("frame() takes a path and optional 'orient: 0'".into())),
                 };
-                
-                let frame = io::load_image(&path_str, "rgba")
+
+                let mut options = LoadOptions::default();
+                for (option, value) in named {
+                    match option {
+                        "orient" => options.auto_orient = self.eval_number(value)? != 0.0,
+                        _ => {
+                            return Err(EngineError::Eval(format!(
+                                "{} (expected orient)",
+                                unknown_option("frame()", option)
+                            )));
+                        }
+                    }
+                }
+
+                let frame = io::load_image_with(&path_str, "rgba", &options)
                     .map_err(|e| EngineError::Eval(format!("{e}")))?;
                 Ok(Value::Frame(frame))
             }
+            "frames" => {
+                let path_str = match args.first() {
+                    Some(Expr::Str(s)) => s.clone(),
+                    _ => return Err(EngineError::Eval("frames() requires a string path".into())),
+                };
+                let seq = io::decode_animation(&path_str)
+                    .map_err(|e| EngineError::Eval(format!("Animation Decoding Failed! {e}")))?;
+                Ok(Value::Sequence(seq))
+            }
+            "meta" => {
+                if args.len() != 2 {
+                    return Err(EngineError::Eval("meta() requires a frame and a tag name".into()));
+                }
+                let frame = self.eval_frame(&args[0])?;
+                let tag = self.eval_string(&args[1])?;
+                if !ImageMetadata::known_tags().any(|t| t.eq_ignore_ascii_case(&tag)) {
+                    return Err(EngineError::Eval(format!("unknown EXIF tag '{tag}'")));
+                }
+
+                // Missing tags read as an empty string so batch scripts can test for them.
+                let Some(metadata) = frame.metadata() else {
+                    return Ok(Value::String(String::new()));
+                };
+                let value = match metadata.exif_field(&tag) {
+                    None => Value::String(String::new()),
+                    Some(ExifValue::Text(text)) => Value::String(text),
+                    Some(ExifValue::Numbers(numbers)) => {
+                        if tag.eq_ignore_ascii_case("GPSLatitude") || tag.eq_ignore_ascii_case("GPSLongitude") {
+                            // Degrees, minutes, seconds folded into signed decimal degrees.
+                            let degrees = numbers.iter().zip([1.0, 60.0, 3600.0]).map(|(v, d)| v / d).sum::<f64>();
+                            let reference = metadata.exif_field(&format!("{tag}Ref"));
+                            let negative = matches!(reference, Some(ExifValue::Text(r)) if r == "S" || r == "W");
+                            Value::Number(if negative { -degrees } else { degrees })
+                        } else if let [single] = numbers[..] {
+                            Value::Number(single)
+                        } else {
+                            Value::String(numbers.iter().map(|n| n.to_string()).collect::<Vec<_>>().join(", "))
+                        }
+                    }
+                };
+                Ok(value)
+            }
             "track" => {
                 let path_str = match args.first(){
                     Some(Expr::Str(s)) => s.clone(),
@@ -889,37 +1225,91 @@ impl Engine {
                 let track = io::decode_audio(path).map_err(|_| EngineError::Eval(format!("Audio Decoding Failed, Check Path....")))?;
                 Ok(Value::Track(track))
             }
+            "format" => Ok(Value::String(self.format_args(args)?)),
+            "font" => {
+                if args.len() < 2 {
+                    return Err(EngineError::Eval(
+                        "font() requires a family name and at least one font path".into(),
+                    ));
+                }
+                let family = self.eval_string(&args[0])?;
+                let mut chain = Vec::new();
+                for source in &args[1..] {
+                    let source = self.eval_string(source)?;
+                    chain.extend(self.resolve_font(&source)?);
+                }
+                self.font_families.insert(family.clone(), chain);
+                Ok(Value::String(family))
+            }
+            "load_font" => {
+                if args.len() != 1 {
+                    return Err(EngineError::Eval("load_font() requires a font path".into()));
+                }
+                let path = self.eval_string(&args[0])?;
+                self.load_font(&path)?;
+                Ok(Value::String(path))
+            }
             "text" => {
-                if args.len() < 6 {
+                let (positional, named) = split_args(args);
+                if positional.len() != 6 {
                     return Err(EngineError::EvalError("text() needs 6 args: str,font_path, size, r, g, b,".into()));
                 }
-                
-                
-                let text_content = match &args[0] {
-                    Expr::Str(s) => s.clone(), 
-                    _ => return Err(EngineError::EvalError("First arg to text() must be string".into())),
-                };
-                let font_path= self.eval_string(&args[1])?;
-                
-                
-                let size = self.eval_number(&args[2])? as f32;
-                let r = self.eval_number(&args[3])? as u8;
-                let g = self.eval_number(&args[4])? as u8;
-                let b = self.eval_number(&args[5])? as u8;
-                let bytes = match  std::fs::read(font_path){
-                    Ok(b) => b,
-                    Err(_) => return Err(EngineError::Eval("Invalid Font Path".into())),
-                };
-                let font = Font::from_bytes(bytes, FontSettings::default()).expect("Font Loading Failed, check path");
 
-                
-                
-                
-                
-                
+                // Any string expression works, so captions can be built with `+` or format().
+                let text_content = display_value(self.eval(positional[0])?)?;
+                let font_path= self.eval_string(positional[1])?;
+
+                let size = self.eval_number(positional[2])? as f32;
+                let r = self.eval_number(positional[3])? as u8;
+                let g = self.eval_number(positional[4])? as u8;
+                let b = self.eval_number(positional[5])? as u8;
+
+                let mut layout = TextLayout::default();
+                let mut outline_color = Color::RGBA(0, 0, 0, 255);
+                let mut shadow_distance = None;
+                let mut shadow_blur = 2.0;
+                let mut shadow_color = Color::RGBA(0, 0, 0, 160);
+                for (option, value) in named {
+                    match option {
+                        "width" => layout.max_width = Some(self.eval_number(value)?.max(1.0) as f32),
+                        "line_spacing" => layout.line_spacing = self.eval_number(value)?.max(0.0) as f32,
+                        "align" => {
+                            let align = self.eval_string(value)?;
+                            layout.align = Align::from_name(&align).ok_or_else(|| {
+                                EngineError::Eval(format!("unknown text alignment '{align}' (expected left, center or right)"))
+                            })?;
+                        }
+                        "kerning" => layout.kerning = self.eval_number(value)? != 0.0,
+                        "outline" => {
+                            let width = self.eval_number(value)?.max(0.0) as f32;
+                            layout.outline = (width > 0.0).then_some(Outline { width, color: outline_color });
+                        }
+                        "outline_color" => outline_color = self.eval_color(value)?,
+                        "shadow" => shadow_distance = Some(self.eval_number(value)? as f32),
+                        "shadow_blur" => shadow_blur = self.eval_number(value)?.max(0.0) as f32,
+                        "shadow_color" => shadow_color = self.eval_color(value)?,
+                        _ => {
+                            return Err(EngineError::Eval(format!(
+                                "{} (expected width, line_spacing, align, kerning, outline, outline_color, shadow, shadow_blur or shadow_color)",
+                                unknown_option("text()", option)
+                            )));
+                        }
+                    }
+                }
+                // Colours may be given before or after the option they belong to.
+                if let Some(outline) = layout.outline.as_mut() {
+                    outline.color = outline_color;
+                }
+                layout.shadow = shadow_distance.map(|d| Shadow { dx: d, dy: d, blur: shadow_blur, color: shadow_color });
+
+                let mut fonts = self.resolve_font(&font_path)?;
+                let font = fonts.remove(0);
+
                 let mut txt_obj = Text::new(&text_content, font,size, Pos(0,0),Color::RGBA(r, g, b,255));
-                let frame = txt_obj.picturize().expect("Text Conversion Failed!");
-                
+                txt_obj.set_fallbacks(fonts);
+                txt_obj.set_layout(layout);
+                let frame = txt_obj.picturize().map_err(|e| EngineError::Eval(format!("text(): {e}")))?;
+
                 Ok(Value::Frame(frame))
             }
             
@@ -935,6 +1325,187 @@ impl Engine {
 
                 Ok(Value::Frame(Frame::blank(width, height)))
             }
+            "solid" | "linear_gradient" | "radial_gradient" | "checkerboard" | "perlin" | "simplex" | "worley" => {
+                let (positional, named) = split_args(args);
+                let usage = match name {
+                    "solid" => "solid() requires width, height and a colour",
+                    "linear_gradient" => "linear_gradient() requires width, height, angle and at least one colour stop",
+                    "radial_gradient" => "radial_gradient() requires width, height and at least one colour stop",
+                    "checkerboard" => "checkerboard() requires width, height, square size and optionally two colours",
+                    _ => "noise generators require width, height, scale and optionally a seed",
+                };
+                let arity_ok = match name {
+                    "solid" => positional.len() == 3,
+                    "linear_gradient" => positional.len() >= 4,
+                    "radial_gradient" => positional.len() >= 3,
+                    "checkerboard" => positional.len() == 3 || positional.len() == 5,
+                    _ => positional.len() == 3 || positional.len() == 4,
+                };
+                if !arity_ok {
+                    return Err(EngineError::Eval(usage.into()));
+                }
+                let width = self.eval_number(positional[0])?;
+                let height = self.eval_number(positional[1])?;
+                if width < 1.0 || height < 1.0 {
+                    return Err(EngineError::Eval(format!("{name}() requires a positive width and height")));
+                }
+                let (width, height) = (width as u32, height as u32);
+
+                // Options each generator accepts, checked before any are read.
+                let allowed: &[&str] = match name {
+                    "radial_gradient" => &["cx", "cy", "radius"],
+                    "perlin" | "simplex" | "worley" => &["octaves"],
+                    _ => &[],
+                };
+                let mut options = HashMap::new();
+                for (option, value) in named {
+                    if !allowed.contains(&option) {
+                        return Err(EngineError::Eval(unknown_option(&format!("{name}()"), option)));
+                    }
+                    options.insert(option, self.eval_number(value)? as f32);
+                }
+
+                let frame = match name {
+                    "solid" => generate::solid(width, height, self.eval_color(positional[2])?),
+                    "linear_gradient" => {
+                        let angle = self.eval_number(positional[2])? as f32;
+                        let gradient = self.eval_gradient(&positional[3..])?;
+                        generate::linear_gradient(width, height, angle, &gradient)
+                    }
+                    "radial_gradient" => {
+                        let gradient = self.eval_gradient(&positional[2..])?;
+                        let cx = options.get("cx").copied().unwrap_or(width as f32 / 2.0);
+                        let cy = options.get("cy").copied().unwrap_or(height as f32 / 2.0);
+                        // By default the last stop reaches the farthest corner.
+                        let radius = options.get("radius").copied().unwrap_or_else(|| {
+                            let dx = cx.max(width as f32 - cx);
+                            let dy = cy.max(height as f32 - cy);
+                            dx.hypot(dy)
+                        });
+                        generate::radial_gradient(width, height, cx, cy, radius, &gradient)
+                    }
+                    "checkerboard" => {
+                        let size = self.eval_number(positional[2])?.max(1.0) as u32;
+                        let (a, b) = match positional.get(3..5) {
+                            Some([a, b]) => (self.eval_color(a)?, self.eval_color(b)?),
+                            _ => (Color::RGBA(255, 255, 255, 255), Color::RGBA(204, 204, 204, 255)),
+                        };
+                        generate::checkerboard(width, height, size, a, b)
+                    }
+                    _ => {
+                        let kind = match name {
+                            "perlin" => NoiseKind::Perlin,
+                            "simplex" => NoiseKind::Simplex,
+                            _ => NoiseKind::Worley,
+                        };
+                        let scale = self.eval_number(positional[2])?.max(1.0) as f32;
+                        let seed = match positional.get(3) {
+                            Some(seed) => self.eval_number(seed)? as i64 as u32,
+                            None => 0,
+                        };
+                        let octaves = options.get("octaves").copied().unwrap_or(1.0).clamp(1.0, 12.0) as u32;
+                        Noise { kind, scale, seed, octaves }.render(width, height)
+                    }
+                };
+                Ok(Value::Frame(frame.map_err(|e| EngineError::Eval(format!("{name}(): {e}")))?))
+            }
+            "psnr" | "ssim" | "max_abs_diff" | "diff_image" => {
+                if args.len() != 2 {
+                    return Err(EngineError::Eval(format!("{name}() requires two frames")));
+                }
+                let a = self.eval_frame(&args[0])?;
+                let b = self.eval_frame(&args[1])?;
+                let fail = |e: FrameError| EngineError::Eval(format!("{name}(): {e}"));
+                Ok(match name {
+                    "psnr" => Value::Number(compare::psnr(&a, &b).map_err(fail)?),
+                    "ssim" => Value::Number(compare::ssim(&a, &b).map_err(fail)?),
+                    "max_abs_diff" => Value::Number(compare::max_abs_diff(&a, &b).map_err(fail)? as f64),
+                    _ => Value::Frame(compare::diff_image(&a, &b).map_err(fail)?),
+                })
+            }
+            "multiband_blend" => {
+                if args.len() != 4 {
+                    return Err(EngineError::Eval("multiband_blend() requires (a, b, mask, levels)".into()));
+                }
+                let a = self.eval_frame(&args[0])?;
+                let b = self.eval_frame(&args[1])?;
+                let mask = self.eval_frame(&args[2])?;
+                let levels = self.eval_number(&args[3])?.max(1.0) as usize;
+                let blended = pyramid::multiband_blend(&a, &b, &mask, levels)
+                    .map_err(|e| EngineError::Eval(format!("multiband_blend(): {e}")))?;
+                Ok(Value::Frame(blended))
+            }
+            "exposure_fuse" => {
+                if args.is_empty() {
+                    return Err(EngineError::Eval("exposure_fuse() requires at least one frame".into()));
+                }
+                let mut frames = Vec::with_capacity(args.len());
+                for arg in args {
+                    frames.push(self.eval_frame(arg)?);
+                }
+                let fused = pyramid::exposure_fuse(&frames)
+                    .map_err(|e| EngineError::Eval(format!("exposure_fuse(): {e}")))?;
+                Ok(Value::Frame(fused))
+            }
+            "montage" => {
+                let (positional, named) = split_args(args);
+                if positional.len() != 6 {
+                    return Err(EngineError::Eval(
+                        "montage() requires (frames, columns, cell_w, cell_h, gap, bg), plus optional captions:, font:, size: and color:".into(),
+                    ));
+                }
+                let frames = self.eval_frame_list(positional[0])?;
+                let montage = Montage {
+                    columns: self.eval_number(positional[1])?.max(1.0) as u32,
+                    cell_width: self.eval_number(positional[2])?.max(1.0) as u32,
+                    cell_height: self.eval_number(positional[3])?.max(1.0) as u32,
+                    gap: self.eval_number(positional[4])?.max(0.0) as u32,
+                    background: self.eval_color(positional[5])?,
+                };
+
+                let mut labels = None;
+                let mut font_path = None;
+                let mut size = 16.0;
+                let mut color = Color::RGBA(0, 0, 0, 255);
+                for (option, value) in named {
+                    match option {
+                        "captions" => {
+                            let Expr::Array(items) = value else {
+                                return Err(EngineError::Eval("montage() captions: must be a list, e.g. [\"a\", \"b\"]".into()));
+                            };
+                            let mut list = Vec::with_capacity(items.len());
+                            for item in items {
+                                list.push(display_value(self.eval(item)?)?);
+                            }
+                            labels = Some(list);
+                        }
+                        "font" => font_path = Some(self.eval_string(value)?),
+                        "size" => size = self.eval_number(value)?.max(1.0) as f32,
+                        "color" => color = self.eval_color(value)?,
+                        _ => {
+                            return Err(EngineError::Eval(format!(
+                                "{} (expected captions, font, size or color)",
+                                unknown_option("montage()", option)
+                            )));
+                        }
+                    }
+                }
+                let captions = match labels {
+                    Some(labels) => {
+                        let font_path = font_path
+                            .ok_or_else(|| EngineError::Eval("montage() captions: need a font: to draw with".into()))?;
+                        let mut fonts = self.resolve_font(&font_path)?;
+                        let font = fonts.remove(0);
+                        Some(Captions { labels, font, fallbacks: fonts, size, color })
+                    }
+                    None => None,
+                };
+
+                let sheet = montage
+                    .render(&frames, captions.as_ref())
+                    .map_err(|e| EngineError::Eval(format!("montage(): {e}")))?;
+                Ok(Value::Frame(sheet))
+            }
             "silence" => {
                 if args.len() != 3 {
                     return Err(EngineError::Eval(
@@ -943,8 +1514,9 @@ impl Engine {
                 }
 
                 let dur = self.eval_number(&args[0])? ;
-                let duration = TimeStamp::from_seconds(dur, 1, 44100);
                 let sample_rate = self.eval_number(&args[1])? as u32;
+                // Counting time in samples keeps the chunk timestamps exact.
+                let duration = TimeStamp::from_seconds(dur, 1, sample_rate.max(1));
                 let channel = self.eval_number(&args[2])? as u16;
 
 
@@ -957,20 +1529,135 @@ impl Engine {
         }
     }
 
+    /// Reads `export` named arguments into encoder settings.
+    fn eval_image_options(&mut self, options: &[(String, Expr)]) -> Result<ImageOptions, EngineError> {
+        let mut image_options = ImageOptions::default();
+        for (name, expr) in options {
+            if name == "metadata" {
+                image_options.metadata = match self.eval_string(expr)?.as_str() {
+                    "keep" => MetadataPolicy::Keep,
+                    "strip" => MetadataPolicy::Strip,
+                    other => {
+                        return Err(EngineError::Eval(format!(
+                            "metadata must be \"keep\" or \"strip\", got \"{other}\""
+                        )));
+                    }
+                };
+                continue;
+            }
+            let value = self.eval_number(expr)?;
+            match name.as_str() {
+                "quality" => {
+                    if !(1.0..=100.0).contains(&value) {
+                        return Err(EngineError::Eval(format!("quality must be between 1 and 100, got {value}")));
+                    }
+                    image_options.quality = Some(value.round() as u8);
+                }
+                "compression" => {
+                    if !(0.0..=9.0).contains(&value) {
+                        return Err(EngineError::Eval(format!("compression must be between 0 and 9, got {value}")));
+                    }
+                    image_options.compression = Some(value.round() as u8);
+                }
+                "depth" => {
+                    image_options.depth = Some(match value as u32 {
+                        8 => BitDepth::Eight,
+                        16 => BitDepth::Sixteen,
+                        _ => return Err(EngineError::Eval(format!("depth must be 8 or 16, got {value}"))),
+                    });
+                }
+                other => {
+                    return Err(EngineError::Eval(format!(
+                        "unknown export option '{other}' (expected quality, compression, depth or metadata)"
+                    )));
+                }
+            }
+        }
+        Ok(image_options)
+    }
+
+    /// Reads `export` named arguments for a sequence into GIF / APNG settings.
+    fn eval_animation_options(&mut self, options: &[(String, Expr)]) -> Result<AnimationOptions, EngineError> {
+        let mut animation_options = AnimationOptions::default();
+        for (name, expr) in options {
+            if name == "dither" {
+                animation_options.dither = Some(self.eval_dither(expr)?);
+                continue;
+            }
+            let value = self.eval_number(expr)?;
+            match name.as_str() {
+                "colors" => {
+                    if !(2.0..=256.0).contains(&value) {
+                        return Err(EngineError::Eval(format!("colors must be between 2 and 256, got {value}")));
+                    }
+                    animation_options.colors = Some(value.round() as u16);
+                }
+                "loops" => {
+                    if !(0.0..=65535.0).contains(&value) {
+                        return Err(EngineError::Eval(format!("loops must be between 0 (forever) and 65535, got {value}")));
+                    }
+                    animation_options.loops = Some(value.round() as u16);
+                }
+                other => {
+                    return Err(EngineError::Eval(format!(
+                        "unknown export option '{other}' for a sequence (expected colors, dither or loops)"
+                    )));
+                }
+            }
+        }
+        Ok(animation_options)
+    }
+
+    /// A dither given as a method name, or as `1` / `0` for Floyd–Steinberg / none.
+    fn eval_dither(&mut self, expr: &Expr) -> Result<Dither, EngineError> {
+        match self.eval(expr)? {
+            Value::Number(n) => Ok(if n != 0.0 { Dither::FloydSteinberg } else { Dither::None }),
+            Value::String(name) => Dither::from_name(&name).ok_or_else(|| {
+                EngineError::Eval(format!(
+                    "unknown dither '{name}' (expected none, floyd, atkinson, bayer or bluenoise)"
+                ))
+            }),
+            _ => Err(EngineError::Eval("dither must be a method name or 0/1".into())),
+        }
+    }
+
     fn eval_frame(&mut self, expr: &Expr) -> Result<Frame, EngineError> {
         match self.eval(expr)? {
             Value::Frame(f) => Ok(f),
             Value::Number(_) => Err(EngineError::Eval("expected a frame, got a number".into())),
             Value::String(_) => Err(EngineError::Eval("expectd a frame found string".into())),
             Value::Track(_) => Err(EngineError::Eval(("expected a frame found track".into()))),
+            Value::Sequence(_) => Err(EngineError::Eval("expected a frame, got a sequence".into())),
         }
     }
+    /// Frames from a list of frames and image paths, `[a, "b.png"]`, or from a
+    /// single frame, path or sequence.
+    fn eval_frame_list(&mut self, expr: &Expr) -> Result<Vec<Frame>, EngineError> {
+        let items = match expr {
+            Expr::Array(items) => items.iter().collect(),
+            other => vec![other],
+        };
+        let mut frames = Vec::with_capacity(items.len());
+        for item in items {
+            match self.eval(item)? {
+                Value::Frame(frame) => frames.push(frame),
+                Value::String(path) => frames.push(
+                    io::load_image_with(&path, "rgba", &LoadOptions::default())
+                        .map_err(|e| EngineError::Eval(format!("{e}")))?,
+                ),
+                Value::Sequence(seq) => frames.extend(seq.frames.into_iter().map(|f| f.frame)),
+                _ => return Err(EngineError::Eval("expected frames or image paths".into())),
+            }
+        }
+        Ok(frames)
+    }
     fn eval_export(&mut self, expr: &Expr) -> Result<Value, EngineError> {
         match self.eval(expr)? {
             Value::Track(t) => Ok(Value::Track(t)),
             Value::Number(_) => Err(EngineError::Eval("expected a track/frame, got a number".into())),
             Value::String(_) => Err(EngineError::Eval("expectd a track/frame found string".into())),
             Value::Frame(f) => Ok(Value::Frame(f)),
+            Value::Sequence(seq) => Ok(Value::Sequence(seq)),
         }
     }
 
@@ -980,16 +1667,12 @@ impl Engine {
             Value::Number(_) => Err(EngineError::Eval("expected a track, got a number".into())),
             Value::String(_) => Err(EngineError::Eval("expectd a track found string".into())),
             Value::Frame(_) => Err(EngineError::Eval(("expected a track found frame".into()))),
+            Value::Sequence(_) => Err(EngineError::Eval("expected a track, got a sequence".into())),
         }
     }
 
     fn eval_number(&mut self, expr: &Expr) -> Result<f64, EngineError> {
-        match self.eval(expr)? {
-            Value::Number(n) => Ok(n),
-            Value::Frame(_) => Err(EngineError::Eval("expected a number, got a frame".into())),
-            Value::String(_) => Err(EngineError::Eval("expectd a number found string".into())),
-            Value::Track(_) => Err(EngineError::Eval(("expected a number found track".into())))
-        }
+        as_number(self.eval(expr)?)
     }
     fn eval_string(&mut self, expr: &Expr) -> Result<String, EngineError> {
         let val = self.eval(expr)?;
@@ -999,6 +1682,57 @@ impl Engine {
         }
     }
 
+    fn eval_color(&mut self, expr: &Expr) -> Result<Color, EngineError> {
+        parse_color(&self.eval_string(expr)?)
+    }
+
+    /// Gradient stops: a colour, optionally followed by its position as `30%` or `0.3`.
+    fn eval_gradient(&mut self, stops: &[&Expr]) -> Result<Gradient, EngineError> {
+        let mut parsed = Vec::new();
+        for stop in stops {
+            let text = self.eval_string(stop)?;
+            let mut parts = text.split_whitespace();
+            let color = parse_color(parts.next().unwrap_or(""))?;
+            let offset = match parts.next() {
+                None => None,
+                Some(position) => {
+                    let (number, divisor) = match position.strip_suffix('%') {
+                        Some(percent) => (percent, 100.0),
+                        None => (position, 1.0),
+                    };
+                    let value: f32 = number
+                        .parse()
+                        .map_err(|_| EngineError::Eval(format!("invalid gradient stop position '{position}'")))?;
+                    Some(value / divisor)
+                }
+            };
+            if parts.next().is_some() {
+                return Err(EngineError::Eval(format!("gradient stop '{text}' should be a colour and an optional position")));
+            }
+            parsed.push(GradientStop { offset, color });
+        }
+        Gradient::new(&parsed).ok_or_else(|| EngineError::Eval("a gradient needs at least one colour stop".into()))
+    }
+
+    /// A colour for drawing, or `"none"` for no paint.
+    fn eval_paint(&mut self, expr: &Expr) -> Result<Option<Color>, EngineError> {
+        if let Value::String(s) = self.eval(expr)? {
+            if s.eq_ignore_ascii_case("none") {
+                return Ok(None);
+            }
+        }
+        self.eval_color(expr).map(Some)
+    }
+
+    fn eval_blend_mode(&mut self, expr: &Expr) -> Result<BlendMode, EngineError> {
+        let name = self.eval_string(expr)?;
+        BlendMode::from_name(&name).ok_or_else(|| {
+            EngineError::Compile(format!(
+                "unknown blend mode '{name}' (expected normal, multiply, screen, overlay, darken, lighten, add, subtract, difference, hard_light or soft_light)"
+            ))
+        })
+    }
+
     fn eval_usize(&mut self, expr: &Expr) -> Result<usize, EngineError> {
         let n = self.eval_number(expr)?;
         if n < 0.0 {
@@ -1012,29 +1746,223 @@ impl Engine {
             .last()
             .ok_or_else(|| EngineError::Eval("Empty pipeline stage".into()))?;
 
-        let mut params = Vec::new();
-
-        for arg in &stage.args {
-            match self.eval(arg)? {
-                Value::Number(v) => params.push(v as f32),
-                _ => {
-                    return Err(EngineError::Eval(
-                        format!("Audio filter '{}' only accepts numeric parameters.", name)
-                    ));
+        // A script's own `af` comes first, so declaring one with the name of a native
+        // stage keeps working as it did before that stage existed.
+        if let Some(filter) = self.afilters.get(name).cloned() {
+            let mut params = Vec::new();
+            for arg in &stage.args {
+                match self.eval(arg)? {
+                    Value::Number(v) => params.push(v as f32),
+                    _ => {
+                        return Err(EngineError::Eval(
+                            format!("Audio filter '{}' only accepts numeric parameters.", name)
+                        ));
+                    }
                 }
             }
+            return Ok(AudioOperation::PointFilter { filter, params });
         }
-        
 
-        let filter = self
-            .afilters
-            .get(name)
-            .ok_or_else(|| EngineError::Eval(format!("Unknown audio filter '{}'", name)))?;
+        // `song -> resample(48000)` so it can be mixed with 48 kHz tracks.
+        if name.as_str() == "resample" {
+            if stage.args.len() != 1 {
+                return Err(EngineError::Compile("resample requires exactly 1 argument: (rate)".into()));
+            }
+            let rate = self.eval_number(&stage.args[0])?;
+            if !(1.0..=768_000.0).contains(&rate) {
+                return Err(EngineError::Compile(format!("resample rate must be 1 - 768000 Hz, got {rate}")));
+            }
+            return Ok(AudioOperation::Resample(rate.round() as u32));
+        }
 
-        Ok(AudioOperation::PointFilter {
-            filter: filter.clone(),
-            params,
-        })
+        // `voice -> highpass(80) -> peaking(3000, 4, 1.5)`: RBJ cookbook biquads.
+        let kind = match name.as_str() {
+            "lowpass" => Some(BiquadKind::Lowpass),
+            "highpass" => Some(BiquadKind::Highpass),
+            "bandpass" => Some(BiquadKind::Bandpass),
+            "notch" => Some(BiquadKind::Notch),
+            "peaking" => Some(BiquadKind::Peaking),
+            "lowshelf" => Some(BiquadKind::LowShelf),
+            "highshelf" => Some(BiquadKind::HighShelf),
+            _ => None,
+        };
+        if let Some(kind) = kind {
+            let with_gain = matches!(kind, BiquadKind::Peaking | BiquadKind::LowShelf | BiquadKind::HighShelf);
+            let usage = if with_gain { "(freq, gain_db, q?)" } else { "(freq, q?)" };
+            let required = if with_gain { 2 } else { 1 };
+            if stage.args.len() < required || stage.args.len() > required + 1 {
+                return Err(EngineError::Compile(format!("{name} takes {usage}")));
+            }
+            let freq = self.eval_number(&stage.args[0])? as f32;
+            let gain_db = if with_gain { self.eval_number(&stage.args[1])? as f32 } else { 0.0 };
+            let q = match stage.args.get(required) {
+                Some(arg) => self.eval_number(arg)? as f32,
+                None => std::f32::consts::FRAC_1_SQRT_2,
+            };
+            if freq <= 0.0 || q <= 0.0 {
+                return Err(EngineError::Compile(format!("{name}: freq and q must be positive")));
+            }
+            return Ok(AudioOperation::Biquad(Biquad { kind, freq, q, gain_db }));
+        }
+
+        // `guitar -> chorus(1.5, 3, 0.5) -> reverb(0.7, 0.5, 0.25)`
+        let usage = match name.as_str() {
+            "delay" => Some("(ms, feedback, mix)"),
+            "chorus" => Some("(rate, depth_ms, mix)"),
+            "flanger" => Some("(rate, depth_ms, feedback, mix)"),
+            "phaser" => Some("(rate, depth, feedback, mix)"),
+            "reverb" => Some("(room, damping, wet)"),
+            _ => None,
+        };
+        if let Some(usage) = usage {
+            let count = usage.matches(',').count() + 1;
+            if stage.args.len() != count {
+                return Err(EngineError::Compile(format!("{name} takes {usage}")));
+            }
+            let mut values = Vec::with_capacity(count);
+            for arg in &stage.args {
+                values.push(self.eval_number(arg)? as f32);
+            }
+            // Feedback stops short of 1 so the repeats die away.
+            let within = |v: f32, what: &str, max: f32| {
+                if (0.0..=max).contains(&v) {
+                    Ok(v)
+                } else {
+                    Err(EngineError::Compile(format!("{name}: {what} must be 0 - {max}, got {v}")))
+                }
+            };
+            let above_zero = |v: f32, what: &str, max: f32| {
+                if v > 0.0 && v <= max {
+                    Ok(v)
+                } else {
+                    Err(EngineError::Compile(format!("{name}: {what} must be above 0 and at most {max}, got {v}")))
+                }
+            };
+            let effect = match name.as_str() {
+                "delay" => DelayEffect::Echo {
+                    ms: above_zero(values[0], "ms", 10_000.0)?,
+                    feedback: within(values[1], "feedback", 0.99)?,
+                    mix: within(values[2], "mix", 1.0)?,
+                },
+                "chorus" => DelayEffect::Chorus {
+                    rate: above_zero(values[0], "rate", 20.0)?,
+                    depth: within(values[1], "depth", 20.0)?,
+                    mix: within(values[2], "mix", 1.0)?,
+                },
+                "flanger" => DelayEffect::Flanger {
+                    rate: above_zero(values[0], "rate", 20.0)?,
+                    depth: within(values[1], "depth", 20.0)?,
+                    feedback: within(values[2], "feedback", 0.99)?,
+                    mix: within(values[3], "mix", 1.0)?,
+                },
+                "phaser" => DelayEffect::Phaser {
+                    rate: above_zero(values[0], "rate", 20.0)?,
+                    depth: within(values[1], "depth", 1.0)?,
+                    feedback: within(values[2], "feedback", 0.99)?,
+                    mix: within(values[3], "mix", 1.0)?,
+                },
+                _ => DelayEffect::Reverb {
+                    room: within(values[0], "room", 1.0)?,
+                    damping: within(values[1], "damping", 1.0)?,
+                    wet: within(values[2], "wet", 1.0)?,
+                },
+            };
+            return Ok(AudioOperation::Delay(effect));
+        }
+
+        // `music -> compressor(-30, 8, 10, 250, 0, sidechain: voice)` ducks under the voice.
+        let usage = match name.as_str() {
+            "compressor" => Some("(threshold_db, ratio, attack_ms, release_ms, makeup_db)"),
+            "expander" => Some("(threshold_db, ratio, attack_ms, release_ms)"),
+            "gate" => Some("(threshold_db, attack_ms, hold_ms, release_ms)"),
+            "limiter" => Some("(ceiling_db, lookahead_ms)"),
+            _ => None,
+        };
+        if let Some(usage) = usage {
+            let (positional, named) = split_args(&stage.args);
+            // `gate(threshold)` is the old stdlib filter's form, with a linear threshold.
+            let linear_gate = name.as_str() == "gate" && positional.len() == 1;
+            if positional.len() != usage.matches(',').count() + 1 && !linear_gate {
+                return Err(EngineError::Compile(format!("{name} takes {usage}")));
+            }
+            let mut values = Vec::with_capacity(positional.len());
+            for arg in positional {
+                values.push(self.eval_number(arg)? as f32);
+            }
+
+            let mut sidechain = None;
+            for (option, value) in named {
+                match option {
+                    "sidechain" if name.as_str() != "limiter" => match self.eval(value)? {
+                        Value::Track(track) => sidechain = Some(track),
+                        _ => return Err(EngineError::Compile(format!("{name}: sidechain: must be a track"))),
+                    },
+                    _ => return Err(EngineError::Compile(unknown_option(name, option))),
+                }
+            }
+
+            // Levels are dBFS, so thresholds sit at or below 0; times are 0 - 10 s.
+            let level = |v: f32, what: &str| {
+                if (-120.0..=0.0).contains(&v) {
+                    Ok(v)
+                } else {
+                    Err(EngineError::Compile(format!("{name}: {what} is in dBFS and must be -120 - 0, got {v}")))
+                }
+            };
+            let time = |v: f32, what: &str| {
+                if (0.0..=10_000.0).contains(&v) {
+                    Ok(v)
+                } else {
+                    Err(EngineError::Compile(format!("{name}: {what} must be 0 - 10000 ms, got {v}")))
+                }
+            };
+            let ratio = |v: f32| {
+                if v >= 1.0 {
+                    Ok(v)
+                } else {
+                    Err(EngineError::Compile(format!("{name}: ratio must be 1 or more, got {v}")))
+                }
+            };
+            let dynamics = match name.as_str() {
+                "compressor" => Dynamics::Compressor {
+                    threshold: level(values[0], "threshold")?,
+                    ratio: ratio(values[1])?,
+                    attack: time(values[2], "attack")?,
+                    release: time(values[3], "release")?,
+                    makeup: values[4],
+                },
+                "expander" => Dynamics::Expander {
+                    threshold: level(values[0], "threshold")?,
+                    ratio: ratio(values[1])?,
+                    attack: time(values[2], "attack")?,
+                    release: time(values[3], "release")?,
+                },
+                "gate" if linear_gate => {
+                    if !(0.0..=1.0).contains(&values[0]) {
+                        return Err(EngineError::Compile(format!(
+                            "gate: a lone threshold is linear and must be 0 - 1, got {}",
+                            values[0]
+                        )));
+                    }
+                    // A threshold of 0 turned the gate off; -120 dBFS comes as close as levels go.
+                    let threshold = 20.0 * values[0].max(1e-6).log10();
+                    Dynamics::Gate { threshold, attack: 0.0, hold: 0.0, release: 0.0 }
+                }
+                "gate" => Dynamics::Gate {
+                    threshold: level(values[0], "threshold")?,
+                    attack: time(values[1], "attack")?,
+                    hold: time(values[2], "hold")?,
+                    release: time(values[3], "release")?,
+                },
+                _ => Dynamics::Limiter {
+                    ceiling: level(values[0], "ceiling")?,
+                    lookahead: time(values[1], "lookahead")?,
+                },
+            };
+            return Ok(AudioOperation::Dynamics { dynamics, sidechain });
+        }
+
+        Err(EngineError::Eval(format!("Unknown audio filter '{}'", name)))
     }
 
     fn compile_stage(
@@ -1063,20 +1991,200 @@ impl Engine {
             let x = self.eval_number(&stage.args[0])?.max(0.0) as u32;
             let y = self.eval_number(&stage.args[1])?.max(0.0) as u32;
             let width = self.eval_number(&stage.args[2])?.max(1.0) as u32;
-            let height = self.eval_number(&stage.args[3])?.min(1.0) as u32;
+            let height = self.eval_number(&stage.args[3])?.max(1.0) as u32;
             return Ok(Operation::NativeCrop { x, y, width, height });
         }
+
+        // `img -> trim()` / `trim(8)`: cut away borders of the corner colour or transparency.
+        if name.as_str() == "trim" {
+            if stage.args.len() > 1 {
+                return Err(EngineError::Compile("trim takes at most 1 argument: (tolerance)".into()));
+            }
+            let tolerance = match stage.args.first() {
+                Some(arg) => self.eval_number(arg)?.max(0.0) as f32,
+                None => 0.0,
+            };
+            return Ok(Operation::NativeTrim { tolerance });
+        }
+
+        if name.as_str() == "smart_crop" {
+            let (positional, named) = split_args(&stage.args);
+            if positional.len() != 2 {
+                return Err(EngineError::Compile("smart_crop requires 2 arguments: (width, height)".into()));
+            }
+            let width = self.eval_number(positional[0])?.max(1.0) as u32;
+            let height = self.eval_number(positional[1])?.max(1.0) as u32;
+            let mut saliency = Saliency::Edges;
+            for (option, value) in named {
+                match option {
+                    "method" => {
+                        let method = self.eval_string(value)?;
+                        saliency = Saliency::from_name(&method).ok_or_else(|| {
+                            EngineError::Compile(format!("unknown smart_crop method '{method}' (expected edges or entropy)"))
+                        })?;
+                    }
+                    _ => return Err(EngineError::Compile(unknown_option("smart_crop", option))),
+                }
+            }
+            return Ok(Operation::NativeSmartCrop { width, height, saliency });
+        }
+
+        // `seam_resize(1080, 1080, protect: people)`: guide frames are white where they apply.
+        if name.as_str() == "seam_resize" {
+            let (positional, named) = split_args(&stage.args);
+            if positional.len() != 2 {
+                return Err(EngineError::Compile(
+                    "seam_resize requires 2 arguments: (width, height), plus optional protect: and remove:".into(),
+                ));
+            }
+            let width = self.eval_number(positional[0])?.max(1.0) as u32;
+            let height = self.eval_number(positional[1])?.max(1.0) as u32;
+            let (mut protect, mut remove) = (None, None);
+            for (option, value) in named {
+                match option {
+                    "protect" => protect = Some(self.eval_frame(value)?),
+                    "remove" => remove = Some(self.eval_frame(value)?),
+                    _ => return Err(EngineError::Compile(unknown_option("seam_resize", option))),
+                }
+            }
+            return Ok(Operation::NativeSeamResize { width, height, protect, remove });
+        }
         if name.as_str() == "blend" {
-            if stage.args.len() != 4 {
+            let (positional, named) = split_args(&stage.args);
+            if positional.len() != 4 {
                 return Err(EngineError::Compile("blend requires exactly 4 arguments: (x, y, frame, alpha)".into()));
             }
-            let x = self.eval_number(&stage.args[0])?.max(0.0) as u32;
-            let y = self.eval_number(&stage.args[1])?.max(0.0) as u32;
-            let frame = self.eval_frame(&stage.args[2]).expect("Not a valid Frame.");
-            let alpha = self.eval_number(&stage.args[3])?.max(0.0) ;
-            return Ok(Operation::Blend { x, y, frame2: frame, alpha });
+            let x = self.eval_number(positional[0])?.max(0.0) as u32;
+            let y = self.eval_number(positional[1])?.max(0.0) as u32;
+            let frame = self.eval_frame(positional[2])?;
+            let alpha = self.eval_number(positional[3])?.max(0.0);
+            let mut mode = BlendMode::Normal;
+            for (option, value) in named {
+                match option {
+                    "mode" => mode = self.eval_blend_mode(value)?,
+                    _ => return Err(EngineError::Compile(unknown_option("blend", option))),
+                }
+            }
+            return Ok(Operation::Blend { x, y, frame2: frame, alpha, mode });
         }
-        
+        // Vector shapes: the positional arguments place the shape, the colour fills it
+        // (a line strokes with it), and `stroke:`, `stroke_width:`, `fill:` and `mode:` refine the paint.
+        if matches!(name.as_str(), "rect" | "line" | "circle" | "ellipse" | "polygon" | "bezier") {
+            let (positional, named) = split_args(&stage.args);
+            let usage = match name.as_str() {
+                "rect" => "rect requires (x, y, width, height, color) and an optional corner radius",
+                "line" => "line requires exactly 6 arguments: (x0, y0, x1, y1, width, color)",
+                "circle" => "circle requires exactly 4 arguments: (cx, cy, radius, color)",
+                "ellipse" => "ellipse requires exactly 5 arguments: (cx, cy, rx, ry, color)",
+                "polygon" => "polygon requires exactly 2 arguments: (\"x,y x,y ...\", color)",
+                _ => "bezier requires exactly 2 arguments: (\"M x y C ...\", color)",
+            };
+            let expected = match name.as_str() {
+                "rect" => 5..=6,
+                "line" => 6..=6,
+                "circle" => 4..=4,
+                "ellipse" => 5..=5,
+                _ => 2..=2,
+            };
+            if !expected.contains(&positional.len()) {
+                return Err(EngineError::Compile(usage.into()));
+            }
+            // Polygons and paths take their points as a string instead.
+            let mut numbers = Vec::new();
+            if !matches!(name.as_str(), "polygon" | "bezier") {
+                for arg in &positional[..*expected.start() - 1] {
+                    numbers.push(self.eval_number(arg)? as f32);
+                }
+            }
+            let color = self.eval_paint(positional[*expected.start() - 1])?;
+
+            let mut fill = color;
+            let mut stroke = None;
+            let mut stroke_width = 1.0;
+            let shape = match name.as_str() {
+                "rect" => {
+                    let radius = match positional.get(5) {
+                        Some(arg) => self.eval_number(arg)? as f32,
+                        None => 0.0,
+                    };
+                    Shape::Rect { x: numbers[0], y: numbers[1], width: numbers[2], height: numbers[3], radius }
+                }
+                "line" => {
+                    fill = None;
+                    stroke = color;
+                    stroke_width = numbers[4];
+                    Shape::Line { x0: numbers[0], y0: numbers[1], x1: numbers[2], y1: numbers[3] }
+                }
+                "circle" => Shape::Circle { cx: numbers[0], cy: numbers[1], radius: numbers[2] },
+                "ellipse" => Shape::Ellipse { cx: numbers[0], cy: numbers[1], rx: numbers[2], ry: numbers[3] },
+                "polygon" => {
+                    let points = draw::parse_points(&self.eval_string(positional[0])?)
+                        .map_err(|e| EngineError::Compile(format!("polygon: {e}")))?;
+                    Shape::Polygon(points)
+                }
+                _ => {
+                    let path = draw::parse_path(&self.eval_string(positional[0])?)
+                        .map_err(|e| EngineError::Compile(format!("bezier: {e}")))?;
+                    Shape::Path(path)
+                }
+            };
+
+            let mut mode = BlendMode::Normal;
+            for (option, value) in named {
+                match option {
+                    "fill" => fill = self.eval_paint(value)?,
+                    "stroke" => stroke = self.eval_paint(value)?,
+                    "stroke_width" => stroke_width = self.eval_number(value)?.max(0.0) as f32,
+                    "mode" => mode = self.eval_blend_mode(value)?,
+                    _ => {
+                        return Err(EngineError::Compile(format!(
+                            "{} (expected fill, stroke, stroke_width or mode)",
+                            unknown_option(name, option)
+                        )));
+                    }
+                }
+            }
+            return Ok(Operation::NativeDraw { drawing: Drawing { shape, fill, stroke, stroke_width, mode } });
+        }
+        // Pipelines work in float; `depth(8)` or `depth(16)` rounds explicitly, e.g. before export.
+        if name.as_str() == "depth" {
+            if stage.args.len() != 1 {
+                return Err(EngineError::Compile("depth requires exactly 1 argument: (bits)".into()));
+            }
+            let depth = match self.eval_number(&stage.args[0])? as u32 {
+                8 => BitDepth::Eight,
+                16 => BitDepth::Sixteen,
+                32 => BitDepth::Float,
+                other => {
+                    return Err(EngineError::Compile(format!(
+                        "depth must be 8, 16 or 32 (float), got {other}"
+                    )));
+                }
+            };
+            return Ok(Operation::NativeDepth { depth });
+        }
+        // Layout conversions; later stages keep whatever layout the frame has.
+        let format = match name.as_str() {
+            "to_gray" => Some(PixelFormat::Gray),
+            "to_rgb" => Some(PixelFormat::RGB),
+            "to_rgba" => Some(PixelFormat::RGBA),
+            "to_yuv420" => Some(PixelFormat::YUV420),
+            _ => None,
+        };
+        if let Some(format) = format {
+            if !stage.args.is_empty() {
+                return Err(EngineError::Compile(format!("{name} takes no arguments")));
+            }
+            return Ok(Operation::NativeFormat { format });
+        }
+        // Linear-light processing: `linear() -> blur(5) -> srgb()` blurs light, not encoded values.
+        if name.as_str() == "linear" || name.as_str() == "srgb" {
+            if !stage.args.is_empty() {
+                return Err(EngineError::Compile(format!("{name} takes no arguments")));
+            }
+            return Ok(Operation::NativeTransfer { linear: name.as_str() == "linear" });
+        }
+
 
         let mask = match &stage.mask {
             Some((x_range, y_range)) => Some(self.build_mask(x_range, y_range)?),
@@ -1114,6 +2222,210 @@ impl Engine {
         }
         // --- END OF BACKDOOR ---
 
+        // Native edge and feature detectors combine several kernels per pixel,
+        // which a single `kernel` declaration cannot express.
+        let detector = match name.as_str() {
+            "sobel" | "scharr" | "laplacian" => {
+                if !stage.args.is_empty() {
+                    return Err(EngineError::Compile(format!("{name} takes no arguments")));
+                }
+                Some(match name.as_str() {
+                    "sobel" => EdgeDetector::Sobel,
+                    "scharr" => EdgeDetector::Scharr,
+                    _ => EdgeDetector::Laplacian,
+                })
+            }
+            "canny" => {
+                if stage.args.len() != 2 {
+                    return Err(EngineError::Compile("canny requires exactly 2 arguments: (low, high)".into()));
+                }
+                let low = self.eval_number(&stage.args[0])?.max(0.0) as f32;
+                let high = self.eval_number(&stage.args[1])?.max(0.0) as f32;
+                Some(EdgeDetector::Canny { low: low.min(high), high: high.max(low) })
+            }
+            "harris" => {
+                if stage.args.len() != 2 {
+                    return Err(EngineError::Compile("harris requires exactly 2 arguments: (k, threshold)".into()));
+                }
+                let k = self.eval_number(&stage.args[0])? as f32;
+                let threshold = self.eval_number(&stage.args[1])?.clamp(0.0, 1.0) as f32;
+                Some(EdgeDetector::Harris { k, threshold })
+            }
+            _ => None,
+        };
+        if let Some(detector) = detector {
+            return Ok(Operation::NativeEdge { detector, mask });
+        }
+
+        let denoiser = match name.as_str() {
+            "bilateral" => {
+                if stage.args.len() != 3 {
+                    return Err(EngineError::Compile(
+                        "bilateral requires exactly 3 arguments: (radius, sigma_space, sigma_color)".into(),
+                    ));
+                }
+                let radius = self.eval_number(&stage.args[0])?.max(1.0) as usize;
+                let sigma_space = self.eval_number(&stage.args[1])?.max(0.01) as f32;
+                let sigma_color = self.eval_number(&stage.args[2])?.max(0.01) as f32;
+                Some(Denoiser::Bilateral { radius, sigma_space, sigma_color })
+            }
+            "nlmeans" => {
+                if stage.args.len() != 3 {
+                    return Err(EngineError::Compile(
+                        "nlmeans requires exactly 3 arguments: (h, patch, search)".into(),
+                    ));
+                }
+                let h = self.eval_number(&stage.args[0])?.max(0.01) as f32;
+                let patch = self.eval_number(&stage.args[1])?.max(1.0) as usize;
+                let search = self.eval_number(&stage.args[2])?.max(1.0) as usize;
+                Some(Denoiser::NonLocalMeans { h, patch, search })
+            }
+            "guided" => {
+                if stage.args.len() != 2 {
+                    return Err(EngineError::Compile("guided requires exactly 2 arguments: (radius, eps)".into()));
+                }
+                let radius = self.eval_number(&stage.args[0])?.max(1.0) as usize;
+                let eps = self.eval_number(&stage.args[1])?.max(0.0) as f32;
+                Some(Denoiser::Guided { radius, eps })
+            }
+            _ => None,
+        };
+        if let Some(denoiser) = denoiser {
+            return Ok(Operation::NativeDenoise { denoiser, mask });
+        }
+
+        // Keys only lower alpha, so they stack, and a mask limits them to part of the frame.
+        if matches!(name.as_str(), "key" | "luma_key" | "despill" | "choke" | "grow") {
+            let (positional, named) = split_args(&stage.args);
+
+            let mut despill = 0.0;
+            let mut invert = false;
+            for (option, value) in named {
+                match (name.as_str(), option) {
+                    ("key", "despill") => despill = self.eval_number(value)?.clamp(0.0, 1.0) as f32,
+                    ("luma_key", "invert") => invert = self.eval_number(value)? != 0.0,
+                    _ => {
+                        return Err(EngineError::Compile(unknown_option(name, option)));
+                    }
+                }
+            }
+
+            let keyer = match name.as_str() {
+                "key" => {
+                    if positional.len() != 3 {
+                        return Err(EngineError::Compile(
+                            "key requires exactly 3 arguments: (color, tolerance, softness), plus optional despill:".into(),
+                        ));
+                    }
+                    let color = self.eval_color(positional[0])?.channels_f32();
+                    Keyer::Chroma {
+                        color: [color[0] / 255.0, color[1] / 255.0, color[2] / 255.0],
+                        tolerance: self.eval_number(positional[1])?.max(0.0) as f32,
+                        softness: self.eval_number(positional[2])?.max(0.0) as f32,
+                        despill,
+                    }
+                }
+                "luma_key" => {
+                    if positional.len() != 2 {
+                        return Err(EngineError::Compile(
+                            "luma_key requires exactly 2 arguments: (threshold, softness), plus optional invert:".into(),
+                        ));
+                    }
+                    Keyer::Luma {
+                        threshold: self.eval_number(positional[0])? as f32,
+                        softness: self.eval_number(positional[1])?.max(0.0) as f32,
+                        invert,
+                    }
+                }
+                "despill" => {
+                    if positional.is_empty() || positional.len() > 2 {
+                        return Err(EngineError::Compile("despill requires (strength) or (strength, color)".into()));
+                    }
+                    let strength = self.eval_number(positional[0])?.clamp(0.0, 1.0) as f32;
+                    let color = match positional.get(1) {
+                        Some(expr) => self.eval_color(expr)?.channels_f32(),
+                        None => [0.0, 255.0, 0.0, 255.0],
+                    };
+                    Keyer::Despill { color: [color[0] / 255.0, color[1] / 255.0, color[2] / 255.0], strength }
+                }
+                _ => {
+                    if positional.len() != 1 {
+                        return Err(EngineError::Compile(format!("{name} requires exactly 1 argument: (radius)")));
+                    }
+                    let radius = self.eval_number(positional[0])?.max(0.0) as u32;
+                    if name.as_str() == "choke" { Keyer::Choke { radius } } else { Keyer::Grow { radius } }
+                }
+            };
+            return Ok(Operation::NativeKey { keyer, mask });
+        }
+
+        // Palette mapping and dithering visit pixels in order carrying error
+        // forward, which per-pixel filters cannot do.
+        if matches!(name.as_str(), "palette" | "palette_from" | "dither") {
+            let (positional, named) = split_args(&stage.args);
+
+            let mut dither = Dither::None;
+            let mut method = PaletteMethod::MedianCut;
+            for (option, value) in named {
+                match (name.as_str(), option) {
+                    ("palette" | "palette_from", "dither") => dither = self.eval_dither(value)?,
+                    ("palette" | "palette_from", "method") => {
+                        method = match self.eval_string(value)?.to_lowercase().as_str() {
+                            "median" | "mediancut" | "median_cut" => PaletteMethod::MedianCut,
+                            "kmeans" | "k_means" => PaletteMethod::KMeans,
+                            other => {
+                                return Err(EngineError::Compile(format!(
+                                    "unknown palette method '{other}' (expected median or kmeans)"
+                                )));
+                            }
+                        }
+                    }
+                    _ => {
+                        return Err(EngineError::Compile(unknown_option(name, option)));
+                    }
+                }
+            }
+
+            let (target, dither) = match name.as_str() {
+                "palette" => {
+                    if positional.len() != 1 {
+                        return Err(EngineError::Compile(
+                            "palette requires exactly 1 argument: (colors), plus optional method: and dither:".into(),
+                        ));
+                    }
+                    let colors = self.eval_number(positional[0])?.clamp(2.0, 256.0) as usize;
+                    (QuantizeTarget::Adaptive { colors, method }, dither)
+                }
+                "palette_from" => {
+                    if positional.is_empty() || positional.len() > 2 {
+                        return Err(EngineError::Compile(
+                            "palette_from requires (frame) or (frame, colors), plus optional method: and dither:".into(),
+                        ));
+                    }
+                    let source = self.eval_frame(positional[0])?;
+                    let colors = match positional.get(1) {
+                        Some(expr) => self.eval_number(expr)?.clamp(2.0, 256.0) as usize,
+                        None => 256,
+                    };
+                    let palette = Palette::from_frames(&[&source], colors, method)
+                        .map_err(|e| EngineError::Compile(format!("palette_from: {e}")))?;
+                    (QuantizeTarget::Palette(palette), dither)
+                }
+                _ => {
+                    if positional.is_empty() || positional.len() > 2 {
+                        return Err(EngineError::Compile("dither requires (method) or (method, levels)".into()));
+                    }
+                    let dither = self.eval_dither(positional[0])?;
+                    let levels = match positional.get(1) {
+                        Some(expr) => self.eval_number(expr)?.clamp(2.0, 256.0) as u32,
+                        None => 2,
+                    };
+                    (QuantizeTarget::Levels(levels), dither)
+                }
+            };
+            return Ok(Operation::NativeQuantize { quantize: Quantize { target, dither }, mask });
+        }
+
         if let Some(kernel) = self.kernels.get(name.as_str()).cloned() {
             return Ok(Operation::Convolution { kernel, mask });
         }
@@ -1150,3 +2462,151 @@ impl Engine {
         }
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::media::frame::PixelData;
+    use crate::parser::{self, PipeStage};
+
+    #[test]
+    fn crop_keeps_the_requested_height() {
+        let stage = PipeStage {
+            path: vec!["crop".into()],
+            args: [0, 0, 10, 20].into_iter().map(Expr::Int).collect(),
+            mask: None,
+        };
+        let operation = Engine::new().compile_stage(&stage).unwrap();
+        let mut frame = Frame::new(40, 40, PixelData::GRAY(vec![0; 1600])).unwrap();
+        EffectPipeline { operations: vec![operation] }.execute(&mut frame).unwrap();
+        assert_eq!((frame.width(), frame.height()), (10, 20));
+    }
+
+    #[test]
+    fn blend_onto_a_number_is_an_error() {
+        let stage = PipeStage {
+            path: vec!["blend".into()],
+            args: vec![Expr::Int(0), Expr::Int(0), Expr::Int(3), Expr::Float(1.0)],
+            mask: None,
+        };
+        assert!(Engine::new().compile_stage(&stage).is_err());
+    }
+
+    #[test]
+    fn afilter_state_runs_on_across_chunks() {
+        let program = parser::parse("af onepole(a) { state y = 0; y = y + a * (l - y); l = y; r = y; }").unwrap();
+        let mut engine = Engine::new();
+        engine.run(&program).unwrap();
+        let stage = PipeStage { path: vec!["onepole".into()], args: vec![Expr::Float(0.1)], mask: None };
+        let pipeline = AudioPipeline { operations: vec![engine.compile_audio(&stage).unwrap()] };
+
+        let input: Vec<f32> = (0..1000).map(|i| if i % 200 < 100 { 1.0 } else { -0.5 }).collect();
+        let mut track = Track::from_channels(48000, vec![input.clone(), input.clone()], 64);
+        pipeline.execute(&mut track).unwrap();
+
+        let mut y = 0.0f32;
+        let expected: Vec<f32> = input
+            .iter()
+            .map(|x| {
+                y += 0.1 * (x - y);
+                y
+            })
+            .collect();
+        for c in 0..2 {
+            let out: Vec<f32> = track.buffer().iter().flat_map(|f| f.data[c].iter().copied()).collect();
+            assert_eq!(out.len(), expected.len());
+            assert!(out.iter().zip(&expected).all(|(a, b)| (a - b).abs() < 1e-5), "channel {c}");
+        }
+    }
+
+    /// The `af` declarations of examples/audio.drive, without its file I/O.
+    fn audio_example() -> Engine {
+        let mut program = parser::parse(include_str!("../examples/audio.drive")).unwrap();
+        program.items.retain(|item| matches!(item, Item::AudioFilterDecl(_)));
+        let mut engine = Engine::new();
+        engine.run(&program).unwrap();
+        engine
+    }
+
+    /// Runs one audio stage over a short stereo ramp and returns the left channel.
+    fn run_audio(engine: &mut Engine, name: &str, args: Vec<Expr>) -> Vec<f32> {
+        let stage = PipeStage { path: vec![name.into()], args, mask: None };
+        let pipeline = AudioPipeline { operations: vec![engine.compile_audio(&stage).unwrap()] };
+        let ramp: Vec<f32> = (0..256).map(|i| i as f32 / 256.0 - 0.5).collect();
+        let mut track = Track::from_channels(48000, vec![ramp.clone(), ramp], 64);
+        pipeline.execute(&mut track).unwrap();
+        track.buffer().iter().flat_map(|f| f.data[0].iter().copied()).collect()
+    }
+
+    #[test]
+    fn a_declared_af_wins_over_the_native_stage_of_its_name() {
+        // The example's `lowpass(cutoff)` scales by `cutoff`; the native lowpass at
+        // 0.6 Hz would all but silence the ramp.
+        let mut engine = audio_example();
+        let out = run_audio(&mut engine, "lowpass", vec![Expr::Float(0.6)]);
+        assert!(out.iter().enumerate().all(|(i, s)| (s - (i as f32 / 256.0 - 0.5) * 0.6).abs() < 1e-6));
+
+        let native = run_audio(&mut Engine::new(), "lowpass", vec![Expr::Int(1000)]);
+        assert!(native.iter().any(|s| s.abs() > 0.1));
+    }
+
+    #[test]
+    fn a_declared_af_wins_over_the_native_delay_effects() {
+        let mut engine = Engine::new();
+        engine.run(&parser::parse("af delay(k) { l = l * k; r = r * k; } af reverb(k) { l = l + k; }").unwrap()).unwrap();
+        let ramp = |i: usize| i as f32 / 256.0 - 0.5;
+        let scaled = run_audio(&mut engine, "delay", vec![Expr::Float(0.5)]);
+        assert!(scaled.iter().enumerate().all(|(i, s)| (s - ramp(i) * 0.5).abs() < 1e-6));
+        let shifted = run_audio(&mut engine, "reverb", vec![Expr::Float(0.25)]);
+        assert!(shifted.iter().enumerate().all(|(i, s)| (s - (ramp(i) + 0.25)).abs() < 1e-6));
+    }
+
+    #[test]
+    fn one_argument_gate_keeps_the_old_linear_threshold() {
+        let gate = |threshold: f64| {
+            let stage = PipeStage { path: vec!["gate".into()], args: vec![Expr::Float(threshold)], mask: None };
+            Engine::new().compile_audio(&stage)
+        };
+        assert!(gate(1.5).is_err());
+
+        let pipeline = AudioPipeline { operations: vec![gate(0.05).unwrap()] };
+        let samples = vec![0.01, 0.2, -0.03, -0.5, 0.04, 0.06];
+        let mut track = Track::from_channels(48000, vec![samples], 4);
+        pipeline.execute(&mut track).unwrap();
+        let out: Vec<f32> = track.buffer().iter().flat_map(|f| f.data[0].iter().copied()).collect();
+        assert_eq!(out, [0.0, 0.2, 0.0, -0.5, 0.0, 0.06]);
+    }
+
+    #[test]
+    fn a_declared_af_wins_over_the_native_dynamics() {
+        let mut engine = Engine::new();
+        let source = "af gate(t) { if abs(l) > t { l = 0.0; } else { l = l; } } af compressor(k) { l = l * k; }";
+        engine.run(&parser::parse(source).unwrap()).unwrap();
+        let ramp = |i: usize| i as f32 / 256.0 - 0.5;
+        // The declared gate mutes the loud samples, the reverse of the native one.
+        let gated = run_audio(&mut engine, "gate", vec![Expr::Float(0.3)]);
+        assert!(gated.iter().enumerate().all(|(i, s)| *s == if ramp(i).abs() > 0.3 { 0.0 } else { ramp(i) }));
+        let squeezed = run_audio(&mut engine, "compressor", vec![Expr::Float(2.0)]);
+        assert!(squeezed.iter().enumerate().all(|(i, s)| (s - ramp(i) * 2.0).abs() < 1e-6));
+    }
+
+    #[test]
+    fn fonts_are_parsed_once_and_missing_files_are_errors() {
+        let mono = concat!(env!("CARGO_MANIFEST_DIR"), "/tests/fonts/DejaVuSansMono.ttf");
+        let mut engine = Engine::new();
+        let register = |engine: &mut Engine, source: &str| engine.run(&parser::parse(source).unwrap());
+
+        register(&mut engine, &format!("a = font(\"X\", \"{mono}\");")).unwrap();
+        let first = Rc::clone(&engine.font_families["X"][0]);
+        register(&mut engine, &format!("b = font(\"X\", \"{mono}\"); c = load_font(\"{mono}\");")).unwrap();
+        assert!(Rc::ptr_eq(&first, &engine.font_families["X"][0]));
+        assert!(Rc::ptr_eq(&first, &engine.fonts[mono]));
+        assert_eq!(engine.fonts.len(), 1);
+
+        let missing = "/nonexistent/font.ttf";
+        for source in [format!("d = font(\"Y\", \"{missing}\");"), format!("e = load_font(\"{missing}\");")] {
+            assert!(matches!(register(&mut engine, &source), Err(EngineError::Eval(_))), "{source}");
+        }
+        assert!(!engine.font_families.contains_key("Y"));
+    }
+}
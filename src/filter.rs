@@ -20,6 +20,19 @@ pub struct AudioFilter {
     pub params: Vec<String>,
     pub l_program: Vec<Instruction>,
     pub r_program: Vec<Instruction>,
+
+    // `state` declarations in order, and the programs giving their first values
+    pub states: Vec<String>,
+    pub state_init: Vec<Vec<Instruction>>,
+}
+
+/// The `state` variables of one `af` stage while it runs over a track. Each channel
+/// program keeps its own copy: both run every statement, so the copies stay in step
+/// and neither program sees the other's update twice.
+#[derive(Debug, Clone)]
+pub struct AudioState {
+    l: Vec<f32>,
+    r: Vec<f32>,
 }
 
 pub struct Effect {
@@ -135,6 +148,12 @@ pub enum Instruction {
     Round,
     StoreLocal(usize),
     LoadLocal(usize),
+
+    // ─────────────────────────────
+    // Audio filter state, kept between samples
+    // ─────────────────────────────
+    StoreState(usize),
+    LoadState(usize),
 }
 
 #[derive(Debug, Clone, Copy)]
@@ -180,7 +199,7 @@ impl FilterVM {
     fn push(&mut self, value: f32) {
         self.stack.push(value);
     }
-    fn run_program(&mut self, program: &[Instruction], ctx: &VMContext, params: &[f32]) {
+    fn run_program(&mut self, program: &[Instruction], ctx: &VMContext, params: &[f32], state: &mut [f32]) {
         let mut ip = 0;
         while ip< program.len() {
             let instruction = &program[ip];
@@ -198,22 +217,19 @@ impl FilterVM {
                 }
                 
                 Instruction::LoadR => match ctx {
-                    VMContext::Pixel(p) => self.push(p.color.r() as f32),
+                    VMContext::Pixel(p) => self.push(p.color.channels_f32()[0]),
                     VMContext::Audio(a) => self.push(a.r),
                 },
                 Instruction::LoadG => match ctx {
-                    VMContext::Pixel(p) => self.push(p.color.g() as f32),
+                    VMContext::Pixel(p) => self.push(p.color.channels_f32()[1]),
                     _ => self.push(0.0),
                 },
                 Instruction::LoadB => match ctx {
-                    VMContext::Pixel(p) => self.push(p.color.b() as f32),
+                    VMContext::Pixel(p) => self.push(p.color.channels_f32()[2]),
                     _ => self.push(0.0),
                 },
                 Instruction::LoadA => match ctx {
-                    VMContext::Pixel(p) => {
-                        let a = match p.color { Color::RGBA(.., a) => a, _ => 255 };
-                        self.push(a as f32);
-                    }
+                    VMContext::Pixel(p) => self.push(p.color.channels_f32()[3]),
                     _ => self.push(1.0),
                 },
                 Instruction::LoadL => match ctx {
@@ -504,6 +520,16 @@ impl FilterVM {
                     let val = self.locals.get(*index).copied().unwrap_or(0.0);
                     self.push(val);
                 }
+                Instruction::StoreState(index) => {
+                    let val = self.pop();
+                    if let Some(slot) = state.get_mut(*index) {
+                        *slot = val;
+                    }
+                }
+                Instruction::LoadState(index) => {
+                    let val = state.get(*index).copied().unwrap_or(0.0);
+                    self.push(val);
+                }
             }
             ip +=1;
         }
@@ -511,13 +537,13 @@ impl FilterVM {
 
     pub fn execute(&mut self, program: &[Instruction], ctx: &PixelContext, params: &[f32]) -> f32 {
         self.stack.clear();
-        self.run_program(program, &VMContext::Pixel(ctx), params);
+        self.run_program(program, &VMContext::Pixel(ctx), params, &mut []);
         self.pop()
     }
 
-    pub fn execute_audio(&mut self, program: &[Instruction], ctx: &AudioContext, params: &[f32]) -> f32 {
+    pub fn execute_audio(&mut self, program: &[Instruction], ctx: &AudioContext, params: &[f32], state: &mut [f32]) -> f32 {
         self.stack.clear();
-        self.run_program(program, &VMContext::Audio(ctx), params);
+        self.run_program(program, &VMContext::Audio(ctx), params, state);
         self.pop()
     }
 }
@@ -541,32 +567,58 @@ impl Filter {
             height,
         };
 
-        let r = vm.execute(&self.r_program, &ctx, params).clamp(0.0, 255.0) as u8;
+        let r = vm.execute(&self.r_program, &ctx, params);
+
+        let g = vm.execute(&self.g_program, &ctx, params);
 
-        let g = vm.execute(&self.g_program, &ctx, params).clamp(0.0, 255.0) as u8;
+        let b = vm.execute(&self.b_program, &ctx, params);
 
-        let b = vm.execute(&self.b_program, &ctx, params).clamp(0.0, 255.0) as u8;
+        let q = |v: f32| v.clamp(0.0, 255.0) as u8;
 
         match color {
-            Color::RGB(_, _, _) => Color::RGB(r, g, b).to_rgba(),
+            Color::RGB(_, _, _) => Color::RGB(q(r), q(g), q(b)),
 
             Color::RGBA(_, _, _, _) => {
-                let a = vm.execute(&self.a_program, &ctx, params).clamp(0.0, 255.0) as u8;
+                let a = vm.execute(&self.a_program, &ctx, params);
 
-                Color::RGBA(r, g, b, a)
+                Color::RGBA(q(r), q(g), q(b), q(a))
             }
 
-            Color::Gray(_) => Color::Gray(r).to_rgba(),
+            Color::Gray(_) => Color::Gray(q(0.299 * r + 0.587 * g + 0.114 * b)),
+
+            // Float colours come from high-bit-depth frames: keep colour unclamped
+            // and let the frame decide how to store it.
+            Color::RGBAF(..) => {
+                let a = vm.execute(&self.a_program, &ctx, params);
+
+                Color::RGBAF(r, g, b, a.clamp(0.0, 255.0))
+            }
         }
     }
 }
 
 impl AudioFilter {
-    pub fn apply(&self, l: f32, r: f32, time: f32, sr: f32, params: &[f32], vm: &mut FilterVM) -> (f32, f32) {
+    /// Fresh state for a run over one track. Initial values may use the parameters and `sr`.
+    pub fn initial_state(&self, sr: f32, params: &[f32], vm: &mut FilterVM) -> AudioState {
+        let ctx = AudioContext { l: 0.0, r: 0.0, time: 0.0, sample_rate: sr };
+        let l: Vec<f32> = self.state_init.iter().map(|init| vm.execute_audio(init, &ctx, params, &mut [])).collect();
+        AudioState { r: l.clone(), l }
+    }
+
+    pub fn apply(
+        &self,
+        l: f32,
+        r: f32,
+        time: f32,
+        sr: f32,
+        params: &[f32],
+        vm: &mut FilterVM,
+        state: &mut AudioState,
+    ) -> (f32, f32) {
         let ctx = AudioContext { l, r, time, sample_rate: sr };
         (
-            vm.execute_audio(&self.l_program, &ctx, params),
-            vm.execute_audio(&self.r_program, &ctx, params),
+            vm.execute_audio(&self.l_program, &ctx, params, &mut state.l),
+            vm.execute_audio(&self.r_program, &ctx, params, &mut state.r),
         )
     }
 }
\ No newline at end of file
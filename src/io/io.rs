@@ -1,18 +1,33 @@
 use crate::media::frame::Frame;
-use crate::media::frame::{FrameError, PixelData};
+use crate::media::frame::{BitDepth, FrameError, PixelData, PixelFormat};
+use crate::media::metadata::ImageMetadata;
 use crate::media::track::{AudioFrame, Track};
 use crate::media::video::TimeStamp;
 use crate::media::video::VideoFrame;
+use crate::media::video::Sequence;
+use crate::pipeline::palette::{Dither, Palette, PaletteMethod};
+use crate::pipeline::pipeline::{EffectPipeline, PipelineError};
+use crate::pipeline::tiled::{RowSink, RowSource};
 
 
 use hound::{SampleFormat, WavSpec, WavWriter};
 use image::GenericImageView;
-use image::codecs::png::PngEncoder;
 use image::io::Reader;
-use image::{ExtendedColorType, ImageEncoder};
+use image::codecs::bmp::BmpEncoder;
+use image::codecs::gif::{GifDecoder, GifEncoder, Repeat};
+use image::codecs::jpeg::JpegEncoder;
+use image::codecs::png::{CompressionType, FilterType as PngFilterType, PngDecoder, PngEncoder};
+use image::codecs::qoi::QoiEncoder;
+use image::codecs::tga::TgaEncoder;
+use image::codecs::tiff::TiffEncoder;
+use image::codecs::webp::WebPEncoder;
+use image::metadata::Orientation;
+use image::{AnimationDecoder, ColorType, Delay, DynamicImage, ExtendedColorType, ImageDecoder, ImageEncoder, RgbaImage};
+use std::borrow::Cow;
 use std::fs::File;
-use std::io::BufWriter;
+use std::io::{BufReader, BufWriter, Write};
 use std::path::Path;
+use std::sync::Arc;
 use symphonia::core::audio::AudioBufferRef;
 use symphonia::core::codecs::DecoderOptions;
 use symphonia::core::errors::Error as SymphoniaError;
@@ -30,19 +45,188 @@ pub enum IOError {
     FFmpegError,
     FFmpegDecodingFailed,
     ReelError,
+    UnsupportedFormat(String),
+    InvalidOption(String),
+    FrameSizeMismatch,
 }
 
+impl std::fmt::Display for IOError {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            IOError::FileNotFound => write!(f, "Could not create or open the file"),
+            IOError::InvalidData => write!(f, "The frame data could not be converted for export"),
+            IOError::EncodingFailed => write!(f, "The encoder failed to write the image"),
+            IOError::FFmpegError => write!(f, "FFmpeg error"),
+            IOError::FFmpegDecodingFailed => write!(f, "FFmpeg decoding failed"),
+            IOError::ReelError => write!(f, "Reel error"),
+            IOError::UnsupportedFormat(ext) => write!(
+                f,
+                "Unsupported image extension '{ext}' (expected png, jpg/jpeg, webp, bmp, tif/tiff, tga or qoi)"
+            ),
+            IOError::InvalidOption(msg) => write!(f, "{msg}"),
+            IOError::FrameSizeMismatch => write!(f, "Every frame of an animation must have the same size"),
+        }
+    }
+}
+
+impl std::error::Error for IOError {}
+
+/// Image container chosen from the export path's extension.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum ImageFormatKind {
+    Png,
+    Jpeg,
+    WebP,
+    Bmp,
+    Tiff,
+    Tga,
+    Qoi,
+}
+
+impl ImageFormatKind {
+    /// Paths without an extension are written as PNG.
+    pub fn from_path(path: &str) -> Result<Self, IOError> {
+        let ext = match Path::new(path).extension().and_then(|e| e.to_str()) {
+            Some(ext) => ext.to_lowercase(),
+            None => return Ok(ImageFormatKind::Png),
+        };
+        match ext.as_str() {
+            "png" => Ok(ImageFormatKind::Png),
+            "jpg" | "jpeg" => Ok(ImageFormatKind::Jpeg),
+            "webp" => Ok(ImageFormatKind::WebP),
+            "bmp" => Ok(ImageFormatKind::Bmp),
+            "tif" | "tiff" => Ok(ImageFormatKind::Tiff),
+            "tga" => Ok(ImageFormatKind::Tga),
+            "qoi" => Ok(ImageFormatKind::Qoi),
+            _ => Err(IOError::UnsupportedFormat(ext)),
+        }
+    }
+
+    /// PNG and TIFF store 16 bits per channel; everything else is 8-bit.
+    pub fn supports_16bit(&self) -> bool {
+        matches!(self, ImageFormatKind::Png | ImageFormatKind::Tiff)
+    }
+
+    /// PNG, JPEG and WebP have a slot for an EXIF block.
+    pub fn stores_exif(&self) -> bool {
+        matches!(self, ImageFormatKind::Png | ImageFormatKind::Jpeg | ImageFormatKind::WebP)
+    }
+
+    /// Those and TIFF have one for an ICC profile.
+    pub fn stores_icc(&self) -> bool {
+        self.stores_exif() || *self == ImageFormatKind::Tiff
+    }
+
+    /// QOI only stores RGB and RGBA; everything else also takes single-channel gray.
+    pub fn supports_gray(&self) -> bool {
+        !matches!(self, ImageFormatKind::Qoi)
+    }
+}
+
+/// Encoder settings, given as named arguments to `export`.
+#[derive(Debug, Clone, Copy, Default)]
+pub struct ImageOptions {
+    /// JPEG quality, 1 - 100. Defaults to 90.
+    pub quality: Option<u8>,
+    /// PNG compression level, 0 (stored) - 9 (smallest). Defaults to the encoder's fast setting.
+    pub compression: Option<u8>,
+    /// What happens to the source frame's EXIF and ICC data.
+    pub metadata: MetadataPolicy,
+    /// Precision to write at instead of the frame's export depth; only PNG and TIFF take 16 bits.
+    pub depth: Option<BitDepth>,
+}
+
+/// What `export` does with the EXIF and ICC data a frame was loaded with.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub enum MetadataPolicy {
+    /// Write the blocks the format has a slot for and drop the rest.
+    #[default]
+    Copy,
+    /// Write every block, failing with `InvalidOption` if the format has no slot for one.
+    Keep,
+    /// Write the pixels alone.
+    Strip,
+}
+
+/// Decoder settings for `load_image_with`.
+#[derive(Debug, Clone, Copy)]
+pub struct LoadOptions {
+    /// Rotate / flip the pixels as the EXIF orientation tag says, then reset the tag.
+    pub auto_orient: bool,
+}
+
+impl Default for LoadOptions {
+    fn default() -> Self {
+        LoadOptions { auto_orient: true }
+    }
+}
+
+/// Loads an image with default settings; see `load_image_with`.
 pub fn load_image(path: &str, fmt: &str) -> Result<Frame, FrameError> {
-    let img = Reader::open(path)
+    load_image_with(path, fmt, &LoadOptions::default())
+}
+
+/// Decodes an image into the requested pixel format. EXIF, ICC and XMP blocks are
+/// kept on the frame; with `auto_orient` the EXIF orientation is applied to the
+/// pixels and the tag is reset to 1 so it is not applied twice after export.
+pub fn load_image_with(path: &str, fmt: &str, options: &LoadOptions) -> Result<Frame, FrameError> {
+    let mut decoder = Reader::open(path)
         .map_err(|_| FrameError::EmptyFrame)?
-        .decode()
+        .into_decoder()
         .map_err(|_| FrameError::EmptyFrame)?;
 
+    // Metadata is optional; a malformed block should not stop the pixels loading.
+    let mut metadata = ImageMetadata {
+        exif: decoder.exif_metadata().ok().flatten(),
+        icc: decoder.icc_profile().ok().flatten(),
+        xmp: decoder.xmp_metadata().ok().flatten(),
+    };
+    let orientation = decoder.orientation().unwrap_or(Orientation::NoTransforms);
+
+    let mut img = DynamicImage::from_decoder(decoder).map_err(|_| FrameError::EmptyFrame)?;
+    if options.auto_orient {
+        img.apply_orientation(orientation);
+        if let Some(exif) = metadata.exif.as_mut() {
+            let _ = Orientation::remove_from_exif_chunk(exif);
+        }
+    }
+
     let (width, height) = img.dimensions();
     let pixel_count = (width * height) as usize;
 
-    let data = match fmt.to_lowercase().as_str() {
-        "rgb" => {
+    // "rgb", "rgba" and "gray" keep the source precision: 16-bit files load as
+    // 16-bit planes and float files as float RGBA. The suffixed formats force one.
+    let color = img.color();
+    let source_float = matches!(color, ColorType::Rgb32F | ColorType::Rgba32F);
+    let source_deep = color.bytes_per_pixel() > color.channel_count();
+    let fmt = match (fmt.to_lowercase().as_str(), source_float, source_deep) {
+        ("rgb" | "rgba", true, _) => "rgbaf32".to_string(),
+        ("rgb" | "rgba" | "gray", _, true) => format!("{}16", fmt.to_lowercase()),
+        (other, _, _) => other.to_string(),
+    };
+
+    let data = match fmt.as_str() {
+        "rgb16" => {
+            let raw = img.to_rgb16().into_raw();
+            let [r, g, b] = deinterleave::<u16, 3>(&raw, pixel_count);
+            PixelData::RGB16(r, g, b)
+        }
+
+        "rgba16" => {
+            let raw = img.to_rgba16().into_raw();
+            let [r, g, b, a] = deinterleave::<u16, 4>(&raw, pixel_count);
+            PixelData::RGBA16(r, g, b, a)
+        }
+
+        "gray16" | "l16" => PixelData::GRAY16(img.to_luma16().into_raw()),
+
+        "rgbaf32" => {
+            let raw = img.to_rgba32f().into_raw();
+            let [r, g, b, a] = deinterleave::<f32, 4>(&raw, pixel_count);
+            PixelData::RGBAF32(r, g, b, a)
+        }
+
+        "rgb" | "rgb8" => {
             let rgb = img.to_rgb8();
             let raw = rgb.as_raw();
 
@@ -59,7 +243,7 @@ pub fn load_image(path: &str, fmt: &str) -> Result<Frame, FrameError> {
             PixelData::RGB(r, g, b)
         }
 
-        "rgba" => {
+        "rgba" | "rgba8" => {
             let rgba = img.to_rgba8();
             let raw = rgba.as_raw();
 
@@ -84,97 +268,480 @@ pub fn load_image(path: &str, fmt: &str) -> Result<Frame, FrameError> {
         }
 
         "yuv420" => {
-            // Enforce even dimensions
-            if width % 2 != 0 || height % 2 != 0 {
-                return Err(FrameError::InvalidFrameSize);
-            }
+            let raw = img.to_rgb8().into_raw();
+            let [r, g, b] = deinterleave::<u8, 3>(&raw, pixel_count);
+            PixelData::RGB(r, g, b).to_yuv420(width, height)?
+        }
 
-            let rgb = img.to_rgb8();
-            let raw = rgb.as_raw();
+        _ => return Err(FrameError::InvalidPixelFormat),
+    };
+    // println!(
+    //     "Loaded Image with \n width = {}\n height = {}\n data = {:?}",
+    //     width, height, data
+    // );
+    let mut frame = Frame::new(width, height, data)?;
+    if !metadata.is_empty() {
+        frame.set_metadata(Some(Arc::new(metadata)));
+    }
+    Ok(frame)
+}
 
-            let mut y_plane = vec![0u8; (width * height) as usize];
-            let mut u_plane = vec![0u8; ((width / 2) * (height / 2)) as usize];
-            let mut v_plane = vec![0u8; ((width / 2) * (height / 2)) as usize];
+/// Splits interleaved samples into `N` planes of `pixel_count` values each.
+fn deinterleave<T: Copy + Default, const N: usize>(raw: &[T], pixel_count: usize) -> [Vec<T>; N] {
+    let mut planes: [Vec<T>; N] = std::array::from_fn(|_| Vec::with_capacity(pixel_count));
+    for chunk in raw.chunks_exact(N) {
+        for (plane, &v) in planes.iter_mut().zip(chunk) {
+            plane.push(v);
+        }
+    }
+    planes
+}
 
-            // --- Y PLANE ---
-            for y in 0..height {
-                for x in 0..width {
-                    let idx = ((y * width + x) * 3) as usize;
+/// Writes a frame with default encoder settings; see `encode_image_with`.
+pub fn encode_image(frame: &Frame, path: &str) -> Result<(), IOError> {
+    encode_image_with(frame, path, &ImageOptions::default())
+}
 
-                    let r = raw[idx] as f32;
-                    let g = raw[idx + 1] as f32;
-                    let b = raw[idx + 2] as f32;
+/// Writes a frame in the format named by the path's extension.
+/// PNG and TIFF get 16 bits per channel when the frame's export depth, or the
+/// `depth` option, is 16 bits or float; every other case is written at 8 bits.
+/// This is the only point where a float pipeline is rounded. Gray and RGB frames
+/// keep their layout (QOI has no gray), YUV420 is written as RGB and JPEG drops
+/// alpha. WebP is always lossless.
+pub fn encode_image_with(frame: &Frame, path: &str, options: &ImageOptions) -> Result<(), IOError> {
+    let format = ImageFormatKind::from_path(path)?;
+    let depth = options.depth.unwrap_or(frame.export_depth());
+    let frame = srgb_encoded(frame)?;
+
+    if options.quality.is_some() && format != ImageFormatKind::Jpeg {
+        return Err(IOError::InvalidOption("'quality' only applies to JPEG export".into()));
+    }
+    if options.compression.is_some() && format != ImageFormatKind::Png {
+        return Err(IOError::InvalidOption("'compression' only applies to PNG export".into()));
+    }
 
-                    let y_val = (0.299 * r + 0.587 * g + 0.114 * b).clamp(0.0, 255.0) as u8;
+    let width = frame.width();
+    let height = frame.height();
 
-                    y_plane[(y * width + x) as usize] = y_val;
-                }
+    // Gray and RGB frames are written in their own layout where the format has it.
+    let layout = match frame.format() {
+        PixelFormat::Gray if !format.supports_gray() => PixelFormat::RGB,
+        PixelFormat::YUV420 => PixelFormat::RGB,
+        PixelFormat::RGBA if format == ImageFormatKind::Jpeg => PixelFormat::RGB,
+        other => other,
+    };
+    if options.depth.is_some_and(|d| d != BitDepth::Eight) && !format.supports_16bit() {
+        return Err(IOError::InvalidOption("'depth: 16' only applies to PNG and TIFF export".into()));
+    }
+    if let (MetadataPolicy::Keep, Some(metadata)) = (options.metadata, frame.metadata()) {
+        for (block, present, stored) in [
+            ("EXIF", metadata.exif.is_some(), format.stores_exif()),
+            ("ICC", metadata.icc.is_some(), format.stores_icc()),
+        ] {
+            if present && !stored {
+                return Err(IOError::InvalidOption(format!(
+                    "'{path}' has no room for the frame's {block} data; export with metadata: \"strip\" to drop it"
+                )));
             }
+        }
+    }
 
-            // --- U & V (4:2:0 subsampling) ---
-            for y in (0..height).step_by(2) {
-                for x in (0..width).step_by(2) {
-                    let mut u_sum = 0.0;
-                    let mut v_sum = 0.0;
+    let deep = depth != BitDepth::Eight && format.supports_16bit();
+    let data = frame
+        .to_format(layout)
+        .and_then(|f| if deep { Ok(f) } else { f.to_depth(BitDepth::Eight) })
+        .map_err(|_| IOError::InvalidData)?;
+
+    let (bytes, color) = if deep {
+        let samples = data.data().interleave16(width, height).map_err(|_| IOError::InvalidData)?;
+        let bytes: Vec<u8> = samples.iter().flat_map(|v| v.to_ne_bytes()).collect();
+        let color = match layout {
+            PixelFormat::Gray => ExtendedColorType::L16,
+            PixelFormat::RGB => ExtendedColorType::Rgb16,
+            _ => ExtendedColorType::Rgba16,
+        };
+        (bytes, color)
+    } else {
+        let color = match layout {
+            PixelFormat::Gray => ExtendedColorType::L8,
+            PixelFormat::RGB => ExtendedColorType::Rgb8,
+            _ => ExtendedColorType::Rgba8,
+        };
+        (data.data().interleave(), color)
+    };
 
-                    // 2x2 block
-                    for dy in 0..2 {
-                        for dx in 0..2 {
-                            let px = x + dx;
-                            let py = y + dy;
+    let file = match File::create(path) {
+        Ok(val) => val,
+        Err(_) => return Err(IOError::FileNotFound),
+    };
+    let mut writer = BufWriter::new(file);
+
+    // The source's pixel size in the EXIF block is brought up to date, since any
+    // resize, crop or trim on the way would leave it stale.
+    let metadata = frame.metadata().filter(|_| options.metadata != MetadataPolicy::Strip).map(|m| {
+        let mut m = ImageMetadata::clone(m);
+        m.set_pixel_dimensions(width, height);
+        m
+    });
+    let metadata = metadata.as_ref();
+    let result = match format {
+        ImageFormatKind::Png => {
+            let compression = match options.compression {
+                Some(0) => CompressionType::Uncompressed,
+                Some(level) => CompressionType::Level(level.min(9)),
+                None => CompressionType::default(),
+            };
+            write_with_metadata(
+                PngEncoder::new_with_quality(writer, compression, PngFilterType::Adaptive),
+                metadata, &bytes, width, height, color,
+            )
+        }
+        ImageFormatKind::Jpeg => write_with_metadata(
+            JpegEncoder::new_with_quality(&mut writer, options.quality.unwrap_or(90).clamp(1, 100)),
+            metadata, &bytes, width, height, color,
+        ),
+        ImageFormatKind::WebP => write_with_metadata(WebPEncoder::new_lossless(writer), metadata, &bytes, width, height, color),
+        ImageFormatKind::Bmp => write_with_metadata(BmpEncoder::new(&mut writer), metadata, &bytes, width, height, color),
+        ImageFormatKind::Tiff => write_with_metadata(TiffEncoder::new(writer), metadata, &bytes, width, height, color),
+        ImageFormatKind::Tga => write_with_metadata(TgaEncoder::new(writer), metadata, &bytes, width, height, color),
+        ImageFormatKind::Qoi => write_with_metadata(QoiEncoder::new(writer), metadata, &bytes, width, height, color),
+    };
+    result.map_err(|_| IOError::EncodingFailed)
+}
+
+/// Hands the source frame's EXIF and ICC blocks to the encoder before writing.
+/// Formats without a slot for a block (EXIF in TIFF, BMP, TGA, QOI) skip it, which
+/// `encode_image_with` has already refused under `MetadataPolicy::Keep`; XMP has no
+/// encoder support and is never written.
+fn write_with_metadata<E: ImageEncoder>(
+    mut encoder: E,
+    metadata: Option<&ImageMetadata>,
+    bytes: &[u8],
+    width: u32,
+    height: u32,
+    color: ExtendedColorType,
+) -> image::ImageResult<()> {
+    if let Some(metadata) = metadata {
+        if let Some(icc) = &metadata.icc {
+            let _ = encoder.set_icc_profile(icc.clone());
+        }
+        if let Some(exif) = &metadata.exif {
+            let _ = encoder.set_exif_metadata(exif.clone());
+        }
+    }
+    encoder.write_image(bytes, width, height, color)
+}
+
+/// Settings for `encode_gif` / `encode_apng`, given as named arguments to `export`.
+#[derive(Debug, Clone, Copy, Default)]
+pub struct AnimationOptions {
+    /// GIF palette size, 2 - 256. Defaults to 256.
+    pub colors: Option<u16>,
+    /// Dithering used when mapping GIF frames to the palette. Defaults to none.
+    pub dither: Option<Dither>,
+    /// Times to play the animation; `None` or 0 loops forever.
+    pub loops: Option<u16>,
+}
+
+/// Decodes every frame of an animated GIF or APNG, composited to full size, with
+/// its delay. Any other image loads as a one-frame sequence with no delay.
+pub fn decode_animation(path: &str) -> Result<Sequence, IOError> {
+    let ext = Path::new(path)
+        .extension()
+        .and_then(|e| e.to_str())
+        .map(|e| e.to_lowercase())
+        .unwrap_or_default();
+    let open = || File::open(path).map(BufReader::new).map_err(|_| IOError::FileNotFound);
+
+    let frames = match ext.as_str() {
+        "gif" => GifDecoder::new(open()?)
+            .map_err(|_| IOError::InvalidData)?
+            .into_frames()
+            .collect_frames(),
+        "png" | "apng" => {
+            let decoder = PngDecoder::new(open()?).map_err(|_| IOError::InvalidData)?;
+            if decoder.is_apng().map_err(|_| IOError::InvalidData)? {
+                decoder.apng().map_err(|_| IOError::InvalidData)?.into_frames().collect_frames()
+            } else {
+                Ok(Vec::new())
+            }
+        }
+        _ => Ok(Vec::new()),
+    }
+    .map_err(|_| IOError::InvalidData)?;
 
-                            let idx = ((py * width + px) * 3) as usize;
+    let mut sequence = Sequence::new();
+    if frames.is_empty() {
+        let still = load_image(path, "rgba").map_err(|_| IOError::InvalidData)?;
+        sequence.push(still, 0);
+        return Ok(sequence);
+    }
 
-                            let r = raw[idx] as f32;
-                            let g = raw[idx + 1] as f32;
-                            let b = raw[idx + 2] as f32;
+    for frame in frames {
+        let (numer, denom) = frame.delay().numer_denom_ms();
+        let delay_ms = (numer as f64 / denom.max(1) as f64).round() as i64;
+        let buffer = frame.into_buffer();
+        let (width, height) = buffer.dimensions();
+        let [r, g, b, a] = deinterleave::<u8, 4>(buffer.as_raw(), (width * height) as usize);
+        let frame = Frame::new(width, height, PixelData::RGBA(r, g, b, a)).map_err(|_| IOError::InvalidData)?;
+        sequence.push(frame, delay_ms);
+    }
+    Ok(sequence)
+}
 
-                            u_sum += -0.169 * r - 0.331 * g + 0.5 * b + 128.0;
-                            v_sum += 0.5 * r - 0.419 * g - 0.081 * b + 128.0;
-                        }
-                    }
+/// Files hold sRGB, so a frame still in linear light is encoded on the way out.
+fn srgb_encoded(frame: &Frame) -> Result<Cow<'_, Frame>, IOError> {
+    if frame.is_linear() {
+        Ok(Cow::Owned(frame.to_srgb().map_err(|_| IOError::InvalidData)?))
+    } else {
+        Ok(Cow::Borrowed(frame))
+    }
+}
 
-                    let index = ((y / 2) * (width / 2) + (x / 2)) as usize;
+/// Checks the sequence is non-empty and uniformly sized; returns that size.
+fn sequence_size(sequence: &Sequence) -> Result<(u32, u32), IOError> {
+    let first = sequence.frames.first().ok_or(IOError::InvalidData)?;
+    let size = (first.frame.width(), first.frame.height());
+    if sequence.frames.iter().any(|vf| (vf.frame.width(), vf.frame.height()) != size) {
+        return Err(IOError::FrameSizeMismatch);
+    }
+    Ok(size)
+}
 
-                    u_plane[index] = (u_sum * 0.25).clamp(0.0, 255.0) as u8;
-                    v_plane[index] = (v_sum * 0.25).clamp(0.0, 255.0) as u8;
+/// Writes an animated GIF. All frames are mapped onto one palette built by median
+/// cut, so colours hold still between frames; pixels under half opacity become
+/// the GIF's transparent colour.
+pub fn encode_gif(sequence: &Sequence, path: &str, options: &AnimationOptions) -> Result<(), IOError> {
+    let (width, height) = sequence_size(sequence)?;
+    let colors = options.colors.unwrap_or(256).clamp(2, 256) as usize;
+
+    let encoded = sequence.frames.iter().map(|vf| srgb_encoded(&vf.frame)).collect::<Result<Vec<_>, _>>()?;
+    let frames: Vec<&Frame> = encoded.iter().map(|f| f.as_ref()).collect();
+    let mut palette = Palette::from_frames(&frames, colors, PaletteMethod::MedianCut).map_err(|_| IOError::InvalidData)?;
+    let mut indexed = frames
+        .iter()
+        .map(|f| palette.index_frame(f, options.dither.unwrap_or(Dither::None)))
+        .collect::<Result<Vec<_>, _>>()
+        .map_err(|_| IOError::InvalidData)?;
+
+    // The transparent colour takes a palette slot of its own. Past 256 entries the
+    // GIF encoder would re-quantise the frames, so make room and index again.
+    let transparent = indexed.iter().flatten().any(Option::is_none);
+    if transparent && palette.len() > 255 {
+        palette = Palette::from_frames(&frames, 255, PaletteMethod::MedianCut).map_err(|_| IOError::InvalidData)?;
+        indexed = frames
+            .iter()
+            .map(|f| palette.index_frame(f, options.dither.unwrap_or(Dither::None)))
+            .collect::<Result<Vec<_>, _>>()
+            .map_err(|_| IOError::InvalidData)?;
+    }
+
+    let file = File::create(path).map_err(|_| IOError::FileNotFound)?;
+    let mut encoder = GifEncoder::new(BufWriter::new(file));
+    let repeat = match options.loops {
+        None | Some(0) => Repeat::Infinite,
+        Some(n) => Repeat::Finite(n),
+    };
+    encoder.set_repeat(repeat).map_err(|_| IOError::EncodingFailed)?;
+
+    for (indices, delay_ms) in indexed.iter().zip(sequence.delays_ms()) {
+        let mut rgba = Vec::with_capacity(indices.len() * 4);
+        for index in indices {
+            match index {
+                Some(i) => {
+                    let [r, g, b] = palette.colors()[*i as usize];
+                    rgba.extend_from_slice(&[r, g, b, 255]);
                 }
+                None => rgba.extend_from_slice(&[0, 0, 0, 0]),
             }
+        }
+        let buffer = RgbaImage::from_raw(width, height, rgba).ok_or(IOError::InvalidData)?;
+        let delay = Delay::from_numer_denom_ms(delay_ms.max(0) as u32, 1);
+        encoder
+            .encode_frame(image::Frame::from_parts(buffer, 0, 0, delay))
+            .map_err(|_| IOError::EncodingFailed)?;
+    }
+    Ok(())
+}
+
+/// Writes an animated PNG with full 8-bit RGBA frames; no palette is involved.
+pub fn encode_apng(sequence: &Sequence, path: &str, options: &AnimationOptions) -> Result<(), IOError> {
+    if options.colors.is_some() || options.dither.is_some() {
+        return Err(IOError::InvalidOption("'colors' and 'dither' only apply to GIF export".into()));
+    }
+    let (width, height) = sequence_size(sequence)?;
 
-            PixelData::YUV420(y_plane, u_plane, v_plane)
+    let file = File::create(path).map_err(|_| IOError::FileNotFound)?;
+    let mut encoder = png::Encoder::new(BufWriter::new(file), width, height);
+    encoder.set_color(png::ColorType::Rgba);
+    encoder.set_depth(png::BitDepth::Eight);
+    encoder
+        .set_animated(sequence.len() as u32, options.loops.unwrap_or(0) as u32)
+        .map_err(|_| IOError::EncodingFailed)?;
+    let mut writer = encoder.write_header().map_err(|_| IOError::EncodingFailed)?;
+
+    for (vf, delay_ms) in sequence.frames.iter().zip(sequence.delays_ms()) {
+        let rgba = srgb_encoded(&vf.frame)?
+            .data()
+            .to_rgba8(width, height)
+            .map_err(|_| IOError::InvalidData)?
+            .interleave();
+        writer
+            .set_frame_delay(delay_ms.clamp(0, u16::MAX as i64) as u16, 1000)
+            .map_err(|_| IOError::EncodingFailed)?;
+        writer.write_image_data(&rgba).map_err(|_| IOError::EncodingFailed)?;
+    }
+    writer.finish().map_err(|_| IOError::EncodingFailed)
+}
+
+/// Reads a PNG a band of rows at a time for `EffectPipeline::execute_streamed`, so
+/// the decoded image is never whole in memory. Rows come out as RGBA, 16-bit for
+/// 16-bit files. Interlaced PNGs store their rows out of order and cannot be streamed.
+pub struct PngRows {
+    reader: png::Reader<BufReader<File>>,
+    width: u32,
+    height: u32,
+    channels: usize,
+    deep: bool,
+}
+
+impl PngRows {
+    pub fn open(path: &str) -> Result<Self, IOError> {
+        let file = File::open(path).map_err(|_| IOError::FileNotFound)?;
+        let mut decoder = png::Decoder::new(BufReader::new(file));
+        decoder.set_transformations(png::Transformations::EXPAND);
+        let reader = decoder.read_info().map_err(|_| IOError::InvalidData)?;
+        if reader.info().interlaced {
+            return Err(IOError::InvalidOption(format!("'{path}' is an interlaced PNG, which cannot be streamed")));
         }
+        let (color, depth) = reader.output_color_type();
+        let (width, height) = (reader.info().width, reader.info().height);
+        Ok(PngRows { reader, width, height, channels: color.samples(), deep: depth == png::BitDepth::Sixteen })
+    }
+}
 
-        _ => return Err(FrameError::InvalidPixelFormat),
+/// Appends one pixel of 1 - 4 samples (gray, gray + alpha, RGB, RGBA) to RGBA planes.
+fn push_rgba<T: Copy>(planes: &mut [Vec<T>; 4], px: &[T], opaque: T) {
+    let (rgb, a) = match px.len() {
+        1 => ([px[0]; 3], opaque),
+        2 => ([px[0]; 3], px[1]),
+        3 => ([px[0], px[1], px[2]], opaque),
+        _ => ([px[0], px[1], px[2]], px[3]),
     };
-    // println!(
-    //     "Loaded Image with \n width = {}\n height = {}\n data = {:?}",
-    //     width, height, data
-    // );
-    Frame::new(width, height, data)
+    for (plane, v) in planes.iter_mut().zip([rgb[0], rgb[1], rgb[2], a]) {
+        plane.push(v);
+    }
 }
 
-pub fn encode_image(frame: &Frame, path: &str) -> Result<(), IOError> {
-    let file = match File::create(path) {
-        Ok(val) => val,
-        Err(_) => return Err(IOError::FileNotFound),
-    };
-    let writer = BufWriter::new(file);
-    let encoder = PngEncoder::new(writer);
-    let width = frame.width();
-    let height = frame.height();
-    let data = match frame.data().to_rgba8(width, height) {
-        Ok(val) => val,
-        Err(_) => return Err(IOError::InvalidData),
-    };
+impl RowSource for PngRows {
+    fn width(&self) -> u32 {
+        self.width
+    }
 
-    encoder
-        .write_image(
-            &data.interleave()[..],
-            width,
-            height,
-            ExtendedColorType::Rgba8,
-        )
-        .map_err(|_| IOError::EncodingFailed)
+    fn height(&self) -> u32 {
+        self.height
+    }
+
+    fn read_rows(&mut self, rows: u32) -> Result<Frame, PipelineError> {
+        let count = rows as usize * self.width as usize;
+        let mut planes8: [Vec<u8>; 4] = std::array::from_fn(|_| Vec::with_capacity(if self.deep { 0 } else { count }));
+        let mut planes16: [Vec<u16>; 4] = std::array::from_fn(|_| Vec::with_capacity(if self.deep { count } else { 0 }));
+        for _ in 0..rows {
+            let row = self
+                .reader
+                .next_row()
+                .map_err(|e| PipelineError::Stream(e.to_string()))?
+                .ok_or_else(|| PipelineError::Stream("the PNG ended before its last row".into()))?;
+            if self.deep {
+                let samples: Vec<u16> = row.data().chunks_exact(2).map(|b| u16::from_be_bytes([b[0], b[1]])).collect();
+                for px in samples.chunks_exact(self.channels) {
+                    push_rgba(&mut planes16, px, u16::MAX);
+                }
+            } else {
+                for px in row.data().chunks_exact(self.channels) {
+                    push_rgba(&mut planes8, px, u8::MAX);
+                }
+            }
+        }
+        let data = if self.deep {
+            let [r, g, b, a] = planes16;
+            PixelData::RGBA16(r, g, b, a)
+        } else {
+            let [r, g, b, a] = planes8;
+            PixelData::RGBA(r, g, b, a)
+        };
+        Frame::new(self.width, rows, data).map_err(PipelineError::Frame)
+    }
+}
+
+/// Writes a PNG a band of rows at a time, as `EffectPipeline::execute_streamed`
+/// produces them, as an RGBA file of 8 bits per channel, or 16 for any deeper `depth`.
+/// The header goes out with the first band. Call `finish` after the last band.
+pub struct PngRowWriter {
+    path: String,
+    width: u32,
+    height: u32,
+    deep: bool,
+    stream: Option<png::StreamWriter<'static, BufWriter<File>>>,
+}
+
+impl PngRowWriter {
+    pub fn create(path: &str, width: u32, height: u32, depth: BitDepth) -> Self {
+        PngRowWriter { path: path.to_string(), width, height, deep: depth != BitDepth::Eight, stream: None }
+    }
+
+    pub fn finish(self) -> Result<(), IOError> {
+        let stream = self.stream.ok_or(IOError::InvalidData)?;
+        stream.finish().map_err(|_| IOError::EncodingFailed)
+    }
+}
+
+impl RowSink for PngRowWriter {
+    fn write_rows(&mut self, rows: &Frame) -> Result<(), PipelineError> {
+        let failed = |e: IOError| PipelineError::Stream(e.to_string());
+        let deep = self.deep;
+        if self.stream.is_none() {
+            let file = File::create(&self.path).map_err(|_| failed(IOError::FileNotFound))?;
+            let mut encoder = png::Encoder::new(BufWriter::new(file), self.width, self.height);
+            encoder.set_color(png::ColorType::Rgba);
+            encoder.set_depth(if deep { png::BitDepth::Sixteen } else { png::BitDepth::Eight });
+            let stream = encoder
+                .write_header()
+                .and_then(|w| w.into_stream_writer())
+                .map_err(|_| failed(IOError::EncodingFailed))?;
+            self.stream = Some(stream);
+        }
+        let Some(stream) = self.stream.as_mut() else {
+            return Err(PipelineError::InvalidData);
+        };
+
+        let depth = if deep { BitDepth::Sixteen } else { BitDepth::Eight };
+        let rows = srgb_encoded(rows)
+            .map_err(failed)?
+            .to_format(PixelFormat::RGBA)
+            .and_then(|f| f.to_depth(depth))
+            .map_err(PipelineError::Frame)?;
+        let bytes = if deep {
+            let samples = rows.data().interleave16(rows.width(), rows.height()).map_err(PipelineError::Frame)?;
+            samples.iter().flat_map(|v| v.to_be_bytes()).collect()
+        } else {
+            rows.data().interleave()
+        };
+        stream.write_all(&bytes).map_err(|e| PipelineError::Stream(e.to_string()))
+    }
+}
+
+/// Runs `pipeline` over a PNG file and writes the result as another PNG, holding
+/// only a band of `tile` rows of either image in memory at once. The result has the
+/// input's bit depth unless the pipeline has a `depth` stage.
+pub fn stream_png(input: &str, output: &str, pipeline: &EffectPipeline, tile: u32) -> Result<(), IOError> {
+    let mut source = PngRows::open(input)?;
+    let depth = pipeline.export_depth(if source.deep { BitDepth::Sixteen } else { BitDepth::Eight });
+    let mut sink = PngRowWriter::create(output, source.width(), source.height(), depth);
+    pipeline
+        .execute_streamed(&mut source, &mut sink, tile)
+        .map_err(|e| IOError::InvalidOption(format!("streaming '{input}' failed: {e:?}")))?;
+    sink.finish()
 }
 
 // fn copy_plane(
@@ -390,3 +957,237 @@ pub fn encode_wav_i16(track: &Track, path: &Path) -> Result<(), WavEncodeError>
     writer.finalize()?;
     Ok(())
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn sixteen_bit_png_round_trips() {
+        let (width, height) = (64u32, 2u32);
+        let ramp: Vec<u16> = (0..width * height).map(|i| (i % width) as u16 * 1001).collect();
+        let alpha = vec![u16::MAX; ramp.len()];
+        let frame = Frame::new(
+            width,
+            height,
+            PixelData::RGBA16(ramp.clone(), ramp.clone(), ramp.clone(), alpha),
+        )
+        .unwrap();
+
+        let path = std::env::temp_dir().join(format!("drive_rgba16_{}.png", std::process::id()));
+        let path = path.to_str().unwrap();
+        encode_image(&frame, path).unwrap();
+        let loaded = load_image(path, "rgba").unwrap();
+        let _ = std::fs::remove_file(path);
+
+        match loaded.data() {
+            PixelData::RGBA16(r, g, b, _) => {
+                assert_eq!(r, &ramp);
+                assert_eq!(g, &ramp);
+                assert_eq!(b, &ramp);
+            }
+            other => panic!("expected RGBA16, got {}", other.ffmpeg_fmt()),
+        }
+    }
+
+    #[test]
+    fn pipelines_export_at_the_source_depth_unless_widened() {
+        use crate::pipeline::pipeline::{EffectPipeline, Operation, Pipeline};
+
+        let path = std::env::temp_dir().join(format!("drive_depth_{}.png", std::process::id()));
+        let path = path.to_str().unwrap();
+        let written = |frame: &Frame, options: &ImageOptions| {
+            encode_image_with(frame, path, options).unwrap();
+            load_image(path, "rgba").unwrap()
+        };
+
+        // The pipeline runs in float, but an 8-bit frame goes back out at 8 bits.
+        let mut frame = Frame::blank(8, 8);
+        let resize = EffectPipeline { operations: vec![Operation::NativeResize { width: 4, height: 4 }] };
+        resize.execute(&mut frame).unwrap();
+        assert!(frame.data().is_float());
+        assert!(matches!(written(&frame, &ImageOptions::default()).data(), PixelData::RGBA(..)));
+
+        let sixteen = ImageOptions { depth: Some(BitDepth::Sixteen), ..Default::default() };
+        assert!(matches!(written(&frame, &sixteen).data(), PixelData::RGBA16(..)));
+
+        let mut widened = Frame::blank(8, 8);
+        let depth = EffectPipeline { operations: vec![Operation::NativeDepth { depth: BitDepth::Float }] };
+        depth.execute(&mut widened).unwrap();
+        assert!(matches!(written(&widened, &ImageOptions::default()).data(), PixelData::RGBA16(..)));
+        let _ = std::fs::remove_file(path);
+
+        let jpeg = std::env::temp_dir().join(format!("drive_depth_{}.jpg", std::process::id()));
+        assert!(matches!(
+            encode_image_with(&frame, jpeg.to_str().unwrap(), &sixteen),
+            Err(IOError::InvalidOption(_))
+        ));
+    }
+
+    #[test]
+    fn export_format_follows_extension() {
+        assert_eq!(ImageFormatKind::from_path("a/out.JPG").unwrap(), ImageFormatKind::Jpeg);
+        assert_eq!(ImageFormatKind::from_path("out.tif").unwrap(), ImageFormatKind::Tiff);
+        assert_eq!(ImageFormatKind::from_path("out").unwrap(), ImageFormatKind::Png);
+        assert!(matches!(
+            ImageFormatKind::from_path("out.gif2"),
+            Err(IOError::UnsupportedFormat(_))
+        ));
+
+        let frame = Frame::blank(4, 4);
+        let path = std::env::temp_dir().join(format!("drive_opts_{}.png", std::process::id()));
+        let options = ImageOptions { quality: Some(80), ..Default::default() };
+        assert!(matches!(
+            encode_image_with(&frame, path.to_str().unwrap(), &options),
+            Err(IOError::InvalidOption(_))
+        ));
+    }
+
+    #[test]
+    fn every_extension_writes_its_own_format_and_round_trips() {
+        use crate::media::compare::{self, Tolerance};
+
+        let ramp = |k: usize| (0..64).map(|i| ((i % 8) * 32 + (i / 8) * k) as u8).collect::<Vec<u8>>();
+        let frame = Frame::new(8, 8, PixelData::RGBA(ramp(3), ramp(1), ramp(2), vec![255; 64])).unwrap();
+        let formats: [(&str, &[u8]); 7] = [
+            ("png", b"\x89PNG\r\n\x1a\n"),
+            ("jpg", &[0xFF, 0xD8, 0xFF]),
+            ("webp", b"RIFF"),
+            ("bmp", b"BM"),
+            ("tif", b"II*\0"),
+            // TGA has no magic; no ID, no colour map, run-length truecolour.
+            ("tga", &[0, 0, 10]),
+            ("qoi", b"qoif"),
+        ];
+        for (ext, magic) in formats {
+            let path = std::env::temp_dir().join(format!("drive_magic_{}.{ext}", std::process::id()));
+            let path = path.to_str().unwrap();
+            encode_image(&frame, path).unwrap();
+            let bytes = std::fs::read(path).unwrap();
+            assert!(bytes.starts_with(magic), "{ext} starts with {:02X?}", &bytes[..4]);
+            if ext == "webp" {
+                assert_eq!(&bytes[8..12], b"WEBP");
+            }
+
+            let loaded = load_image(path, "rgba").unwrap();
+            let tolerance = if ext == "jpg" { Tolerance::max_diff(255.0).with_min_psnr(30.0) } else { Tolerance::default() };
+            compare::matches(&loaded, &frame, &tolerance).unwrap_or_else(|e| panic!("{ext}: {e:?}"));
+
+            // Each setting is only taken by the format it belongs to.
+            let quality = ImageOptions { quality: Some(80), ..Default::default() };
+            let compression = ImageOptions { compression: Some(9), ..Default::default() };
+            assert_eq!(encode_image_with(&frame, path, &quality).is_ok(), ext == "jpg", "{ext} quality");
+            assert_eq!(encode_image_with(&frame, path, &compression).is_ok(), ext == "png", "{ext} compression");
+            let _ = std::fs::remove_file(path);
+        }
+    }
+
+    #[test]
+    fn exif_orientation_is_applied_and_metadata_survives_export() {
+        // Big-endian EXIF with a single Orientation = 6 (rotate 90 degrees clockwise).
+        let exif = vec![0x4D, 0x4D, 0, 42, 0, 0, 0, 8, 0, 1, 0x01, 0x12, 0, 3, 0, 0, 0, 1, 0, 6, 0, 0, 0, 0, 0, 0, 0, 0];
+        let mut frame = Frame::blank(4, 2);
+        frame.set_metadata(Some(Arc::new(ImageMetadata { exif: Some(exif), ..Default::default() })));
+
+        let dir = std::env::temp_dir();
+        let src = dir.join(format!("drive_exif_{}.png", std::process::id()));
+        let src = src.to_str().unwrap();
+        encode_image(&frame, src).unwrap();
+
+        let rotated = load_image(src, "rgba").unwrap();
+        assert_eq!((rotated.width(), rotated.height()), (2, 4));
+        let kept = rotated.metadata().and_then(|m| m.exif_field("Orientation"));
+        assert_eq!(kept, Some(crate::media::metadata::ExifValue::Numbers(vec![1.0])));
+
+        let raw = load_image_with(src, "rgba", &LoadOptions { auto_orient: false }).unwrap();
+        assert_eq!((raw.width(), raw.height()), (4, 2));
+
+        let stripped = dir.join(format!("drive_exif_strip_{}.png", std::process::id()));
+        let stripped = stripped.to_str().unwrap();
+        let options = ImageOptions { metadata: MetadataPolicy::Strip, ..Default::default() };
+        encode_image_with(&raw, stripped, &options).unwrap();
+        assert!(load_image(stripped, "rgba").unwrap().metadata().is_none());
+
+        // BMP has nowhere to put EXIF: dropped by default, refused when asked to keep it.
+        let bmp = dir.join(format!("drive_exif_{}.bmp", std::process::id()));
+        let bmp = bmp.to_str().unwrap();
+        encode_image(&raw, bmp).unwrap();
+        let keep = ImageOptions { metadata: MetadataPolicy::Keep, ..Default::default() };
+        assert!(matches!(encode_image_with(&raw, bmp, &keep), Err(IOError::InvalidOption(_))));
+        let _ = std::fs::remove_file(bmp);
+
+        let _ = std::fs::remove_file(src);
+        let _ = std::fs::remove_file(stripped);
+    }
+
+    #[test]
+    fn gif_and_apng_round_trip_frames_and_delays() {
+        let mut sequence = Sequence::new();
+        for (i, delay) in [(0u8, 100), (1, 250), (2, 40)] {
+            let n = 16 * 8;
+            let ramp: Vec<u8> = (0..n).map(|p| (p % 16) as u8 * 16).collect();
+            let mut alpha = vec![255u8; n];
+            alpha[0] = 0;
+            let data = PixelData::RGBA(ramp.clone(), vec![i * 100; n], ramp, alpha);
+            sequence.push(Frame::new(16, 8, data).unwrap(), delay);
+        }
+
+        let dir = std::env::temp_dir();
+        for ext in ["gif", "png"] {
+            let path = dir.join(format!("drive_anim_{}.{ext}", std::process::id()));
+            let path = path.to_str().unwrap();
+            if ext == "gif" {
+                let options = AnimationOptions { colors: Some(16), dither: Some(Dither::FloydSteinberg), ..Default::default() };
+                encode_gif(&sequence, path, &options).unwrap();
+            } else {
+                encode_apng(&sequence, path, &AnimationOptions::default()).unwrap();
+            }
+            let decoded = decode_animation(path).unwrap();
+            let _ = std::fs::remove_file(path);
+
+            assert_eq!(decoded.len(), 3, "{ext}");
+            assert_eq!(decoded.delays_ms(), vec![100, 250, 40], "{ext}");
+            let corner = decoded.frames[1].frame.get_pixel(&crate::media::frame::Pos(0, 0)).unwrap();
+            assert_eq!(corner.channels_f32()[3], 0.0, "{ext} keeps transparency");
+        }
+    }
+
+    #[test]
+    fn streamed_png_matches_the_loaded_result() {
+        use crate::media::compare::{self, Tolerance};
+        use crate::media::generate::{Noise, NoiseKind};
+        use crate::pipeline::kernel::Kernel;
+        use crate::pipeline::pipeline::{Operation, Pipeline};
+
+        let source = Noise { kind: NoiseKind::Worley, scale: 5.0, seed: 9, octaves: 1 }
+            .render(70, 45)
+            .and_then(|f| f.to_depth(BitDepth::Eight))
+            .unwrap();
+        let dir = std::env::temp_dir();
+        let input = dir.join(format!("drive_stream_in_{}.png", std::process::id()));
+        let output = dir.join(format!("drive_stream_out_{}.png", std::process::id()));
+        let (input, output) = (input.to_str().unwrap(), output.to_str().unwrap());
+        encode_image(&source, input).unwrap();
+
+        let pipeline = EffectPipeline {
+            operations: vec![Operation::Convolution { kernel: Kernel::generate_blur("blur", 3), mask: None }],
+        };
+        stream_png(input, output, &pipeline, 16).unwrap();
+        let streamed = load_image(output, "rgba").unwrap();
+        let mut expected = load_image(input, "rgba").unwrap();
+        pipeline.execute(&mut expected).unwrap();
+
+        // Processing runs in float, but an 8-bit source is written back at 8 bits,
+        // whether streamed or exported whole, unless a `depth` stage widens it.
+        assert!(matches!(streamed.data(), PixelData::RGBA(..)));
+        compare::matches(&streamed, &expected, &Tolerance::max_diff(0.5)).unwrap();
+        encode_image(&expected, output).unwrap();
+        assert!(matches!(load_image(output, "rgba").unwrap().data(), PixelData::RGBA(..)));
+
+        let widened = EffectPipeline { operations: vec![Operation::NativeDepth { depth: BitDepth::Sixteen }] };
+        stream_png(input, output, &widened, 16).unwrap();
+        assert!(matches!(load_image(output, "rgba").unwrap().data(), PixelData::RGBA16(..)));
+        let _ = std::fs::remove_file(input);
+        let _ = std::fs::remove_file(output);
+    }
+}
@@ -47,6 +47,7 @@ pub enum TokenKind {
     Comma,
     DotDot,
     DoubleColon,
+    Colon,
     Arrow,
     Plus,
     Minus,
@@ -210,11 +211,12 @@ pub fn lexer(source: &str) -> Result<Vec<Token>, LexError> {
                         });
                         i += 2;
                     } else {
-                        return Err(LexError::InvalidCharacter {
-                            ch: ':',
+                        tokens.push(Token {
+                            kind: TokenKind::Colon,
+                            value: ":".to_string(),
                             line,
-                            message: "Unexpected ':' — did you mean '::'?".to_string(),
                         });
+                        i += 1;
                     }
                 }
                 '=' => {
@@ -363,8 +365,10 @@ pub fn lexer(source: &str) -> Result<Vec<Token>, LexError> {
                     if c == '\n' {
                         line += 1;
                     }
-                    buffer.push(c);
-                    i += 1;
+                    // Take whole UTF-8 characters so non-ASCII text survives into strings.
+                    let ch = source[i..].chars().next().unwrap_or(c);
+                    buffer.push(ch);
+                    i += ch.len_utf8();
                 }
             }
             State::Number => {
@@ -505,6 +509,24 @@ mod tests {
         );
     }
 
+    #[test]
+    fn lex_state_as_identifier() {
+        // The parser decides where `state` declares a variable.
+        let toks = lexer("let state = 1;").unwrap();
+        let kinds: Vec<_> = toks.iter().map(|t| t.kind.clone()).collect();
+        assert_eq!(
+            kinds,
+            vec![
+                TokenKind::Let,
+                TokenKind::Identifier,
+                TokenKind::Equal,
+                TokenKind::Int,
+                TokenKind::SemiColon,
+                TokenKind::EOF
+            ]
+        );
+    }
+
     #[test]
     fn lex_as_keyword() {
         let toks = lexer("import \"f.drive\" as filt;").unwrap();
@@ -521,4 +543,19 @@ mod tests {
             ]
         );
     }
+
+    #[test]
+    fn lex_single_colon_for_named_args() {
+        let toks = lexer("quality: 85").unwrap();
+        let kinds: Vec<_> = toks.iter().map(|t| t.kind.clone()).collect();
+        assert_eq!(
+            kinds,
+            vec![
+                TokenKind::Identifier,
+                TokenKind::Colon,
+                TokenKind::Int,
+                TokenKind::EOF
+            ]
+        );
+    }
 }
@@ -0,0 +1,245 @@
+use std::fmt;
+
+use crate::media::frame::{Frame, FrameError};
+use crate::pipeline::plane::gaussian_blur;
+
+// ── Frame comparison ─────────────────────────────────────────────────────────
+//
+// Frames are compared as sRGB with premultiplied colour, so pixels that differ
+// only in the colour of fully transparent areas count as equal, and a linear
+// frame compares equal to the same image encoded back to sRGB. Any two layouts
+// and depths can be compared; the frames must be the same size.
+
+/// SSIM window: gaussian sigma of the standard 11×11 window, in pixels.
+const SSIM_SIGMA: f32 = 1.5;
+const SSIM_C1: f32 = 0.01 * 0.01;
+const SSIM_C2: f32 = 0.03 * 0.03;
+
+/// Premultiplied sRGB planes on the 0.0 - 1.0 scale, alpha last.
+fn comparable_planes(frame: &Frame) -> Result<[Vec<f32>; 4], FrameError> {
+    let [mut r, mut g, mut b, a] = if frame.is_linear() { frame.to_srgb()? } else { frame.clone() }.rgba_planes()?;
+    for i in 0..a.len() {
+        let alpha = a[i].clamp(0.0, 1.0);
+        r[i] *= alpha;
+        g[i] *= alpha;
+        b[i] *= alpha;
+    }
+    Ok([r, g, b, a])
+}
+
+fn planes_of(a: &Frame, b: &Frame) -> Result<([Vec<f32>; 4], [Vec<f32>; 4]), FrameError> {
+    if a.width() != b.width() || a.height() != b.height() {
+        return Err(FrameError::SizeMismatch((a.width(), a.height()), (b.width(), b.height())));
+    }
+    Ok((comparable_planes(a)?, comparable_planes(b)?))
+}
+
+/// Largest difference of any channel of any pixel, on the 0 - 255 scale.
+pub fn max_abs_diff(a: &Frame, b: &Frame) -> Result<f32, FrameError> {
+    let (pa, pb) = planes_of(a, b)?;
+    let max = pa
+        .iter()
+        .zip(pb.iter())
+        .flat_map(|(x, y)| x.iter().zip(y.iter()).map(|(x, y)| (x - y).abs()))
+        .fold(0.0f32, f32::max);
+    Ok(max * 255.0)
+}
+
+/// Peak signal-to-noise ratio over all four channels, in dB. Identical frames give
+/// infinity; 40 dB and up is hard to tell apart by eye.
+pub fn psnr(a: &Frame, b: &Frame) -> Result<f64, FrameError> {
+    let (pa, pb) = planes_of(a, b)?;
+    let count = pa.iter().map(Vec::len).sum::<usize>().max(1);
+    let squared: f64 = pa
+        .iter()
+        .zip(pb.iter())
+        .flat_map(|(x, y)| x.iter().zip(y.iter()).map(|(x, y)| ((x - y) as f64).powi(2)))
+        .sum();
+    let mse = squared / count as f64;
+    Ok(if mse == 0.0 { f64::INFINITY } else { 10.0 * (1.0 / mse).log10() })
+}
+
+/// Mean structural similarity of the luma, with the usual gaussian window:
+/// 1 for identical frames, lower as structure differs.
+pub fn ssim(a: &Frame, b: &Frame) -> Result<f64, FrameError> {
+    let (pa, pb) = planes_of(a, b)?;
+    let (width, height) = (a.width() as usize, a.height() as usize);
+    let luma = |planes| Frame::from_float_planes(a.width(), a.height(), planes)?.luma_plane();
+    let (x, y) = (luma(pa)?, luma(pb)?);
+    let blur = |plane: Vec<f32>| gaussian_blur(&plane, width, height, SSIM_SIGMA);
+    let mu_x = blur(x.clone());
+    let mu_y = blur(y.clone());
+    let xx = blur(x.iter().map(|v| v * v).collect());
+    let yy = blur(y.iter().map(|v| v * v).collect());
+    let xy = blur(x.iter().zip(y.iter()).map(|(a, b)| a * b).collect());
+
+    let total: f64 = (0..x.len())
+        .map(|i| {
+            let (mx, my) = (mu_x[i], mu_y[i]);
+            let var_x = (xx[i] - mx * mx).max(0.0);
+            let var_y = (yy[i] - my * my).max(0.0);
+            let cov = xy[i] - mx * my;
+            let numerator = (2.0 * mx * my + SSIM_C1) * (2.0 * cov + SSIM_C2);
+            let denominator = (mx * mx + my * my + SSIM_C1) * (var_x + var_y + SSIM_C2);
+            (numerator / denominator) as f64
+        })
+        .sum();
+    Ok(total / x.len().max(1) as f64)
+}
+
+/// Opaque frame of the per-channel absolute difference; alpha differences show as gray.
+pub fn diff_image(a: &Frame, b: &Frame) -> Result<Frame, FrameError> {
+    let (pa, pb) = planes_of(a, b)?;
+    let diff: Vec<Vec<f32>> = pa.iter().zip(pb.iter()).map(|(x, y)| x.iter().zip(y.iter()).map(|(x, y)| (x - y).abs()).collect()).collect();
+    let planes = [0, 1, 2].map(|c| (0..diff[c].len()).map(|i| diff[c][i].max(diff[3][i])).collect::<Vec<f32>>());
+    let [r, g, b] = planes;
+    let opaque = vec![1.0; r.len()];
+    Frame::from_rgba_planes(a.width(), a.height(), [r, g, b, opaque])
+}
+
+/// How far two frames may drift apart and still count as matching. Every limit
+/// must hold; the default is an exact match.
+#[derive(Debug, Clone, Copy)]
+pub struct Tolerance {
+    /// Largest allowed channel difference, 0 - 255.
+    pub max_abs_diff: f32,
+    /// Lowest allowed PSNR in dB.
+    pub min_psnr: f64,
+    /// Lowest allowed SSIM.
+    pub min_ssim: f64,
+}
+
+impl Default for Tolerance {
+    fn default() -> Self {
+        Self { max_abs_diff: 0.0, min_psnr: f64::INFINITY, min_ssim: 1.0 }
+    }
+}
+
+impl Tolerance {
+    /// Allows channels to differ by up to `max_abs_diff` and checks nothing else,
+    /// e.g. `Tolerance::max_diff(1.0)` for rounding noise.
+    pub fn max_diff(max_abs_diff: f32) -> Self {
+        Self { max_abs_diff, min_psnr: 0.0, min_ssim: f64::NEG_INFINITY }
+    }
+
+    pub fn with_min_psnr(self, min_psnr: f64) -> Self {
+        Self { min_psnr, ..self }
+    }
+
+    pub fn with_min_ssim(self, min_ssim: f64) -> Self {
+        Self { min_ssim, ..self }
+    }
+}
+
+/// All three metrics for a pair of frames.
+#[derive(Debug, Clone, Copy)]
+pub struct Comparison {
+    pub max_abs_diff: f32,
+    pub psnr: f64,
+    pub ssim: f64,
+}
+
+impl Comparison {
+    pub fn of(actual: &Frame, expected: &Frame) -> Result<Comparison, FrameError> {
+        Ok(Comparison {
+            max_abs_diff: max_abs_diff(actual, expected)?,
+            psnr: psnr(actual, expected)?,
+            ssim: ssim(actual, expected)?,
+        })
+    }
+
+    /// Whether every metric is inside the tolerance. SSIM is allowed a hair of
+    /// float error so identical frames pass an exact tolerance.
+    pub fn within(&self, tolerance: &Tolerance) -> bool {
+        self.max_abs_diff <= tolerance.max_abs_diff
+            && self.psnr >= tolerance.min_psnr
+            && self.ssim >= tolerance.min_ssim - 1e-9
+    }
+}
+
+#[derive(Debug)]
+pub enum CompareError {
+    Frame(FrameError),
+    /// The frames compared but fell outside the tolerance.
+    Mismatch { comparison: Comparison, tolerance: Tolerance },
+}
+
+impl fmt::Display for CompareError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            CompareError::Frame(e) => write!(f, "{e}"),
+            CompareError::Mismatch { comparison: c, tolerance: t } => write!(
+                f,
+                "frames differ: max abs diff {:.2} (allowed {:.2}), PSNR {:.2} dB (need {:.2}), SSIM {:.5} (need {:.5})",
+                c.max_abs_diff, t.max_abs_diff, c.psnr, t.min_psnr, c.ssim, t.min_ssim
+            ),
+        }
+    }
+}
+
+impl std::error::Error for CompareError {}
+
+/// Compares `actual` against a reference frame, for golden-image tests:
+///
+/// ```ignore
+/// let expected = io::load_image("tests/golden/blur.png", "rgba")?;
+/// compare::matches(&output, &expected, &Tolerance::max_diff(1.0)).unwrap();
+/// ```
+pub fn matches(actual: &Frame, expected: &Frame, tolerance: &Tolerance) -> Result<Comparison, CompareError> {
+    let comparison = Comparison::of(actual, expected).map_err(CompareError::Frame)?;
+    if comparison.within(tolerance) {
+        Ok(comparison)
+    } else {
+        Err(CompareError::Mismatch { comparison, tolerance: *tolerance })
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::io::io;
+    use crate::media::frame::Color;
+    use crate::media::generate;
+    use crate::pipeline::kernel::Kernel;
+    use crate::pipeline::pipeline::{EffectPipeline, Operation, Pipeline};
+
+    #[test]
+    fn metrics_separate_identical_nudged_and_mismatched_frames() {
+        let light = Color::RGBA(200, 200, 200, 255);
+        let dark = Color::RGBA(40, 40, 40, 255);
+        let a = generate::checkerboard(32, 32, 4, light, dark).unwrap();
+        let same = compare_exact(&a, &a);
+        assert_eq!(same.max_abs_diff, 0.0);
+        assert!(same.psnr.is_infinite());
+
+        let nudged = generate::checkerboard(32, 32, 4, Color::RGBA(201, 200, 200, 255), dark).unwrap();
+        let near = Comparison::of(&nudged, &a).unwrap();
+        assert!((near.max_abs_diff - 1.0).abs() < 0.01 && near.psnr > 50.0 && near.ssim > 0.999);
+        assert!(matches(&nudged, &a, &Tolerance::default()).is_err());
+        assert!(matches(&nudged, &a, &Tolerance::max_diff(1.0).with_min_ssim(0.99)).is_ok());
+
+        let flat = generate::solid(32, 32, light).unwrap();
+        assert!(ssim(&flat, &a).unwrap() < 0.5);
+        assert!(matches!(
+            psnr(&a, &generate::solid(16, 32, light).unwrap()),
+            Err(FrameError::SizeMismatch((32, 32), (16, 32)))
+        ));
+    }
+
+    #[test]
+    fn box_blur_matches_the_golden_image() {
+        let mut frame = generate::checkerboard(32, 32, 8, Color::RGBA(255, 255, 255, 255), Color::RGBA(0, 0, 0, 255)).unwrap();
+        let pipeline = EffectPipeline {
+            operations: vec![Operation::Convolution { kernel: Kernel::generate_blur("box", 3), mask: None }],
+        };
+        pipeline.execute(&mut frame).unwrap();
+
+        let golden = concat!(env!("CARGO_MANIFEST_DIR"), "/tests/golden/checker_box_blur.png");
+        let expected = io::load_image(golden, "rgba").unwrap();
+        matches(&frame, &expected, &Tolerance::max_diff(1.0)).unwrap();
+    }
+
+    fn compare_exact(a: &Frame, b: &Frame) -> Comparison {
+        matches(a, b, &Tolerance::default()).unwrap()
+    }
+}
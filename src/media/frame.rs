@@ -1,4 +1,7 @@
 use std::fmt;
+use std::sync::Arc;
+
+use crate::media::metadata::ImageMetadata;
 
 use std::cmp::max;
 
@@ -9,19 +12,23 @@ pub enum Color {
     RGB(u8, u8, u8),
     RGBA(u8, u8, u8, u8),
     Gray(u8),
+    /// RGBA on the same 0.0 - 255.0 scale as the 8-bit variants, but unclamped.
+    /// This is what high-bit-depth and float frames hand out and accept.
+    RGBAF(f32, f32, f32, f32),
 }
 impl Color {
     pub fn size(&self) -> usize {
         match *self {
             Self::RGB(..) => 3,
             Self::Gray(_) => 1,
-            Self::RGBA(..) => 4,
+            Self::RGBA(..) | Self::RGBAF(..) => 4,
         }
     }
     pub fn r(&self) -> u8 {
         match self {
             Self::RGB(r, _, _) => *r,
             Self::RGBA(r, _, _, _) => *r,
+            Self::RGBAF(r, _, _, _) => r.clamp(0.0, 255.0).round() as u8,
             _ => 0,
         }
     }
@@ -29,6 +36,7 @@ impl Color {
         match self {
             Self::RGB(_, g, _) => *g,
             Self::RGBA(_, g, _, _) => *g,
+            Self::RGBAF(_, g, _, _) => g.clamp(0.0, 255.0).round() as u8,
             _ => 0,
         }
     }
@@ -36,35 +44,249 @@ impl Color {
         match self {
             Self::RGB(_, _, b) => *b,
             Self::RGBA(_, _, b, _) => *b,
+            Self::RGBAF(_, _, b, _) => b.clamp(0.0, 255.0).round() as u8,
             _ => 0,
         }
     }
+    /// All four channels as floats on the 0.0 - 255.0 scale; missing alpha reads as opaque.
+    pub fn channels_f32(&self) -> [f32; 4] {
+        match *self {
+            Color::Gray(v) => [v as f32, v as f32, v as f32, 255.0],
+            Color::RGB(r, g, b) => [r as f32, g as f32, b as f32, 255.0],
+            Color::RGBA(r, g, b, a) => [r as f32, g as f32, b as f32, a as f32],
+            Color::RGBAF(r, g, b, a) => [r, g, b, a],
+        }
+    }
     pub fn to_rgba(self) -> Color {
         match self {
-            Color::Gray(v) => Color::RGBA(v, v, v, v),
+            Color::Gray(v) => Color::RGBA(v, v, v, 255),
             Color::RGB(r, g, b) => Color::RGBA(r, g, b, 255),
             Color::RGBA(r, g, b, a) => Color::RGBA(r, g, b, a),
+            Color::RGBAF(r, g, b, a) => {
+                let q = |v: f32| v.clamp(0.0, 255.0).round() as u8;
+                Color::RGBA(q(r), q(g), q(b), q(a))
+            }
         }
     }
 }
 #[derive(Debug, Clone, Copy)]
 pub struct Pos(pub u32, pub u32);
 
-#[derive(Debug, Clone, PartialEq, Eq, PartialOrd, Ord)]
+/// Channel layout of a frame, independent of its bit depth.
+/// Float frames are always RGBA.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum PixelFormat {
+    Gray,
+    RGB,
+    RGBA,
+    YUV420,
+}
+impl fmt::Display for PixelFormat {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let name = match self {
+            PixelFormat::Gray => "gray",
+            PixelFormat::RGB => "rgb",
+            PixelFormat::RGBA => "rgba",
+            PixelFormat::YUV420 => "yuv420",
+        };
+        write!(f, "{name}")
+    }
+}
+
+#[derive(Debug, Clone, PartialEq, PartialOrd)]
 /// PixelData store the actual Data of a Frame
 pub enum PixelData {
     RGB(Vec<u8>, Vec<u8>, Vec<u8>),
     RGBA(Vec<u8>, Vec<u8>, Vec<u8>, Vec<u8>),
     GRAY(Vec<u8>),
     YUV420(Vec<u8>, Vec<u8>, Vec<u8>),
+    /// 16 bits per channel, full range 0 - 65535.
+    RGB16(Vec<u16>, Vec<u16>, Vec<u16>),
+    RGBA16(Vec<u16>, Vec<u16>, Vec<u16>, Vec<u16>),
+    GRAY16(Vec<u16>),
+    /// Float planes, nominally 0.0 - 1.0. Colour may leave that range between
+    /// pipeline stages and is only clamped when the frame is quantised.
+    RGBAF32(Vec<f32>, Vec<f32>, Vec<f32>, Vec<f32>),
+}
+
+/// Storage precision of a frame's channels.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum BitDepth {
+    Eight,
+    Sixteen,
+    Float,
+}
+
+fn quantize8(v: f32) -> u8 {
+    (v.clamp(0.0, 1.0) * 255.0).round() as u8
+}
+
+fn quantize16(v: f32) -> u16 {
+    (v.clamp(0.0, 1.0) * 65535.0).round() as u16
+}
+
+fn to_u8_plane(plane: &[u16]) -> Vec<u8> {
+    plane.iter().map(|&v| ((v as u32 + 128) / 257) as u8).collect()
+}
+
+fn to_u16_plane(plane: &[u8]) -> Vec<u16> {
+    plane.iter().map(|&v| v as u16 * 257).collect()
+}
+
+/// BT.601 luma of normalised or 8-bit-scale RGB; gray storage always uses this.
+fn luma(r: f32, g: f32, b: f32) -> f32 {
+    0.299 * r + 0.587 * g + 0.114 * b
+}
+
+/// Limited-range BT.601 YCbCr to RGB, the inverse of `rgb_to_yuv`.
+fn yuv_to_rgb(y: u8, u: u8, v: u8) -> [u8; 3] {
+    let c = (y as i32 - 16).max(0);
+    let d = u as i32 - 128;
+    let e = v as i32 - 128;
+    let r = (298 * c + 409 * e + 128) >> 8;
+    let g = (298 * c - 100 * d - 208 * e + 128) >> 8;
+    let b = (298 * c + 516 * d + 128) >> 8;
+    [r, g, b].map(|v| v.clamp(0, 255) as u8)
+}
+
+/// How a colour combines with the colour underneath it. These are the separable
+/// modes of the W3C compositing spec, applied per channel to normalised values.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub enum BlendMode {
+    #[default]
+    Normal,
+    Multiply,
+    Screen,
+    Overlay,
+    Darken,
+    Lighten,
+    Add,
+    Subtract,
+    Difference,
+    HardLight,
+    SoftLight,
+}
+
+impl BlendMode {
+    /// Parses the names scripts use, e.g. `"multiply"`, `"hard_light"`.
+    pub fn from_name(name: &str) -> Option<BlendMode> {
+        match name.to_lowercase().replace('-', "_").as_str() {
+            "normal" => Some(BlendMode::Normal),
+            "multiply" => Some(BlendMode::Multiply),
+            "screen" => Some(BlendMode::Screen),
+            "overlay" => Some(BlendMode::Overlay),
+            "darken" => Some(BlendMode::Darken),
+            "lighten" => Some(BlendMode::Lighten),
+            "add" => Some(BlendMode::Add),
+            "subtract" => Some(BlendMode::Subtract),
+            "difference" => Some(BlendMode::Difference),
+            "hard_light" | "hardlight" => Some(BlendMode::HardLight),
+            "soft_light" | "softlight" => Some(BlendMode::SoftLight),
+            _ => None,
+        }
+    }
+
+    /// Blends one channel: `dst` is underneath, `src` on top.
+    pub fn apply(self, dst: f32, src: f32) -> f32 {
+        let hard_light = |d: f32, s: f32| {
+            if s <= 0.5 { d * 2.0 * s } else { d + (2.0 * s - 1.0) - d * (2.0 * s - 1.0) }
+        };
+        match self {
+            BlendMode::Normal => src,
+            BlendMode::Multiply => dst * src,
+            BlendMode::Screen => dst + src - dst * src,
+            BlendMode::Overlay => hard_light(src, dst),
+            BlendMode::Darken => dst.min(src),
+            BlendMode::Lighten => dst.max(src),
+            BlendMode::Add => (dst + src).min(1.0),
+            BlendMode::Subtract => (dst - src).max(0.0),
+            BlendMode::Difference => (dst - src).abs(),
+            BlendMode::HardLight => hard_light(dst, src),
+            BlendMode::SoftLight => {
+                if src <= 0.5 {
+                    dst - (1.0 - 2.0 * src) * dst * (1.0 - dst)
+                } else {
+                    let d = if dst <= 0.25 { ((16.0 * dst - 12.0) * dst + 4.0) * dst } else { dst.sqrt() };
+                    dst + (2.0 * src - 1.0) * (d - dst)
+                }
+            }
+        }
+    }
+}
+
+/// RGB on the 0 - 255 scale to limited-range BT.601 YCbCr (Y 16 - 235, chroma 16 - 240).
+fn rgb_to_yuv(r: f32, g: f32, b: f32) -> [f32; 3] {
+    [
+        16.0 + 0.257 * r + 0.504 * g + 0.098 * b,
+        128.0 - 0.148 * r - 0.291 * g + 0.439 * b,
+        128.0 + 0.439 * r - 0.368 * g - 0.071 * b,
+    ]
+}
+
+/// sRGB transfer function, decoding to linear light. Out-of-range float colour is
+/// mirrored around zero so it survives a round trip.
+fn srgb_to_linear(v: f32) -> f32 {
+    let a = v.abs();
+    let l = if a <= 0.04045 { a / 12.92 } else { ((a + 0.055) / 1.055).powf(2.4) };
+    l.copysign(v)
+}
+
+fn linear_to_srgb(v: f32) -> f32 {
+    let a = v.abs();
+    let s = if a <= 0.0031308 { a * 12.92 } else { 1.055 * a.powf(1.0 / 2.4) - 0.055 };
+    s.copysign(v)
+}
+
+fn to_f32_plane<T: Copy + Into<f32>>(plane: &[T], max: f32) -> Vec<f32> {
+    plane.iter().map(|&v| v.into() / max).collect()
+}
+
+/// Applies `$body` to every plane of a non-YUV `PixelData`, keeping the variant.
+/// `$p` is bound to each plane in turn, so the body can call generic plane helpers.
+macro_rules! map_planes {
+    ($data:expr, |$p:ident| $body:expr) => {
+        match $data {
+            PixelData::GRAY($p) => Ok(PixelData::GRAY($body)),
+            PixelData::RGB(r, g, b) => Ok(PixelData::RGB(
+                { let $p = r; $body },
+                { let $p = g; $body },
+                { let $p = b; $body },
+            )),
+            PixelData::RGBA(r, g, b, a) => Ok(PixelData::RGBA(
+                { let $p = r; $body },
+                { let $p = g; $body },
+                { let $p = b; $body },
+                { let $p = a; $body },
+            )),
+            PixelData::GRAY16($p) => Ok(PixelData::GRAY16($body)),
+            PixelData::RGB16(r, g, b) => Ok(PixelData::RGB16(
+                { let $p = r; $body },
+                { let $p = g; $body },
+                { let $p = b; $body },
+            )),
+            PixelData::RGBA16(r, g, b, a) => Ok(PixelData::RGBA16(
+                { let $p = r; $body },
+                { let $p = g; $body },
+                { let $p = b; $body },
+                { let $p = a; $body },
+            )),
+            PixelData::RGBAF32(r, g, b, a) => Ok(PixelData::RGBAF32(
+                { let $p = r; $body },
+                { let $p = g; $body },
+                { let $p = b; $body },
+                { let $p = a; $body },
+            )),
+            PixelData::YUV420(..) => Err(FrameError::YUVNotApplied),
+        }
+    };
 }
 
 impl PixelData {
     pub fn bytes_per_pixel(&self) -> usize {
         match self {
-            PixelData::RGB(..) => 3,
-            PixelData::GRAY(_) => 1,
-            PixelData::RGBA(..) => 4,
+            PixelData::RGB(..) | PixelData::RGB16(..) => 3,
+            PixelData::GRAY(_) | PixelData::GRAY16(_) => 1,
+            PixelData::RGBA(..) | PixelData::RGBA16(..) | PixelData::RGBAF32(..) => 4,
             _ => 0,
         }
     }
@@ -74,14 +296,44 @@ impl PixelData {
             PixelData::GRAY(_) => "gray",
             PixelData::RGBA(..) => "rgba",
             PixelData::YUV420(..) => "yuv420p",
+            PixelData::RGB16(..) => "rgb48le",
+            PixelData::RGBA16(..) => "rgba64le",
+            PixelData::GRAY16(_) => "gray16le",
+            PixelData::RGBAF32(..) => "rgbaf32le",
+        }
+    }
+    pub fn format(&self) -> PixelFormat {
+        match self {
+            PixelData::GRAY(_) | PixelData::GRAY16(_) => PixelFormat::Gray,
+            PixelData::RGB(..) | PixelData::RGB16(..) => PixelFormat::RGB,
+            PixelData::RGBA(..) | PixelData::RGBA16(..) | PixelData::RGBAF32(..) => PixelFormat::RGBA,
+            PixelData::YUV420(..) => PixelFormat::YUV420,
+        }
+    }
+    pub fn depth(&self) -> BitDepth {
+        match self {
+            PixelData::RGB16(..) | PixelData::RGBA16(..) | PixelData::GRAY16(_) => BitDepth::Sixteen,
+            PixelData::RGBAF32(..) => BitDepth::Float,
+            _ => BitDepth::Eight,
         }
     }
+    pub fn is_float(&self) -> bool {
+        self.depth() == BitDepth::Float
+    }
+    /// True for anything stored with more than 8 bits per channel.
+    pub fn is_deep(&self) -> bool {
+        self.depth() != BitDepth::Eight
+    }
     pub fn len(&self) -> usize {
         match self {
             PixelData::RGB(d, _, _) => d.len(),
             PixelData::GRAY(d) => d.len(),
             PixelData::RGBA(d, _, _, _) => d.len(),
             PixelData::YUV420(y, _, _) => y.len(),
+            PixelData::RGB16(d, _, _) => d.len(),
+            PixelData::GRAY16(d) => d.len(),
+            PixelData::RGBA16(d, _, _, _) => d.len(),
+            PixelData::RGBAF32(d, _, _, _) => d.len(),
         }
     }
     pub fn interleave(&self) -> Vec<u8> {
@@ -106,14 +358,89 @@ impl PixelData {
                 }
                 v
             }
-            PixelData::YUV420(y, _, _) => {
-                println!(
-                    "The Function is not implmented for YUV420\n The Returned slice is the luma values!"
-                );
-                return y.clone();
+            // Planar Y, then U, then V: the layout of ffmpeg's yuv420p.
+            PixelData::YUV420(y, u, v) => [y.as_slice(), u, v].concat(),
+            deep => deep.to_8bit().interleave(),
+        }
+    }
+    /// Packed 16-bit samples for IO. 8-bit data is widened (`v * 257`), float data
+    /// is clamped and always comes out as RGBA.
+    pub fn interleave16(&self, width: u32, height: u32) -> Result<Vec<u16>, FrameError> {
+        let planes: Vec<Vec<u16>> = match self.to_16bit(width, height)? {
+            PixelData::GRAY16(l) => vec![l],
+            PixelData::RGB16(r, g, b) => vec![r, g, b],
+            PixelData::RGBA16(r, g, b, a) => vec![r, g, b, a],
+            _ => return Err(FrameError::InvalidPixelFormat),
+        };
+        let len = planes[0].len();
+        let mut v = Vec::with_capacity(len * planes.len());
+        for i in 0..len {
+            for plane in &planes {
+                v.push(plane[i]);
+            }
+        }
+        Ok(v)
+    }
+    /// Rounds high-bit-depth data down to 8 bits per channel, keeping the channel
+    /// layout (float becomes RGBA). 8-bit data is returned unchanged.
+    pub fn to_8bit(&self) -> PixelData {
+        match self {
+            PixelData::GRAY16(l) => PixelData::GRAY(to_u8_plane(l)),
+            PixelData::RGB16(r, g, b) => PixelData::RGB(to_u8_plane(r), to_u8_plane(g), to_u8_plane(b)),
+            PixelData::RGBA16(r, g, b, a) => {
+                PixelData::RGBA(to_u8_plane(r), to_u8_plane(g), to_u8_plane(b), to_u8_plane(a))
             }
+            PixelData::RGBAF32(r, g, b, a) => {
+                let [r, g, b, a] = [r, g, b, a].map(|p| p.iter().map(|&v| quantize8(v)).collect());
+                PixelData::RGBA(r, g, b, a)
+            }
+            other => other.clone(),
         }
     }
+    /// Converts to 16 bits per channel, keeping the channel layout. Float becomes
+    /// RGBA16 and YUV420 is converted through RGBA first.
+    pub fn to_16bit(&self, width: u32, height: u32) -> Result<PixelData, FrameError> {
+        Ok(match self {
+            PixelData::GRAY(l) => PixelData::GRAY16(to_u16_plane(l)),
+            PixelData::RGB(r, g, b) => PixelData::RGB16(to_u16_plane(r), to_u16_plane(g), to_u16_plane(b)),
+            PixelData::RGBA(r, g, b, a) => {
+                PixelData::RGBA16(to_u16_plane(r), to_u16_plane(g), to_u16_plane(b), to_u16_plane(a))
+            }
+            PixelData::YUV420(..) => self.to_rgba8(width, height)?.to_16bit(width, height)?,
+            PixelData::RGBAF32(r, g, b, a) => {
+                let [r, g, b, a] = [r, g, b, a].map(|p| p.iter().map(|&v| quantize16(v)).collect());
+                PixelData::RGBA16(r, g, b, a)
+            }
+            deep => deep.clone(),
+        })
+    }
+    /// Converts to normalised float RGBA planes without any rounding.
+    pub fn to_rgbaf32(&self, width: u32, height: u32) -> Result<PixelData, FrameError> {
+        Ok(match self {
+            PixelData::GRAY16(l) => {
+                let l = to_f32_plane(l, 65535.0);
+                PixelData::RGBAF32(l.clone(), l.clone(), l, vec![1.0; self.len()])
+            }
+            PixelData::RGB16(r, g, b) => PixelData::RGBAF32(
+                to_f32_plane(r, 65535.0),
+                to_f32_plane(g, 65535.0),
+                to_f32_plane(b, 65535.0),
+                vec![1.0; self.len()],
+            ),
+            PixelData::RGBA16(r, g, b, a) => {
+                let [r, g, b, a] = [r, g, b, a].map(|p| to_f32_plane(p, 65535.0));
+                PixelData::RGBAF32(r, g, b, a)
+            }
+            PixelData::RGBAF32(..) => self.clone(),
+            _ => match self.to_rgba8(width, height)? {
+                PixelData::RGBA(r, g, b, a) => {
+                    let [r, g, b, a] = [r, g, b, a].map(|p| to_f32_plane(&p, 255.0));
+                    PixelData::RGBAF32(r, g, b, a)
+                }
+                _ => return Err(FrameError::InvalidPixelFormat),
+            },
+        })
+    }
     pub fn to_rgba8(&self, width: u32, height: u32) -> Result<PixelData, FrameError> {
         match self {
             PixelData::GRAY(v) => {
@@ -128,37 +455,23 @@ impl PixelData {
             }
             PixelData::YUV420(y_plane, u_plane, v_plane) => {
                 if width % 2 != 0 || height % 2 != 0 {
-                    return Err(FrameError::InvalidFrameSize);
+                    return Err(FrameError::OddYUVSize);
                 }
 
-                let mut r = Vec::with_capacity((width * height) as usize);
-                let mut g = Vec::with_capacity((width * height) as usize);
-                let mut b = Vec::with_capacity((width * height) as usize);
-                let a = vec![255u8; (width * height) as usize];
+                let len = (width * height) as usize;
+                let mut r = Vec::with_capacity(len);
+                let mut g = Vec::with_capacity(len);
+                let mut b = Vec::with_capacity(len);
+                let a = vec![255u8; len];
 
                 for y in 0..height {
                     for x in 0..width {
                         let yidx = (y * width + x) as usize;
                         let uvidx = ((y / 2) * (width / 2) + (x / 2)) as usize;
-
-                        // ---- fetch values ----
-                        let y_raw = y_plane[yidx] as i32;
-                        let u_raw = u_plane[uvidx] as i32;
-                        let v_raw = v_plane[uvidx] as i32;
-
-                        // ---- convert limited range → full ----
-                        let c = (y_raw - 16).max(0);
-                        let d = u_raw - 128;
-                        let e = v_raw - 128;
-
-                        // ---- integer BT.601 ----
-                        let r_val = (298 * c + 409 * e + 128) >> 8;
-                        let g_val = (298 * c - 100 * d - 208 * e + 128) >> 8;
-                        let b_val = (298 * c + 516 * d + 128) >> 8;
-
-                        r.push(r_val.clamp(0, 255) as u8);
-                        g.push(g_val.clamp(0, 255) as u8);
-                        b.push(b_val.clamp(0, 255) as u8);
+                        let [rv, gv, bv] = yuv_to_rgb(y_plane[yidx], u_plane[uvidx], v_plane[uvidx]);
+                        r.push(rv);
+                        g.push(gv);
+                        b.push(bv);
                     }
                 }
 
@@ -167,8 +480,69 @@ impl PixelData {
             PixelData::RGBA(r, g, b, a) => {
                 Ok(PixelData::RGBA(r.clone(), g.clone(), b.clone(), a.clone()))
             }
+            deep => deep.to_8bit().to_rgba8(width, height),
         }
     }
+    /// Converts to another channel layout, keeping the bit depth where the layout
+    /// has it. Gray and RGB have no float storage, so float frames come out 16-bit;
+    /// YUV420 is always 8-bit. Gray is the BT.601 luma and alpha is dropped as is,
+    /// without compositing onto a background.
+    pub fn to_format(&self, format: PixelFormat, width: u32, height: u32) -> Result<PixelData, FrameError> {
+        if self.format() == format {
+            return Ok(self.clone());
+        }
+        if format == PixelFormat::YUV420 {
+            return self.to_yuv420(width, height);
+        }
+
+        let deep = self.is_deep();
+        let PixelData::RGBAF32(r, g, b, a) = self.to_rgbaf32(width, height)? else {
+            return Err(FrameError::InvalidPixelFormat);
+        };
+        let q8 = |p: &[f32]| p.iter().map(|&v| quantize8(v)).collect::<Vec<u8>>();
+        let q16 = |p: &[f32]| p.iter().map(|&v| quantize16(v)).collect::<Vec<u16>>();
+
+        Ok(match format {
+            PixelFormat::Gray => {
+                let l: Vec<f32> = (0..r.len()).map(|i| luma(r[i], g[i], b[i])).collect();
+                if deep { PixelData::GRAY16(q16(&l)) } else { PixelData::GRAY(q8(&l)) }
+            }
+            PixelFormat::RGB if deep => PixelData::RGB16(q16(&r), q16(&g), q16(&b)),
+            PixelFormat::RGB => PixelData::RGB(q8(&r), q8(&g), q8(&b)),
+            PixelFormat::RGBA if deep => PixelData::RGBA16(q16(&r), q16(&g), q16(&b), q16(&a)),
+            PixelFormat::RGBA => PixelData::RGBA(q8(&r), q8(&g), q8(&b), q8(&a)),
+            PixelFormat::YUV420 => return self.to_yuv420(width, height),
+        })
+    }
+    /// Converts to 8-bit limited-range BT.601 YUV 4:2:0. Each chroma sample is the
+    /// average of its 2x2 block and alpha is dropped.
+    pub fn to_yuv420(&self, width: u32, height: u32) -> Result<PixelData, FrameError> {
+        if let PixelData::YUV420(..) = self {
+            return Ok(self.clone());
+        }
+        if width % 2 != 0 || height % 2 != 0 {
+            return Err(FrameError::OddYUVSize);
+        }
+        let PixelData::RGBAF32(r, g, b, _) = self.to_rgbaf32(width, height)? else {
+            return Err(FrameError::InvalidPixelFormat);
+        };
+        let sample = |i: usize| rgb_to_yuv(r[i] * 255.0, g[i] * 255.0, b[i] * 255.0);
+        let q = |v: f32| v.round().clamp(0.0, 255.0) as u8;
+
+        let (w, h) = (width as usize, height as usize);
+        let y_plane: Vec<u8> = (0..w * h).map(|i| q(sample(i)[0])).collect();
+        let mut u_plane = Vec::with_capacity(w * h / 4);
+        let mut v_plane = Vec::with_capacity(w * h / 4);
+        for cy in 0..h / 2 {
+            for cx in 0..w / 2 {
+                let i = cy * 2 * w + cx * 2;
+                let block = [i, i + 1, i + w, i + w + 1].map(sample);
+                u_plane.push(q(block.iter().map(|s| s[1]).sum::<f32>() / 4.0));
+                v_plane.push(q(block.iter().map(|s| s[2]).sum::<f32>() / 4.0));
+            }
+        }
+        Ok(PixelData::YUV420(y_plane, u_plane, v_plane))
+    }
 }
 
 #[derive(Debug, Clone)]
@@ -177,6 +551,12 @@ pub struct Frame {
     width: u32,
     height: u32,
     data: PixelData,
+    /// EXIF / ICC / XMP read from the source file, shared between clones.
+    metadata: Option<Arc<ImageMetadata>>,
+    /// Colour holds linear light rather than sRGB-encoded values.
+    linear: bool,
+    /// Precision to write the frame at when it was only widened for processing.
+    export_depth: Option<BitDepth>,
 }
 
 #[derive(Debug)]
@@ -189,6 +569,10 @@ pub enum FrameError {
     EmptyFrame,
     YUVNotApplied,
     BlendingFailed,
+    OddYUVSize,
+    NoAlphaChannel,
+    /// Two frames that must be the same size are not: (width, height) of each.
+    SizeMismatch((u32, u32), (u32, u32)),
 }
 impl fmt::Display for FrameError {
     fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
@@ -215,84 +599,92 @@ impl fmt::Display for FrameError {
                 )
             }
             FrameError::YUVNotApplied => {
-                write!(f, "YUV Format is Not Implemented for this function.")
+                write!(f, "This operation does not support YUV420 frames! Convert with to_rgb() first.")
             }
             FrameError::BlendingFailed => {
                 write!(f, "The two frames are not compatible!")
             }
+            FrameError::OddYUVSize => {
+                write!(f, "YUV420 frames need an even width and height!")
+            }
+            FrameError::NoAlphaChannel => {
+                write!(f, "The frame has no alpha channel! Convert with to_rgba() first.")
+            }
+            FrameError::SizeMismatch((w1, h1), (w2, h2)) => {
+                write!(f, "The frames differ in size: {w1}x{h1} and {w2}x{h2}!")
+            }
         }
     }
 }
 
-fn pad_to(frame: &Frame, target_w: usize, target_h: usize) -> Result<Frame, FrameError> {
-    let offset_x = (target_w as u32 - frame.width) / 2;
-    let offset_y = (target_h as u32 - frame.height) / 2;
-
-    match &frame.data {
-        PixelData::GRAY(v) => {
-            let mut out = vec![0u8; target_w * target_h];
-
-            for y in 0..frame.height {
-                for x in 0..frame.width {
-                    let src_idx = (y * frame.width + x) as usize;
-                    let dst_idx = (y + offset_y) as usize * target_w + (x + offset_x) as usize;
-                    out[dst_idx] = v[src_idx];
-                }
-            }
-
-            Frame::new(target_w as u32, target_h as u32, PixelData::GRAY(out))
+fn pad_plane<T: Copy + Default>(
+    src: &[T],
+    width: u32,
+    height: u32,
+    target_w: usize,
+    target_h: usize,
+    offset_x: u32,
+    offset_y: u32,
+) -> Vec<T> {
+    let mut out = vec![T::default(); target_w * target_h];
+    for y in 0..height {
+        for x in 0..width {
+            let src_idx = (y * width + x) as usize;
+            let dst_idx = (y + offset_y) as usize * target_w + (x + offset_x) as usize;
+            out[dst_idx] = src[src_idx];
         }
+    }
+    out
+}
 
-        PixelData::RGB(r, g, b) => {
-            let mut r_out = vec![0u8; target_w * target_h];
-            let mut g_out = vec![0u8; target_w * target_h];
-            let mut b_out = vec![0u8; target_w * target_h];
-
-            for y in 0..frame.height {
-                for x in 0..frame.width {
-                    let src = (y * frame.width + x) as usize;
-                    let dst = (y + offset_y) as usize * target_w + (x + offset_x) as usize;
+fn crop_plane<T: Copy + Default>(src: &[T], src_w: u32, x: u32, y: u32, width: u32, height: u32) -> Vec<T> {
+    let mut dst = vec![T::default(); (width * height) as usize];
+    for row in 0..height {
+        let src_start = ((y + row) * src_w + x) as usize;
+        let dst_start = (row * width) as usize;
+        dst[dst_start..dst_start + width as usize].copy_from_slice(&src[src_start..src_start + width as usize]);
+    }
+    dst
+}
 
-                    r_out[dst] = r[src];
-                    g_out[dst] = g[src];
-                    b_out[dst] = b[src];
-                }
-            }
+/// Copies `src` (`src_w` wide) into `dst` (`dst_w` wide) with its corner at (x, y).
+fn blit_plane<T: Copy>(dst: &mut [T], dst_w: u32, src: &[T], src_w: u32, x: u32, y: u32) {
+    let (dst_w, src_w) = (dst_w as usize, src_w as usize);
+    for (row, line) in src.chunks_exact(src_w.max(1)).enumerate() {
+        let start = (y as usize + row) * dst_w + x as usize;
+        dst[start..start + src_w].copy_from_slice(line);
+    }
+}
 
-            Frame::new(
-                target_w as u32,
-                target_h as u32,
-                PixelData::RGB(r_out, g_out, b_out),
-            )
+/// Nearest-neighbour resample of a single plane.
+fn resize_plane<T: Copy + Default>(src: &[T], src_w: u32, src_h: u32, target_w: u32, target_h: u32) -> Vec<T> {
+    let scale_x = src_w as f32 / target_w as f32;
+    let scale_y = src_h as f32 / target_h as f32;
+    let mut out = vec![T::default(); (target_w * target_h) as usize];
+    for y in 0..target_h {
+        for x in 0..target_w {
+            let src_x = ((x as f32 * scale_x) as u32).min(src_w - 1);
+            let src_y = ((y as f32 * scale_y) as u32).min(src_h - 1);
+            out[(y * target_w + x) as usize] = src[(src_y * src_w + src_x) as usize];
         }
+    }
+    out
+}
 
-        PixelData::RGBA(r, g, b, a) => {
-            let mut r_out = vec![0u8; target_w * target_h];
-            let mut g_out = vec![0u8; target_w * target_h];
-            let mut b_out = vec![0u8; target_w * target_h];
-            let mut a_out = vec![0u8; target_w * target_h];
-
-            for y in 0..frame.height {
-                for x in 0..frame.width {
-                    let src = (y * frame.width + x) as usize;
-                    let dst = (y + offset_y) as usize * target_w + (x + offset_x) as usize;
-
-                    r_out[dst] = r[src];
-                    g_out[dst] = g[src];
-                    b_out[dst] = b[src];
-                    a_out[dst] = a[src];
-                }
-            }
-
-            Frame::new(
-                target_w as u32,
-                target_h as u32,
-                PixelData::RGBA(r_out, g_out, b_out, a_out),
-            )
-        }
+fn pad_to(frame: &Frame, target_w: usize, target_h: usize) -> Result<Frame, FrameError> {
+    let offset_x = (target_w as u32 - frame.width) / 2;
+    let offset_y = (target_h as u32 - frame.height) / 2;
 
-        _ => Err(FrameError::YUVNotApplied),
-    }
+    let data = map_planes!(&frame.data, |p| pad_plane(
+        p,
+        frame.width,
+        frame.height,
+        target_w,
+        target_h,
+        offset_x,
+        offset_y
+    ))?;
+    Frame::new(target_w as u32, target_h as u32, data)
 }
 
 impl std::error::Error for FrameError {}
@@ -307,6 +699,9 @@ impl Frame {
                 height,
 
                 data,
+                metadata: None,
+                linear: false,
+                export_depth: None,
             })
         } else {
             Err(FrameError::InvalidFrameSize)
@@ -338,6 +733,12 @@ impl Frame {
                 y[index] = clamp(y[index]);
                 true
             }
+            _ => {
+                let [r, g, b, a] = self.deep_pixel(index);
+                let d = delta as f32 / 255.0;
+                self.set_deep_pixel(index, [r + d, g + d, b + d, a]);
+                true
+            }
         }
     }
 
@@ -353,6 +754,27 @@ impl Frame {
     pub fn data_mut(&mut self) -> &mut PixelData {
         &mut self.data
     }
+    pub fn metadata(&self) -> Option<&Arc<ImageMetadata>> {
+        self.metadata.as_ref()
+    }
+    pub fn set_metadata(&mut self, metadata: Option<Arc<ImageMetadata>>) {
+        self.metadata = metadata;
+    }
+    pub fn is_linear(&self) -> bool {
+        self.linear
+    }
+    /// Marks the colour as linear light or sRGB without touching the data.
+    pub fn set_linear(&mut self, linear: bool) {
+        self.linear = linear;
+    }
+    /// The precision the frame is exported at: the source file's when a pipeline
+    /// widened it, otherwise the precision it is stored at.
+    pub fn export_depth(&self) -> BitDepth {
+        self.export_depth.unwrap_or(self.data.depth())
+    }
+    pub fn set_export_depth(&mut self, depth: Option<BitDepth>) {
+        self.export_depth = depth;
+    }
 }
 impl Frame {
     pub fn pixel_index(&self, pos: &Pos) -> Result<usize, FrameError> {
@@ -378,32 +800,97 @@ impl Frame {
             PixelData::RGB(r, g, b) => Ok(Color::RGB(r[index], g[index], b[index])),
             PixelData::RGBA(r, g, b, a) => Ok(Color::RGBA(r[index], g[index], b[index], a[index])),
             PixelData::GRAY(l) => Ok(Color::Gray(l[index])),
-            _ => return Err(FrameError::YUVNotApplied),
+            PixelData::YUV420(y, u, v) => {
+                let [r, g, b] = yuv_to_rgb(y[index], u[self.chroma_index(pos)], v[self.chroma_index(pos)]);
+                Ok(Color::RGB(r, g, b))
+            }
+            _ => {
+                let [r, g, b, a] = self.deep_pixel(index).map(|v| v * 255.0);
+                Ok(Color::RGBAF(r, g, b, a))
+            }
+        }
+    }
+
+    /// Index of the chroma sample a YUV420 pixel shares with its 2x2 block.
+    fn chroma_index(&self, pos: &Pos) -> usize {
+        let Pos(x, y) = *pos;
+        (y / 2) as usize * (self.width / 2) as usize + (x / 2) as usize
+    }
+
+    /// Normalised RGBA of one pixel of a 16-bit or float frame (gray is replicated).
+    fn deep_pixel(&self, index: usize) -> [f32; 4] {
+        let w = |v: u16| v as f32 / 65535.0;
+        match &self.data {
+            PixelData::GRAY16(l) => [w(l[index]), w(l[index]), w(l[index]), 1.0],
+            PixelData::RGB16(r, g, b) => [w(r[index]), w(g[index]), w(b[index]), 1.0],
+            PixelData::RGBA16(r, g, b, a) => [w(r[index]), w(g[index]), w(b[index]), w(a[index])],
+            PixelData::RGBAF32(r, g, b, a) => [r[index], g[index], b[index], a[index]],
+            _ => [0.0; 4],
+        }
+    }
+
+    /// Writes normalised RGBA into a 16-bit or float frame. Float keeps colour
+    /// unclamped; 16-bit storage clamps and gray takes the BT.601 luma.
+    fn set_deep_pixel(&mut self, index: usize, [rv, gv, bv, av]: [f32; 4]) {
+        match &mut self.data {
+            PixelData::GRAY16(l) => l[index] = quantize16(0.299 * rv + 0.587 * gv + 0.114 * bv),
+            PixelData::RGB16(r, g, b) => {
+                r[index] = quantize16(rv);
+                g[index] = quantize16(gv);
+                b[index] = quantize16(bv);
+            }
+            PixelData::RGBA16(r, g, b, a) => {
+                r[index] = quantize16(rv);
+                g[index] = quantize16(gv);
+                b[index] = quantize16(bv);
+                a[index] = quantize16(av);
+            }
+            PixelData::RGBAF32(r, g, b, a) => {
+                r[index] = rv;
+                g[index] = gv;
+                b[index] = bv;
+                a[index] = av.clamp(0.0, 1.0);
+            }
+            _ => {}
         }
     }
 
     /// The set_pixel() method allows us to set the color of a pixel at a specific position
+    /// Any colour is accepted and converted to the frame's layout: gray frames take the
+    /// BT.601 luma, frames without alpha drop it, and 16-bit and float frames convert
+    /// to their own precision. A YUV420 pixel writes its own luma and the chroma it
+    /// shares with the rest of its 2x2 block.
     pub fn set_pixel(&mut self, pos: &Pos, color: &Color) -> Result<(), FrameError> {
         let index = self.pixel_index(pos)?;
 
-        if self.data.bytes_per_pixel() != color.size() {
-            return Err(FrameError::InvalidPixelFormat);
+        if self.data.is_deep() {
+            self.set_deep_pixel(index, color.channels_f32().map(|v| v / 255.0));
+            return Ok(());
         }
 
+        let chroma = self.chroma_index(pos);
+        let q = |v: f32| v.round().clamp(0.0, 255.0) as u8;
+        let [rv, gv, bv, av] = color.channels_f32();
+
         match (&mut self.data, color) {
-            (PixelData::RGB(r, g, b), Color::RGB(rv, gv, bv)) => {
-                r[index] = *rv;
-                g[index] = *gv;
-                b[index] = *bv;
+            (PixelData::GRAY(l), Color::Gray(v)) => l[index] = *v,
+            (PixelData::GRAY(l), _) => l[index] = q(luma(rv, gv, bv)),
+            (PixelData::RGB(r, g, b), _) => {
+                r[index] = q(rv);
+                g[index] = q(gv);
+                b[index] = q(bv);
             }
-            (PixelData::RGBA(r, g, b, a), Color::RGBA(rv, gv, bv, av)) => {
-                r[index] = *rv;
-                g[index] = *gv;
-                b[index] = *bv;
-                a[index] = *av;
+            (PixelData::RGBA(r, g, b, a), _) => {
+                r[index] = q(rv);
+                g[index] = q(gv);
+                b[index] = q(bv);
+                a[index] = q(av);
             }
-            (PixelData::GRAY(l), Color::Gray(v)) => {
-                l[index] = *v;
+            (PixelData::YUV420(y, u, v), _) => {
+                let [yv, uv, vv] = rgb_to_yuv(rv, gv, bv);
+                y[index] = q(yv);
+                u[chroma] = q(uv);
+                v[chroma] = q(vv);
             }
             _ => return Err(FrameError::InvalidPixelFormat),
         }
@@ -411,8 +898,8 @@ impl Frame {
         Ok(())
     }
     pub fn set_alpha(&mut self, pos: &Pos, value: u8) -> Result<(), FrameError> {
-        if self.data.bytes_per_pixel() != 4 {
-            return Err(FrameError::InvalidPixelFormat);
+        if self.data.format() != PixelFormat::RGBA {
+            return Err(FrameError::NoAlphaChannel);
         }
         let index = self.pixel_index(pos)?;
         let data = &mut self.data;
@@ -420,6 +907,10 @@ impl Frame {
             PixelData::RGBA(_, _, _, a) => {
                 a[index] = value;
             }
+            PixelData::RGBA16(..) | PixelData::RGBAF32(..) => {
+                let [r, g, b, _] = self.deep_pixel(index);
+                self.set_deep_pixel(index, [r, g, b, value as f32 / 255.0]);
+            }
             _ => return Err(FrameError::InvalidPixelFormat),
         }
         Ok(())
@@ -428,9 +919,21 @@ impl Frame {
         let data = self.data.to_rgba8(self.width, self.height)?;
         Frame::new(self.width, self.height, data)
     }
+    /// Converts to another channel layout; see `PixelData::to_format`. Metadata is kept.
+    pub fn to_format(&self, format: PixelFormat) -> Result<Frame, FrameError> {
+        let data = self.data.to_format(format, self.width, self.height)?;
+        let mut frame = Frame::new(self.width, self.height, data)?;
+        frame.metadata = self.metadata.clone();
+        frame.linear = self.linear;
+        frame.export_depth = self.export_depth;
+        Ok(frame)
+    }
+    pub fn format(&self) -> PixelFormat {
+        self.data.format()
+    }
     pub fn opacity(&mut self, pos: &Pos, value: u8) -> Result<(), FrameError> {
-        if self.data.bytes_per_pixel() != 4 {
-            return Err(FrameError::InvalidPixelFormat);
+        if self.data.format() != PixelFormat::RGBA {
+            return Err(FrameError::NoAlphaChannel);
         }
         if value > 100 {
             return Err(FrameError::InvalidOpacityValue);
@@ -442,6 +945,10 @@ impl Frame {
             PixelData::RGBA(_, _, _, a) => {
                 a[index] = ((a[index] as u16 * value as u16) / 100) as u8;
             }
+            PixelData::RGBA16(..) | PixelData::RGBAF32(..) => {
+                let [r, g, b, a] = self.deep_pixel(index);
+                self.set_deep_pixel(index, [r, g, b, a * value as f32 / 100.0]);
+            }
             _ => return Err(FrameError::InvalidPixelFormat),
         }
         Ok(())
@@ -462,6 +969,11 @@ impl Frame {
             PixelData::YUV420(l, _, _) => {
                 l[index] = (128.0 + factor * (l[index] as f32 - 128.0)).clamp(0.0, 255.0) as u8;
             }
+            _ => {
+                let [r, g, b, a] = self.deep_pixel(index);
+                let c = |v: f32| 0.5 + factor * (v - 0.5);
+                self.set_deep_pixel(index, [c(r), c(g), c(b), a]);
+            }
         }
         Ok(())
     }
@@ -471,8 +983,21 @@ impl Frame {
 
         let alpha = alpha.clamp(0.0, 1.0);
 
-        if std::mem::discriminant(&data) != std::mem::discriminant(&data2) {
-            return Err(FrameError::BlendingFailed);
+        // Mixed layouts, YUV and deep frames blend on float planes and come back in
+        // this frame's layout.
+        let same_8bit = std::mem::discriminant(&data) == std::mem::discriminant(data2) && !data.is_deep();
+        if !same_8bit || data.format() == PixelFormat::YUV420 || self.linear != frame.linear {
+            if self.width != frame.width || self.height != frame.height {
+                return Err(FrameError::BlendingFailed);
+            }
+            let mut planes = self.rgba_planes()?;
+            let other = self.matching_planes(frame)?;
+            for (plane, plane2) in planes.iter_mut().zip(other.iter()) {
+                for (p, p2) in plane.iter_mut().zip(plane2.iter()) {
+                    *p = *p * (1.0 - alpha) + p2 * alpha;
+                }
+            }
+            return self.with_planes(planes);
         }
 
         if self.width.abs_diff(frame.width) != 0 || self.height.abs_diff(frame.height) != 0 {
@@ -548,6 +1073,7 @@ impl Frame {
             }
         };
 
+        let chroma = self.chroma_index(pos);
         let data = &mut self.data;
         match data {
             PixelData::RGB(r, g, b) | PixelData::RGBA(r, g, b, _) => {
@@ -558,9 +1084,25 @@ impl Frame {
                 b[index] = (y + s_value * (b[index] as f32 - y).clamp(0.0, 255.0)) as u8;
                 true
             }
+            // Gray has no chroma to scale.
+            PixelData::GRAY(_) | PixelData::GRAY16(_) => true,
+            // Chroma is shared by a 2x2 block, so it is scaled once, from the block's
+            // top-left pixel.
+            PixelData::YUV420(_, u, v) => {
+                let Pos(x, y) = *pos;
+                if x % 2 == 0 && y % 2 == 0 {
+                    for plane in [u, v] {
+                        plane[chroma] = (128.0 + s_value * (plane[chroma] as f32 - 128.0)).clamp(0.0, 255.0) as u8;
+                    }
+                }
+                true
+            }
             _ => {
-                eprintln!("Saturation is only implemented for RGB or RGBA!");
-                return false;
+                let [r, g, b, a] = self.deep_pixel(index);
+                let y = luma(r, g, b);
+                let s = |v: f32| y + s_value * (v - y);
+                self.set_deep_pixel(index, [s(r), s(g), s(b), a]);
+                true
             }
         }
     }
@@ -571,62 +1113,183 @@ impl Frame {
             return Err(FrameError::BlitFailed);
         }
 
-        let (r, g, b, a) = match &mut self.data {
-            PixelData::RGBA(r, g, b, a) => (r, g, b, a),
-            _ => return Err(FrameError::BlitFailed),
-        };
-
-        let (r2, g2, b2, a2) = match &frame.data {
-            PixelData::RGBA(r, g, b, a) => (r, g, b, a),
-            _ => return Err(FrameError::BlitFailed),
+        // Frames of the same layout and depth copy whole rows plane by plane.
+        let (dw, sw) = (self.width, frame.width);
+        let copied = match (&mut self.data, &frame.data) {
+            (PixelData::GRAY(d), PixelData::GRAY(s)) => {
+                blit_plane(d, dw, s, sw, x, y);
+                true
+            }
+            (PixelData::GRAY16(d), PixelData::GRAY16(s)) => {
+                blit_plane(d, dw, s, sw, x, y);
+                true
+            }
+            (PixelData::RGB(r, g, b), PixelData::RGB(r2, g2, b2)) => {
+                for (d, s) in [(r, r2), (g, g2), (b, b2)] {
+                    blit_plane(d, dw, s, sw, x, y);
+                }
+                true
+            }
+            (PixelData::RGB16(r, g, b), PixelData::RGB16(r2, g2, b2)) => {
+                for (d, s) in [(r, r2), (g, g2), (b, b2)] {
+                    blit_plane(d, dw, s, sw, x, y);
+                }
+                true
+            }
+            (PixelData::RGBA(r, g, b, a), PixelData::RGBA(r2, g2, b2, a2)) => {
+                for (d, s) in [(r, r2), (g, g2), (b, b2), (a, a2)] {
+                    blit_plane(d, dw, s, sw, x, y);
+                }
+                true
+            }
+            (PixelData::RGBA16(r, g, b, a), PixelData::RGBA16(r2, g2, b2, a2)) => {
+                for (d, s) in [(r, r2), (g, g2), (b, b2), (a, a2)] {
+                    blit_plane(d, dw, s, sw, x, y);
+                }
+                true
+            }
+            (PixelData::RGBAF32(r, g, b, a), PixelData::RGBAF32(r2, g2, b2, a2)) => {
+                for (d, s) in [(r, r2), (g, g2), (b, b2), (a, a2)] {
+                    blit_plane(d, dw, s, sw, x, y);
+                }
+                true
+            }
+            _ => false,
         };
+        if copied {
+            return Ok(());
+        }
 
+        // Any other pair of layouts copies pixel by pixel, converting on the way.
         for row in 0..frame.height {
             for col in 0..frame.width {
-                let dst_idx = ((y + row) * self.width + (x + col)) as usize;
-                let src_idx = (row * frame.width + col) as usize;
-
-                r[dst_idx] = r2[src_idx];
-                g[dst_idx] = g2[src_idx];
-                b[dst_idx] = b2[src_idx];
-                a[dst_idx] = a2[src_idx];
+                let color = frame.get_pixel(&Pos(col, row))?;
+                self.set_pixel(&Pos(x + col, y + row), &color)?;
             }
         }
-
         Ok(())
     }
+
+    /// A zeroed frame of another size in this frame's layout, precision and transfer,
+    /// for assembling a result piece by piece with `blit`.
+    pub fn blank_like(&self, width: u32, height: u32) -> Result<Frame, FrameError> {
+        let len = width as usize * height as usize;
+        let data = map_planes!(&self.data, |_p| vec![Default::default(); len])?;
+        let mut frame = Frame::new(width, height, data)?;
+        frame.linear = self.linear;
+        Ok(frame)
+    }
     pub fn blend_on(&mut self, pos: &Pos, frame: &Frame, alpha: f64) -> Result<(), FrameError> {
+        self.blend_on_with(pos, frame, alpha, BlendMode::Normal)
+    }
+
+    /// `blend_on` with a blend mode. Every mode composites `frame` over the canvas
+    /// (source-over) through its own alpha scaled by `alpha`, as `paint` does, so
+    /// transparent parts of `frame` leave the canvas alone; `mode` decides how colour
+    /// mixes where the canvas is opaque.
+    pub fn blend_on_with(&mut self, pos: &Pos, frame: &Frame, alpha: f64, mode: BlendMode) -> Result<(), FrameError> {
         let Pos(x, y) = *pos;
 
         if x + frame.width > self.width || y + frame.height > self.height {
             return Err(FrameError::BlitFailed);
         }
 
-        let (r, g, b, a) = match &mut self.data {
-            PixelData::RGBA(r, g, b, a) => (r, g, b, a),
-            _ => return Err(FrameError::BlitFailed),
-        };
+        // Any canvas other than 8-bit RGBA is blended on float planes and rebuilt in
+        // its own layout, so a float canvas takes any source without rounding it.
+        // A linear canvas decodes an sRGB source first, so the mix is done in light.
+        if !matches!(self.data, PixelData::RGBA(..)) || self.linear != frame.linear || mode != BlendMode::Normal {
+            let mut planes = self.rgba_planes()?;
+            let src = self.matching_planes(frame)?;
+            let alpha = alpha as f32;
+            for row in 0..frame.height {
+                for col in 0..frame.width {
+                    let j = ((y + row) * self.width + (x + col)) as usize;
+                    let i = (row * frame.width + col) as usize;
+                    let color = [src[0][i], src[1][i], src[2][i]];
+                    composite(&mut planes, j, color, src[3][i] * alpha, mode);
+                }
+            }
+            let metadata = self.metadata.take();
+            *self = self.with_planes(planes)?;
+            self.metadata = metadata;
+            return Ok(());
+        }
 
-        let (r2, g2, b2, a2) = match &frame.data {
-            PixelData::RGBA(r, g, b, a) => (r, g, b, a),
-            _ => return Err(FrameError::BlitFailed),
+        let converted;
+        let frame = match frame.data {
+            PixelData::RGBA(..) => frame,
+            _ => {
+                converted = frame.to_rgba()?;
+                &converted
+            }
+        };
+        let (PixelData::RGBA(r, g, b, a), PixelData::RGBA(r2, g2, b2, a2)) = (&mut self.data, &frame.data) else {
+            return Err(FrameError::BlitFailed);
         };
 
+        // `composite` in `Normal` mode, on bytes.
         for row in 0..frame.height {
             for col in 0..frame.width {
                 let j = ((y + row) * self.width + (x + col)) as usize;
                 let i = (row * frame.width + col) as usize;
 
-                r[j] = (r[j] as f64 * (1.0 - alpha) + r2[i] as f64 * alpha) as u8;
-                g[j] = (g[j] as f64 * (1.0 - alpha) + g2[i] as f64 * alpha) as u8;
-                b[j] = (b[j] as f64 * (1.0 - alpha) + b2[i] as f64 * alpha) as u8;
-                a[j] = (a[j] as f64 * (1.0 - alpha) + a2[i] as f64 * alpha) as u8;
+                let src_a = a2[i] as f64 / 255.0 * alpha;
+                if src_a <= 0.0 {
+                    continue;
+                }
+                let dst_a = a[j] as f64 / 255.0;
+                let out_a = src_a + dst_a * (1.0 - src_a);
+                let mix = |dst: u8, src: u8| ((src as f64 * src_a + dst as f64 * dst_a * (1.0 - src_a)) / out_a).round() as u8;
+                r[j] = mix(r[j], r2[i]);
+                g[j] = mix(g[j], g2[i]);
+                b[j] = mix(b[j], b2[i]);
+                a[j] = (out_a * 255.0).round() as u8;
             }
         }
 
         Ok(())
     }
 }
+/// Source-over of one straight colour with opacity `src_a` onto pixel `i` of
+/// normalised planes, mixing with `mode` where the canvas is opaque.
+pub(crate) fn composite(planes: &mut [Vec<f32>; 4], i: usize, src: [f32; 3], src_a: f32, mode: BlendMode) {
+    if src_a <= 0.0 {
+        return;
+    }
+    let dst_a = planes[3][i].clamp(0.0, 1.0);
+    let out_a = src_a + dst_a * (1.0 - src_a);
+    for (c, &s) in src.iter().enumerate() {
+        let dst = planes[c][i];
+        // Where the canvas is transparent the source shows as is.
+        let top = (1.0 - dst_a) * s + dst_a * mode.apply(dst, s);
+        planes[c][i] = (src_a * top + (1.0 - src_a) * dst_a * dst) / out_a;
+    }
+    planes[3][i] = out_a;
+}
+
+impl Frame {
+    /// Composites `color` over the frame through a coverage plane (one value in
+    /// 0.0 - 1.0 per pixel), as drawn shapes are. The colour's own alpha scales the
+    /// coverage; `mode` decides how it mixes with what is underneath.
+    pub fn paint(&mut self, coverage: &[f32], color: Color, mode: BlendMode) -> Result<(), FrameError> {
+        if coverage.len() != (self.width * self.height) as usize {
+            return Err(FrameError::InvalidFrameSize);
+        }
+        let [r, g, b, a] = color.channels_f32().map(|v| v / 255.0);
+        let source = if self.linear { [r, g, b].map(srgb_to_linear) } else { [r, g, b] };
+
+        let mut planes = self.rgba_planes()?;
+        for (i, &cover) in coverage.iter().enumerate() {
+            composite(&mut planes, i, source, cover.clamp(0.0, 1.0) * a, mode);
+        }
+
+        let metadata = self.metadata.take();
+        *self = self.with_planes(planes)?;
+        self.metadata = metadata;
+        Ok(())
+    }
+}
+
 impl Frame {
     pub fn blank(width: u32, height: u32) -> Self {
         let len = (width * height) as usize;
@@ -640,6 +1303,9 @@ impl Frame {
                 vec![0u8; len],
                 vec![255u8; len],
             ),
+            metadata: None,
+            linear: false,
+            export_depth: None,
         }
     }
     pub fn crop(&self, x: u32, y: u32, width: u32, height: u32) -> Result<Frame, FrameError> {
@@ -647,143 +1313,245 @@ impl Frame {
             return Err(FrameError::InvalidFrameSize);
         }
 
-        let len = (width * height) as usize;
-
-        match &self.data {
-            PixelData::GRAY(src) => {
-                let mut dst = vec![0u8; len];
-
-                for row in 0..height {
-                    let src_start = ((y + row) * self.width + x) as usize;
-                    let src_end = src_start + width as usize;
-
-                    let dst_start = (row * width) as usize;
+        // Chroma covers 2x2 blocks, so a YUV crop has to start and end on block edges.
+        if let PixelData::YUV420(yp, u, v) = &self.data {
+            if [x, y, width, height].iter().any(|v| v % 2 != 0) {
+                return Err(FrameError::OddYUVSize);
+            }
+            let (cw, x2, y2, w2, h2) = (self.width / 2, x / 2, y / 2, width / 2, height / 2);
+            let data = PixelData::YUV420(
+                crop_plane(yp, self.width, x, y, width, height),
+                crop_plane(u, cw, x2, y2, w2, h2),
+                crop_plane(v, cw, x2, y2, w2, h2),
+            );
+            return Frame::new(width, height, data);
+        }
 
-                    dst[dst_start..dst_start + width as usize]
-                        .copy_from_slice(&src[src_start..src_end]);
-                }
+        let data = map_planes!(&self.data, |p| crop_plane(p, self.width, x, y, width, height))?;
+        Frame::new(width, height, data)
+    }
+    pub fn resize(&self, target_w: u32, target_h: u32) -> Result<Frame, FrameError> {
+        if target_w == 0 || target_h == 0 {
+            return Err(FrameError::InvalidFrameSize);
+        }
 
-                Frame::new(width, height, PixelData::GRAY(dst))
+        if let PixelData::YUV420(y, u, v) = &self.data {
+            if target_w % 2 != 0 || target_h % 2 != 0 {
+                return Err(FrameError::OddYUVSize);
             }
+            let (w2, h2, tw2, th2) = (self.width / 2, self.height / 2, target_w / 2, target_h / 2);
+            let data = PixelData::YUV420(
+                resize_plane(y, self.width, self.height, target_w, target_h),
+                resize_plane(u, w2, h2, tw2, th2),
+                resize_plane(v, w2, h2, tw2, th2),
+            );
+            return Frame::new(target_w, target_h, data);
+        }
 
-            PixelData::RGB(r, g, b) => {
-                let mut r_out = vec![0u8; len];
-                let mut g_out = vec![0u8; len];
-                let mut b_out = vec![0u8; len];
+        let data = map_planes!(&self.data, |p| resize_plane(p, self.width, self.height, target_w, target_h))?;
+        Frame::new(target_w, target_h, data)
+    }
+}
 
-                for row in 0..height {
-                    let src_start = ((y + row) * self.width + x) as usize;
-                    let src_end = src_start + width as usize;
 
-                    let dst_start = (row * width) as usize;
+// ── Float planes ─────────────────────────────────────────────────────────────
 
-                    r_out[dst_start..dst_start + width as usize]
-                        .copy_from_slice(&r[src_start..src_end]);
+impl Frame {
+    /// Splits the frame into four normalised (0.0 - 1.0) RGBA planes.
+    /// Native stages that need more than one neighbourhood lookup per pixel work on these.
+    /// 16-bit and float frames come out at full precision.
+    pub fn rgba_planes(&self) -> Result<[Vec<f32>; 4], FrameError> {
+        match self.data.to_rgbaf32(self.width, self.height)? {
+            PixelData::RGBAF32(r, g, b, a) => Ok([r, g, b, a]),
+            _ => Err(FrameError::InvalidPixelFormat),
+        }
+    }
 
-                    g_out[dst_start..dst_start + width as usize]
-                        .copy_from_slice(&g[src_start..src_end]);
+    /// Rebuilds an RGBA frame from normalised planes, rounding back to 8 bits.
+    pub fn from_rgba_planes(width: u32, height: u32, planes: [Vec<f32>; 4]) -> Result<Frame, FrameError> {
+        let [r, g, b, a] = planes.map(|plane| plane.iter().map(|&v| quantize8(v)).collect::<Vec<u8>>());
+        Frame::new(width, height, PixelData::RGBA(r, g, b, a))
+    }
 
-                    b_out[dst_start..dst_start + width as usize]
-                        .copy_from_slice(&b[src_start..src_end]);
-                }
+    /// Builds a float RGBA frame from normalised planes. Colour is kept as is, alpha is clamped.
+    pub fn from_float_planes(width: u32, height: u32, planes: [Vec<f32>; 4]) -> Result<Frame, FrameError> {
+        let [r, g, b, mut a] = planes;
+        a.iter_mut().for_each(|v| *v = v.clamp(0.0, 1.0));
+        Frame::new(width, height, PixelData::RGBAF32(r, g, b, a))
+    }
 
-                Frame::new(width, height, PixelData::RGB(r_out, g_out, b_out))
+    /// Rebuilds a frame of this frame's size, layout and transfer from normalised
+    /// planes. RGBA comes back as float when this frame has more than 8 bits per
+    /// channel and as 8-bit otherwise; gray, RGB and YUV420 keep their own layout
+    /// and depth.
+    pub fn with_planes(&self, planes: [Vec<f32>; 4]) -> Result<Frame, FrameError> {
+        let mut frame = match self.format() {
+            PixelFormat::RGBA if self.data.is_deep() => Frame::from_float_planes(self.width, self.height, planes)?,
+            PixelFormat::RGBA => Frame::from_rgba_planes(self.width, self.height, planes)?,
+            format => {
+                let frame = Frame::from_float_planes(self.width, self.height, planes)?.to_format(format)?;
+                if self.data.is_deep() { frame } else { frame.to_depth(BitDepth::Eight)? }
             }
+        };
+        frame.linear = self.linear;
+        Ok(frame)
+    }
 
-            PixelData::RGBA(r, g, b, a) => {
-                let mut r_out = vec![0u8; len];
-                let mut g_out = vec![0u8; len];
-                let mut b_out = vec![0u8; len];
-                let mut a_out = vec![0u8; len];
-
-                for row in 0..height {
-                    let src_start = ((y + row) * self.width + x) as usize;
-                    let src_end = src_start + width as usize;
-
-                    let dst_start = (row * width) as usize;
-
-                    r_out[dst_start..dst_start + width as usize]
-                        .copy_from_slice(&r[src_start..src_end]);
+    /// `other`'s normalised planes in this frame's transfer: an sRGB frame is decoded
+    /// when this one is linear, and the other way round.
+    fn matching_planes(&self, other: &Frame) -> Result<[Vec<f32>; 4], FrameError> {
+        let [r, g, b, a] = other.rgba_planes()?;
+        if other.linear == self.linear {
+            return Ok([r, g, b, a]);
+        }
+        let transfer = if self.linear { srgb_to_linear } else { linear_to_srgb };
+        let [r, g, b] = [r, g, b].map(|p| p.into_iter().map(transfer).collect::<Vec<f32>>());
+        Ok([r, g, b, a])
+    }
 
-                    g_out[dst_start..dst_start + width as usize]
-                        .copy_from_slice(&g[src_start..src_end]);
+    /// BT.601 luma of every pixel, normalised to 0.0 - 1.0.
+    pub fn luma_plane(&self) -> Result<Vec<f32>, FrameError> {
+        let [r, g, b, _] = self.rgba_planes()?;
+        Ok((0..r.len())
+            .map(|i| 0.299 * r[i] + 0.587 * g[i] + 0.114 * b[i])
+            .collect())
+    }
 
-                    b_out[dst_start..dst_start + width as usize]
-                        .copy_from_slice(&b[src_start..src_end]);
+    /// Converts the frame to the given storage precision. Going down to 8 or 16 bits
+    /// clamps and rounds; going up to float is lossless.
+    pub fn to_depth(&self, depth: BitDepth) -> Result<Frame, FrameError> {
+        let data = match depth {
+            BitDepth::Eight => self.data.to_8bit(),
+            BitDepth::Sixteen => self.data.to_16bit(self.width, self.height)?,
+            BitDepth::Float => self.data.to_rgbaf32(self.width, self.height)?,
+        };
+        let mut frame = Frame::new(self.width, self.height, data)?;
+        frame.linear = self.linear;
+        Ok(frame)
+    }
 
-                    a_out[dst_start..dst_start + width as usize]
-                        .copy_from_slice(&a[src_start..src_end]);
-                }
+    /// Decodes sRGB colour to linear light, so averaging pixels averages light.
+    /// Alpha is untouched. 8-bit frames are widened first, since linear values
+    /// band badly at 8 bits; an already linear frame is returned as is.
+    pub fn to_linear(&self) -> Result<Frame, FrameError> {
+        self.with_transfer(true, srgb_to_linear)
+    }
 
-                Frame::new(width, height, PixelData::RGBA(r_out, g_out, b_out, a_out))
-            }
+    /// Encodes linear-light colour back to sRGB; the inverse of `to_linear`.
+    pub fn to_srgb(&self) -> Result<Frame, FrameError> {
+        self.with_transfer(false, linear_to_srgb)
+    }
 
-            PixelData::YUV420(..) => Err(FrameError::YUVNotApplied),
+    fn with_transfer(&self, linear: bool, transfer: fn(f32) -> f32) -> Result<Frame, FrameError> {
+        if self.linear == linear {
+            return Ok(self.clone());
+        }
+        if self.format() == PixelFormat::YUV420 {
+            return Err(FrameError::YUVNotApplied);
         }
+        let base = if self.data.is_deep() { self.clone() } else { self.to_depth(BitDepth::Sixteen)? };
+        let [r, g, b, a] = base.rgba_planes()?;
+        let [r, g, b] = [r, g, b].map(|p| p.into_iter().map(transfer).collect::<Vec<f32>>());
+        let mut frame = base.with_planes([r, g, b, a])?;
+        frame.metadata = self.metadata.clone();
+        frame.linear = linear;
+        frame.export_depth = self.export_depth;
+        Ok(frame)
     }
-    pub fn resize(&self, target_w: u32, target_h: u32) -> Result<Frame, FrameError> {
-        if target_w == 0 || target_h == 0 {
-            return Err(FrameError::InvalidFrameSize);
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::media::compare::{self, Tolerance};
+
+    #[test]
+    fn conversions_keep_layout_and_round_trip() {
+        assert!(matches!(Color::Gray(40).to_rgba(), Color::RGBA(40, 40, 40, 255)));
+
+        // Two flat 2x2 blocks, so 4:2:0 chroma loses nothing.
+        let (red, blue) = ([200u8, 30, 40], [20u8, 60, 220]);
+        let px = |i: usize| if i % 4 < 2 { red } else { blue };
+        let planes: [Vec<u8>; 3] = std::array::from_fn(|c| (0..8).map(|i| px(i)[c]).collect());
+        let [r, g, b] = planes;
+        let rgb = Frame::new(4, 2, PixelData::RGB(r, g, b)).unwrap();
+
+        let yuv = rgb.to_format(PixelFormat::YUV420).unwrap();
+        assert_eq!(yuv.data().interleave().len(), 8 + 2 + 2);
+        for i in 0..8u32 {
+            let Color::RGB(r, g, b) = yuv.get_pixel(&Pos(i % 4, i / 4)).unwrap() else { panic!() };
+            let want = px(i as usize);
+            assert!([r, g, b].iter().zip(want).all(|(&v, w)| v.abs_diff(w) <= 2), "{:?} vs {want:?}", (r, g, b));
         }
+        assert!(matches!(yuv.crop(1, 0, 2, 2), Err(FrameError::OddYUVSize)));
+
+        let mut gray = rgb.to_format(PixelFormat::Gray).unwrap();
+        gray.set_pixel(&Pos(0, 0), &Color::RGBA(255, 255, 255, 0)).unwrap();
+        assert!(matches!(gray.get_pixel(&Pos(0, 0)), Ok(Color::Gray(255))));
+        let rebuilt = gray.with_planes(gray.rgba_planes().unwrap()).unwrap();
+        assert_eq!(rebuilt.data(), gray.data());
+        assert!(matches!(gray.set_alpha(&Pos(0, 0), 0), Err(FrameError::NoAlphaChannel)));
+    }
 
-        let scale_x = self.width as f32 / target_w as f32;
-        let scale_y = self.height as f32 / target_h as f32;
-        let len = (target_w * target_h) as usize;
+    #[test]
+    fn linear_light_round_trips_and_blends_in_light() {
+        let half = |n| vec![0.5f32; n];
+        let srgb = Frame::from_float_planes(2, 1, [half(2), half(2), half(2), vec![1.0; 2]]).unwrap();
+
+        let linear = srgb.to_linear().unwrap();
+        assert!(linear.is_linear());
+        let [r, ..] = linear.rgba_planes().unwrap();
+        assert!((r[0] - 0.214).abs() < 1e-3);
+
+        let back = linear.to_srgb().unwrap();
+        assert!(!back.is_linear());
+        assert!((back.rgba_planes().unwrap()[0][1] - 0.5).abs() < 1e-5);
+
+        // An sRGB white source is decoded before mixing onto a linear black canvas.
+        let mut canvas = Frame::from_float_planes(1, 1, [vec![0.0], vec![0.0], vec![0.0], vec![1.0]])
+            .unwrap()
+            .to_linear()
+            .unwrap();
+        let white = Frame::from_float_planes(1, 1, [vec![1.0], vec![1.0], vec![1.0], vec![1.0]]).unwrap();
+        canvas.blend_on(&Pos(0, 0), &white, 0.5).unwrap();
+        assert!(canvas.is_linear());
+        let mixed = canvas.to_srgb().unwrap().rgba_planes().unwrap()[0][0];
+        assert!((mixed - 0.735).abs() < 1e-2, "{mixed}");
+    }
 
-        match &self.data {
-            PixelData::GRAY(l) => {
-                let mut out = vec![0u8; len];
-                for y in 0..target_h {
-                    for x in 0..target_w {
-                        let src_x = ((x as f32 * scale_x) as u32).min(self.width - 1);
-                        let src_y = ((y as f32 * scale_y) as u32).min(self.height - 1);
-                        out[(y * target_w + x) as usize] = l[(src_y * self.width + src_x) as usize];
-                    }
-                }
-                Frame::new(target_w, target_h, PixelData::GRAY(out))
-            }
-            PixelData::RGB(r, g, b) => {
-                let mut r_out = vec![0u8; len];
-                let mut g_out = vec![0u8; len];
-                let mut b_out = vec![0u8; len];
-
-                for y in 0..target_h {
-                    for x in 0..target_w {
-                        let src_x = ((x as f32 * scale_x) as u32).min(self.width - 1);
-                        let src_y = ((y as f32 * scale_y) as u32).min(self.height - 1);
-                        let src_idx = (src_y * self.width + src_x) as usize;
-                        let dst_idx = (y * target_w + x) as usize;
-
-                        r_out[dst_idx] = r[src_idx];
-                        g_out[dst_idx] = g[src_idx];
-                        b_out[dst_idx] = b[src_idx];
-                    }
-                }
-                Frame::new(target_w, target_h, PixelData::RGB(r_out, g_out, b_out))
-            }
-            PixelData::RGBA(r, g, b, a) => {
-                let mut r_out = vec![0u8; len];
-                let mut g_out = vec![0u8; len];
-                let mut b_out = vec![0u8; len];
-                let mut a_out = vec![0u8; len];
-
-                for y in 0..target_h {
-                    for x in 0..target_w {
-                        let src_x = ((x as f32 * scale_x) as u32).min(self.width - 1);
-                        let src_y = ((y as f32 * scale_y) as u32).min(self.height - 1);
-                        let src_idx = (src_y * self.width + src_x) as usize;
-                        let dst_idx = (y * target_w + x) as usize;
-
-                        r_out[dst_idx] = r[src_idx];
-                        g_out[dst_idx] = g[src_idx];
-                        b_out[dst_idx] = b[src_idx];
-                        a_out[dst_idx] = a[src_idx];
-                    }
-                }
-                Frame::new(target_w, target_h, PixelData::RGBA(r_out, g_out, b_out, a_out))
-            }
-            PixelData::YUV420(..) => Err(FrameError::YUVNotApplied),
+    #[test]
+    fn every_blend_mode_goes_through_the_overlay_alpha() {
+        // Gray canvas; the overlay is clear white, then half-transparent white.
+        let canvas = Frame::new(2, 1, PixelData::RGBA(vec![100; 2], vec![100; 2], vec![100; 2], vec![255; 2])).unwrap();
+        let overlay = Frame::new(2, 1, PixelData::RGBA(vec![255; 2], vec![255; 2], vec![255; 2], vec![0, 128])).unwrap();
+        for mode in [BlendMode::Normal, BlendMode::Screen] {
+            // The 8-bit path and the float one agree.
+            let (mut bytes, mut floats) = (canvas.clone(), canvas.to_depth(BitDepth::Float).unwrap());
+            bytes.blend_on_with(&Pos(0, 0), &overlay, 1.0, mode).unwrap();
+            floats.blend_on_with(&Pos(0, 0), &overlay, 1.0, mode).unwrap();
+            compare::matches(&bytes, &floats, &Tolerance::max_diff(1.0)).unwrap();
+
+            let [r, _, _, a] = bytes.rgba_planes().unwrap().map(|p| p.iter().map(|v| v * 255.0).collect::<Vec<f32>>());
+            assert_eq!((r[0], a[0]), (100.0, 255.0), "{mode:?} leaves the canvas under a clear pixel");
+            assert!((r[1] - 178.0).abs() <= 1.0, "{mode:?}: {}", r[1]);
         }
     }
-}
 
+    #[test]
+    fn normal_blend_changed_only_for_transparent_overlays() {
+        let canvas = Frame::new(3, 1, PixelData::RGBA(vec![100; 3], vec![100; 3], vec![100; 3], vec![255; 3])).unwrap();
+        let overlay = Frame::new(3, 1, PixelData::RGBA(vec![255; 3], vec![255; 3], vec![255; 3], vec![255, 0, 128])).unwrap();
+        let mut blended = canvas.clone();
+        blended.blend_on(&Pos(0, 0), &overlay, 0.5).unwrap();
+        let PixelData::RGBA(r, _, _, a) = blended.data() else { panic!("layout changed") };
+
+        // The old cross-fade, `canvas * 0.5 + overlay * 0.5` on all four channels, gave
+        // (177, 255), (177, 127) and (177, 191), truncating. An opaque overlay still
+        // matches it, now rounded...
+        assert_eq!((r[0], a[0]), (178, 255));
+        // ...but a clear one now leaves the canvas alone instead of halving its alpha,
+        // and a half-clear one adds half as much colour and keeps the canvas opaque.
+        assert_eq!((r[1], a[1]), (100, 255));
+        assert_eq!((r[2], a[2]), (139, 255));
+    }
+}
@@ -0,0 +1,295 @@
+use crate::media::frame::{BitDepth, Color, Frame, FrameError, PixelData};
+use crate::pipeline::plane::par_pixels;
+
+// ── Procedural frame sources ─────────────────────────────────────────────────
+//
+// Gradients and noise are computed in float and stored as 16-bit RGBA so smooth
+// ramps do not band; flat fills and checkerboards are exact at 8 bits. Noise is
+// seeded through an integer hash of the lattice cell, so the same seed always
+// gives the same image and there is no permutation table to repeat.
+
+/// One colour of a gradient. Stops without an offset are spread evenly between
+/// their neighbours, as in CSS; the first defaults to 0 and the last to 1.
+#[derive(Debug, Clone, Copy)]
+pub struct GradientStop {
+    pub offset: Option<f32>,
+    pub color: Color,
+}
+
+#[derive(Debug, Clone)]
+pub struct Gradient {
+    /// Offsets ascending, colours premultiplied on the 0.0 - 1.0 scale.
+    stops: Vec<(f32, [f32; 4])>,
+}
+
+impl Gradient {
+    pub fn new(stops: &[GradientStop]) -> Option<Gradient> {
+        if stops.is_empty() {
+            return None;
+        }
+        let mut offsets: Vec<Option<f32>> = stops.iter().map(|s| s.offset).collect();
+        let last = offsets.len() - 1;
+        offsets[0] = offsets[0].or(Some(0.0));
+        offsets[last] = offsets[last].or(Some(1.0));
+
+        // An offset before an earlier one is pulled up to it, as CSS does.
+        let mut floor = f32::MIN;
+        for offset in offsets.iter_mut().flatten() {
+            *offset = offset.max(floor);
+            floor = *offset;
+        }
+
+        let mut resolved = vec![0.0f32; offsets.len()];
+        let mut i = 0;
+        while i < offsets.len() {
+            if let Some(offset) = offsets[i] {
+                resolved[i] = offset;
+                i += 1;
+                continue;
+            }
+            let start = i - 1;
+            let end = (i..offsets.len()).find(|&j| offsets[j].is_some()).unwrap_or(last);
+            let (a, b) = (resolved[start], offsets[end].unwrap_or(1.0));
+            for (k, slot) in resolved.iter_mut().enumerate().take(end).skip(i) {
+                *slot = a + (b - a) * (k - start) as f32 / (end - start) as f32;
+            }
+            i = end;
+        }
+
+        let stops = resolved
+            .into_iter()
+            .zip(stops)
+            .map(|(offset, stop)| {
+                let [r, g, b, a] = stop.color.channels_f32().map(|v| v / 255.0);
+                (offset, [r * a, g * a, b * a, a])
+            })
+            .collect();
+        Some(Gradient { stops })
+    }
+
+    /// Straight RGBA at position `t`, clamped to the end stops.
+    pub fn sample(&self, t: f32) -> [f32; 4] {
+        let stops = &self.stops;
+        let premultiplied = if t <= stops[0].0 {
+            stops[0].1
+        } else if t >= stops[stops.len() - 1].0 {
+            stops[stops.len() - 1].1
+        } else {
+            let i = stops.iter().rposition(|s| s.0 <= t).unwrap_or(0);
+            let ((t0, c0), (t1, c1)) = (stops[i], stops[i + 1]);
+            let f = if t1 > t0 { (t - t0) / (t1 - t0) } else { 1.0 };
+            std::array::from_fn(|c| c0[c] + (c1[c] - c0[c]) * f)
+        };
+        let [r, g, b, a] = premultiplied;
+        if a > 0.0 { [r / a, g / a, b / a, a] } else { [0.0; 4] }
+    }
+}
+
+fn from_pixels(width: u32, height: u32, pixels: Vec<[f32; 4]>) -> Result<Frame, FrameError> {
+    let planes: [Vec<f32>; 4] = std::array::from_fn(|c| pixels.iter().map(|p| p[c]).collect());
+    Frame::from_float_planes(width, height, planes)?.to_depth(BitDepth::Sixteen)
+}
+
+pub fn solid(width: u32, height: u32, color: Color) -> Result<Frame, FrameError> {
+    let [r, g, b, a] = color.to_rgba().channels_f32().map(|v| v as u8);
+    let len = (width * height) as usize;
+    Frame::new(width, height, PixelData::RGBA(vec![r; len], vec![g; len], vec![b; len], vec![a; len]))
+}
+
+/// Gradient along a line through the centre at `angle` degrees: 0 runs bottom to
+/// top, 90 left to right, like CSS. The line is just long enough for the first and
+/// last stops to reach the corners.
+pub fn linear_gradient(width: u32, height: u32, angle: f32, gradient: &Gradient) -> Result<Frame, FrameError> {
+    let (sin, cos) = angle.to_radians().sin_cos();
+    let (dx, dy) = (sin, -cos);
+    let length = (width as f32 * sin).abs() + (height as f32 * cos).abs();
+    let (cx, cy) = (width as f32 / 2.0, height as f32 / 2.0);
+    let pixels = par_pixels(width as usize, height as usize, |x, y| {
+        let along = (x as f32 + 0.5 - cx) * dx + (y as f32 + 0.5 - cy) * dy;
+        gradient.sample(if length > 0.0 { along / length + 0.5 } else { 0.0 })
+    });
+    from_pixels(width, height, pixels)
+}
+
+/// Circular gradient from `(cx, cy)` out to `radius` pixels.
+pub fn radial_gradient(width: u32, height: u32, cx: f32, cy: f32, radius: f32, gradient: &Gradient) -> Result<Frame, FrameError> {
+    let pixels = par_pixels(width as usize, height as usize, |x, y| {
+        let distance = (x as f32 + 0.5 - cx).hypot(y as f32 + 0.5 - cy);
+        gradient.sample(if radius > 0.0 { distance / radius } else { 1.0 })
+    });
+    from_pixels(width, height, pixels)
+}
+
+/// Squares of `size` pixels alternating between `a` (top left) and `b`.
+pub fn checkerboard(width: u32, height: u32, size: u32, a: Color, b: Color) -> Result<Frame, FrameError> {
+    let size = size.max(1) as usize;
+    let (a, b) = (a.to_rgba().channels_f32(), b.to_rgba().channels_f32());
+    let pixels = par_pixels(width as usize, height as usize, |x, y| if (x / size + y / size) % 2 == 0 { a } else { b });
+    let planes: [Vec<u8>; 4] = std::array::from_fn(|c| pixels.iter().map(|p| p[c] as u8).collect());
+    let [r, g, bl, al] = planes;
+    Frame::new(width, height, PixelData::RGBA(r, g, bl, al))
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum NoiseKind {
+    /// Gradient noise on a square lattice.
+    Perlin,
+    /// Gradient noise on a triangular lattice: fewer axis-aligned artefacts.
+    Simplex,
+    /// Distance to the nearest of one random point per cell: cells and pebbles.
+    Worley,
+}
+
+#[derive(Debug, Clone, Copy)]
+pub struct Noise {
+    pub kind: NoiseKind,
+    /// Feature size in pixels: one lattice cell of the first octave.
+    pub scale: f32,
+    pub seed: u32,
+    /// Octaves of fractal noise, each at twice the frequency and half the amplitude.
+    pub octaves: u32,
+}
+
+impl Noise {
+    /// Noise value in 0.0 - 1.0 at a point given in pixels.
+    pub fn sample(&self, x: f32, y: f32) -> f32 {
+        let (mut total, mut weight, mut amplitude, mut frequency) = (0.0, 0.0, 1.0, 1.0 / self.scale.max(1e-3));
+        for octave in 0..self.octaves.max(1) {
+            let seed = self.seed.wrapping_add(octave.wrapping_mul(0x9e37_79b9));
+            let (px, py) = (x * frequency, y * frequency);
+            let value = match self.kind {
+                NoiseKind::Perlin => 0.5 + 0.5 * perlin(px, py, seed),
+                NoiseKind::Simplex => 0.5 + 0.5 * simplex(px, py, seed),
+                NoiseKind::Worley => worley(px, py, seed),
+            };
+            total += value * amplitude;
+            weight += amplitude;
+            amplitude *= 0.5;
+            frequency *= 2.0;
+        }
+        (total / weight).clamp(0.0, 1.0)
+    }
+
+    /// Opaque grayscale frame of the noise.
+    pub fn render(&self, width: u32, height: u32) -> Result<Frame, FrameError> {
+        let pixels = par_pixels(width as usize, height as usize, |x, y| {
+            let v = self.sample(x as f32 + 0.5, y as f32 + 0.5);
+            [v, v, v, 1.0]
+        });
+        from_pixels(width, height, pixels)
+    }
+}
+
+/// Integer hash of a lattice point, well mixed in every bit.
+fn hash(x: i32, y: i32, seed: u32) -> u32 {
+    let mut h = (x as u32).wrapping_mul(0x8da6_b343) ^ (y as u32).wrapping_mul(0xd816_3841) ^ seed.wrapping_mul(0xcb1a_b31f);
+    h ^= h >> 16;
+    h = h.wrapping_mul(0x7feb_352d);
+    h ^= h >> 15;
+    h = h.wrapping_mul(0x846c_a68b);
+    h ^ (h >> 16)
+}
+
+/// Dot product of the offset with one of eight gradient directions.
+fn gradient_dot(h: u32, dx: f32, dy: f32) -> f32 {
+    match h & 7 {
+        0 => dx + dy,
+        1 => dx - dy,
+        2 => -dx + dy,
+        3 => -dx - dy,
+        4 => dx,
+        5 => -dx,
+        6 => dy,
+        _ => -dy,
+    }
+}
+
+/// Classic Perlin noise, roughly -1.0 - 1.0.
+fn perlin(x: f32, y: f32, seed: u32) -> f32 {
+    let (x0, y0) = (x.floor(), y.floor());
+    let (fx, fy) = (x - x0, y - y0);
+    let (ix, iy) = (x0 as i32, y0 as i32);
+    let fade = |t: f32| t * t * t * (t * (t * 6.0 - 15.0) + 10.0);
+    let (u, v) = (fade(fx), fade(fy));
+    let corner = |cx: i32, cy: i32| gradient_dot(hash(ix + cx, iy + cy, seed), fx - cx as f32, fy - cy as f32);
+    let top = corner(0, 0) + u * (corner(1, 0) - corner(0, 0));
+    let bottom = corner(0, 1) + u * (corner(1, 1) - corner(0, 1));
+    top + v * (bottom - top)
+}
+
+/// 2D simplex noise, roughly -1.0 - 1.0.
+fn simplex(x: f32, y: f32, seed: u32) -> f32 {
+    const F2: f32 = 0.366_025_42; // (sqrt(3) - 1) / 2
+    const G2: f32 = 0.211_324_87; // (3 - sqrt(3)) / 6
+
+    let s = (x + y) * F2;
+    let (i, j) = ((x + s).floor(), (y + s).floor());
+    let t = (i + j) * G2;
+    let (x0, y0) = (x - (i - t), y - (j - t));
+    let (i1, j1) = if x0 > y0 { (1, 0) } else { (0, 1) };
+    let corners = [
+        (x0, y0, 0, 0),
+        (x0 - i1 as f32 + G2, y0 - j1 as f32 + G2, i1, j1),
+        (x0 - 1.0 + 2.0 * G2, y0 - 1.0 + 2.0 * G2, 1, 1),
+    ];
+    let (i, j) = (i as i32, j as i32);
+    let total: f32 = corners
+        .iter()
+        .map(|&(dx, dy, ci, cj)| {
+            let falloff = 0.5 - dx * dx - dy * dy;
+            if falloff <= 0.0 {
+                return 0.0;
+            }
+            falloff.powi(4) * gradient_dot(hash(i + ci, j + cj, seed), dx, dy)
+        })
+        .sum();
+    70.0 * total
+}
+
+/// Distance to the nearest feature point, one per lattice cell, in cell units.
+fn worley(x: f32, y: f32, seed: u32) -> f32 {
+    let (ix, iy) = (x.floor() as i32, y.floor() as i32);
+    let mut nearest = f32::MAX;
+    for cy in iy - 1..=iy + 1 {
+        for cx in ix - 1..=ix + 1 {
+            let h = hash(cx, cy, seed);
+            let px = cx as f32 + (h & 0xffff) as f32 / 65535.0;
+            let py = cy as f32 + (h >> 16) as f32 / 65535.0;
+            nearest = nearest.min((px - x).hypot(py - y));
+        }
+    }
+    nearest.min(1.0)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn gradients_spread_stops_and_noise_is_seeded() {
+        let stop = |offset, color| GradientStop { offset, color };
+        let gradient = Gradient::new(&[
+            stop(None, Color::RGBA(0, 0, 0, 255)),
+            stop(None, Color::RGBA(255, 0, 0, 255)),
+            stop(Some(0.8), Color::RGBA(255, 255, 255, 255)),
+            stop(None, Color::RGBA(0, 0, 255, 255)),
+        ])
+        .unwrap();
+        // The unplaced second stop sits halfway between 0 and 0.8.
+        assert_eq!(gradient.sample(0.4), [1.0, 0.0, 0.0, 1.0]);
+        assert_eq!(gradient.sample(-1.0), [0.0, 0.0, 0.0, 1.0]);
+        let mid = gradient.sample(0.9);
+        assert!(mid.iter().zip([0.5, 0.5, 1.0, 1.0]).all(|(v, want)| (v - want).abs() < 1e-5));
+
+        for kind in [NoiseKind::Perlin, NoiseKind::Simplex, NoiseKind::Worley] {
+            let noise = Noise { kind, scale: 8.0, seed: 42, octaves: 3 };
+            let a = noise.render(32, 32).unwrap().rgba_planes().unwrap();
+            let b = noise.render(32, 32).unwrap().rgba_planes().unwrap();
+            let other = Noise { seed: 43, ..noise }.render(32, 32).unwrap().rgba_planes().unwrap();
+            assert_eq!(a[0], b[0]);
+            assert_ne!(a[0], other[0]);
+            let (lo, hi) = a[0].iter().fold((1.0f32, 0.0f32), |(lo, hi), &v| (lo.min(v), hi.max(v)));
+            assert!(lo >= 0.0 && hi <= 1.0 && hi - lo > 0.3, "{kind:?} spans {lo}..{hi}");
+        }
+    }
+}
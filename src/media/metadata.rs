@@ -0,0 +1,268 @@
+/// Metadata blocks carried by a decoded image so they can be read from scripts
+/// and written back on export.
+///
+/// Blocks are kept as the raw bytes the decoder handed out. `exif` is a TIFF
+/// structure starting with `II*\0` or `MM\0*`, the same layout every container
+/// stores it in once the container's own header is removed.
+#[derive(Debug, Clone, Default, PartialEq)]
+pub struct ImageMetadata {
+    pub exif: Option<Vec<u8>>,
+    pub icc: Option<Vec<u8>>,
+    pub xmp: Option<Vec<u8>>,
+}
+
+/// A decoded EXIF field.
+#[derive(Debug, Clone, PartialEq)]
+pub enum ExifValue {
+    Text(String),
+    Numbers(Vec<f64>),
+}
+
+/// Which IFD a tag lives in. GPS tags reuse small tag numbers, so the number
+/// alone does not identify a field.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+enum Ifd {
+    Primary,
+    Exif,
+    Gps,
+}
+
+const EXIF_POINTER: u16 = 0x8769;
+const GPS_POINTER: u16 = 0x8825;
+
+/// Tags `meta()` can look up by name.
+const TAGS: &[(&str, Ifd, u16)] = &[
+    ("ImageDescription", Ifd::Primary, 0x010E),
+    ("Make", Ifd::Primary, 0x010F),
+    ("Model", Ifd::Primary, 0x0110),
+    ("Orientation", Ifd::Primary, 0x0112),
+    ("XResolution", Ifd::Primary, 0x011A),
+    ("YResolution", Ifd::Primary, 0x011B),
+    ("Software", Ifd::Primary, 0x0131),
+    ("DateTime", Ifd::Primary, 0x0132),
+    ("Artist", Ifd::Primary, 0x013B),
+    ("Copyright", Ifd::Primary, 0x8298),
+    ("ExposureTime", Ifd::Exif, 0x829A),
+    ("FNumber", Ifd::Exif, 0x829D),
+    ("ISOSpeedRatings", Ifd::Exif, 0x8827),
+    ("DateTimeOriginal", Ifd::Exif, 0x9003),
+    ("DateTimeDigitized", Ifd::Exif, 0x9004),
+    ("OffsetTime", Ifd::Exif, 0x9010),
+    ("OffsetTimeOriginal", Ifd::Exif, 0x9011),
+    ("ExposureBiasValue", Ifd::Exif, 0x9204),
+    ("Flash", Ifd::Exif, 0x9209),
+    ("FocalLength", Ifd::Exif, 0x920A),
+    ("PixelXDimension", Ifd::Exif, 0xA002),
+    ("PixelYDimension", Ifd::Exif, 0xA003),
+    ("LensMake", Ifd::Exif, 0xA433),
+    ("LensModel", Ifd::Exif, 0xA434),
+    ("GPSLatitudeRef", Ifd::Gps, 0x0001),
+    ("GPSLatitude", Ifd::Gps, 0x0002),
+    ("GPSLongitudeRef", Ifd::Gps, 0x0003),
+    ("GPSLongitude", Ifd::Gps, 0x0004),
+    ("GPSAltitude", Ifd::Gps, 0x0006),
+];
+
+impl ImageMetadata {
+    pub fn is_empty(&self) -> bool {
+        self.exif.is_none() && self.icc.is_none() && self.xmp.is_none()
+    }
+
+    /// Names accepted by `exif_field`, in table order.
+    pub fn known_tags() -> impl Iterator<Item = &'static str> {
+        TAGS.iter().map(|(name, _, _)| *name)
+    }
+
+    /// Looks up an EXIF field by its standard name, e.g. `"DateTimeOriginal"`.
+    /// Returns `None` when the image has no EXIF block or the field is absent;
+    /// callers check `known_tags` to tell that apart from a misspelt name.
+    pub fn exif_field(&self, name: &str) -> Option<ExifValue> {
+        let &(_, ifd, tag) = TAGS.iter().find(|(n, _, _)| n.eq_ignore_ascii_case(name))?;
+        let exif = ExifReader::new(self.exif.as_deref()?)?;
+        let offset = match ifd {
+            Ifd::Primary => exif.first_ifd()?,
+            Ifd::Exif => exif.pointer(EXIF_POINTER)?,
+            Ifd::Gps => exif.pointer(GPS_POINTER)?,
+        };
+        exif.read_field(offset, tag)
+    }
+
+    /// Sets PixelXDimension and PixelYDimension, where the EXIF block has them, to
+    /// the size the image is written at; after a resize or crop they would still give
+    /// the source's. The fields are rewritten in place as LONGs, which fit inline just
+    /// as the SHORTs some writers use do.
+    pub fn set_pixel_dimensions(&mut self, width: u32, height: u32) {
+        let Some(exif) = self.exif.as_mut() else { return };
+        let start = if exif.starts_with(b"Exif\0\0") { 6 } else { 0 };
+        let Some((little_endian, entries)) = ExifReader::new(exif).and_then(|reader| {
+            let ifd = reader.pointer(EXIF_POINTER)?;
+            let entries = [(0xA002, width), (0xA003, height)].map(|(tag, value)| {
+                let entry = reader.find_entry(ifd, tag)?;
+                let single = matches!(reader.u16_at(entry + 2), Some(3 | 4)) && reader.u32_at(entry + 4) == Some(1);
+                (single && reader.u32_at(entry + 8).is_some()).then_some((start + entry, value))
+            });
+            Some((reader.little_endian, entries))
+        }) else {
+            return;
+        };
+        for (entry, value) in entries.into_iter().flatten() {
+            let (kind, count, value) = if little_endian {
+                (4u16.to_le_bytes(), 1u32.to_le_bytes(), value.to_le_bytes())
+            } else {
+                (4u16.to_be_bytes(), 1u32.to_be_bytes(), value.to_be_bytes())
+            };
+            exif[entry + 2..entry + 4].copy_from_slice(&kind);
+            exif[entry + 4..entry + 8].copy_from_slice(&count);
+            exif[entry + 8..entry + 12].copy_from_slice(&value);
+        }
+    }
+}
+
+/// Bounds-checked reader over a TIFF-structured EXIF block. Every offset in the
+/// block comes from the file, so nothing here indexes without checking.
+struct ExifReader<'a> {
+    data: &'a [u8],
+    little_endian: bool,
+}
+
+impl<'a> ExifReader<'a> {
+    fn new(data: &'a [u8]) -> Option<Self> {
+        // Some writers keep the JPEG APP1 prefix; skip it if present.
+        let data = data.strip_prefix(b"Exif\0\0").unwrap_or(data);
+        let little_endian = match data.get(0..4)? {
+            [0x49, 0x49, 42, 0] => true,
+            [0x4D, 0x4D, 0, 42] => false,
+            _ => return None,
+        };
+        Some(Self { data, little_endian })
+    }
+
+    fn u16_at(&self, offset: usize) -> Option<u16> {
+        let bytes: [u8; 2] = self.data.get(offset..offset + 2)?.try_into().ok()?;
+        Some(if self.little_endian { u16::from_le_bytes(bytes) } else { u16::from_be_bytes(bytes) })
+    }
+
+    fn u32_at(&self, offset: usize) -> Option<u32> {
+        let bytes: [u8; 4] = self.data.get(offset..offset + 4)?.try_into().ok()?;
+        Some(if self.little_endian { u32::from_le_bytes(bytes) } else { u32::from_be_bytes(bytes) })
+    }
+
+    fn first_ifd(&self) -> Option<usize> {
+        Some(self.u32_at(4)? as usize)
+    }
+
+    /// Offset of a sub-IFD referenced from the primary IFD.
+    fn pointer(&self, tag: u16) -> Option<usize> {
+        let entry = self.find_entry(self.first_ifd()?, tag)?;
+        Some(self.u32_at(entry + 8)? as usize)
+    }
+
+    /// Offset of the 12-byte directory entry for `tag` in the IFD at `ifd`.
+    fn find_entry(&self, ifd: usize, tag: u16) -> Option<usize> {
+        let count = self.u16_at(ifd)? as usize;
+        (0..count)
+            .map(|i| ifd + 2 + i * 12)
+            .find(|&entry| self.u16_at(entry) == Some(tag))
+    }
+
+    fn read_field(&self, ifd: usize, tag: u16) -> Option<ExifValue> {
+        let entry = self.find_entry(ifd, tag)?;
+        let kind = self.u16_at(entry + 2)?;
+        let count = self.u32_at(entry + 4)? as usize;
+
+        let size: usize = match kind {
+            1 | 2 | 6 | 7 => 1,
+            3 | 8 => 2,
+            4 | 9 => 4,
+            5 | 10 => 8,
+            _ => return None,
+        };
+        let len = size.checked_mul(count)?;
+        // Values of four bytes or less are stored inline in the entry.
+        let start = if len <= 4 { entry + 8 } else { self.u32_at(entry + 8)? as usize };
+        let bytes = self.data.get(start..start.checked_add(len)?)?;
+
+        if kind == 2 {
+            let text = bytes.split(|&b| b == 0).next().unwrap_or(&[]);
+            return Some(ExifValue::Text(String::from_utf8_lossy(text).trim().to_string()));
+        }
+
+        let numbers = (0..count)
+            .map(|i| {
+                let at = start + i * size;
+                Some(match kind {
+                    1 | 7 => self.data[at] as f64,
+                    6 => self.data[at] as i8 as f64,
+                    3 => self.u16_at(at)? as f64,
+                    8 => self.u16_at(at)? as i16 as f64,
+                    4 => self.u32_at(at)? as f64,
+                    9 => self.u32_at(at)? as i32 as f64,
+                    5 => rational(self.u32_at(at)? as f64, self.u32_at(at + 4)? as f64),
+                    _ => rational(self.u32_at(at)? as i32 as f64, self.u32_at(at + 4)? as i32 as f64),
+                })
+            })
+            .collect::<Option<Vec<f64>>>()?;
+        Some(ExifValue::Numbers(numbers))
+    }
+}
+
+fn rational(num: f64, den: f64) -> f64 {
+    if den == 0.0 { 0.0 } else { num / den }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// Little-endian block with Make in IFD0, and DateTimeOriginal and a 4000 x 3000
+    /// pixel size (one SHORT, one LONG) in the Exif IFD.
+    fn sample_exif() -> Vec<u8> {
+        let mut b = vec![0x49, 0x49, 42, 0, 8, 0, 0, 0];
+        // IFD0 at 8: Make stored inline, then the pointer to the Exif IFD.
+        b.extend_from_slice(&2u16.to_le_bytes());
+        b.extend_from_slice(&[0x0F, 0x01, 2, 0, 4, 0, 0, 0, b'A', b'B', b'C', 0]);
+        b.extend_from_slice(&[0x69, 0x87, 4, 0, 1, 0, 0, 0, 38, 0, 0, 0]);
+        b.extend_from_slice(&0u32.to_le_bytes());
+        // Exif IFD at 38: the date points at a 20-byte string at 80.
+        b.extend_from_slice(&3u16.to_le_bytes());
+        b.extend_from_slice(&[0x03, 0x90, 2, 0, 20, 0, 0, 0, 80, 0, 0, 0]);
+        b.extend_from_slice(&[0x02, 0xA0, 3, 0, 1, 0, 0, 0, 0xA0, 0x0F, 0, 0]);
+        b.extend_from_slice(&[0x03, 0xA0, 4, 0, 1, 0, 0, 0, 0xB8, 0x0B, 0, 0]);
+        b.extend_from_slice(&0u32.to_le_bytes());
+        b.extend_from_slice(b"2024:05:01 12:30:00\0");
+        b
+    }
+
+    #[test]
+    fn reads_fields_from_primary_and_exif_ifd() {
+        let meta = ImageMetadata { exif: Some(sample_exif()), ..Default::default() };
+        assert_eq!(meta.exif_field("Make"), Some(ExifValue::Text("ABC".into())));
+        assert_eq!(
+            meta.exif_field("DateTimeOriginal"),
+            Some(ExifValue::Text("2024:05:01 12:30:00".into()))
+        );
+        assert_eq!(meta.exif_field("Model"), None);
+        assert_eq!(meta.exif_field("PixelXDimension"), Some(ExifValue::Numbers(vec![4000.0])));
+        assert_eq!(meta.exif_field("PixelYDimension"), Some(ExifValue::Numbers(vec![3000.0])));
+
+        let mut truncated = sample_exif();
+        truncated.truncate(60);
+        let meta = ImageMetadata { exif: Some(truncated), ..Default::default() };
+        assert_eq!(meta.exif_field("DateTimeOriginal"), None);
+    }
+
+    #[test]
+    fn pixel_dimensions_follow_the_written_size() {
+        let mut meta = ImageMetadata { exif: Some(sample_exif()), ..Default::default() };
+        meta.set_pixel_dimensions(640, 70000);
+        assert_eq!(meta.exif_field("PixelXDimension"), Some(ExifValue::Numbers(vec![640.0])));
+        assert_eq!(meta.exif_field("PixelYDimension"), Some(ExifValue::Numbers(vec![70000.0])));
+        assert_eq!(meta.exif_field("Make"), Some(ExifValue::Text("ABC".into())));
+        assert_eq!(meta.exif_field("DateTimeOriginal"), Some(ExifValue::Text("2024:05:01 12:30:00".into())));
+
+        // Blocks without the fields are left as they are.
+        let mut bare = ImageMetadata { exif: Some(sample_exif()[..38].to_vec()), ..Default::default() };
+        bare.set_pixel_dimensions(1, 1);
+        assert_eq!(bare.exif.as_deref(), Some(&sample_exif()[..38]));
+    }
+}
@@ -1,3 +1,8 @@
+pub mod compare;
 pub mod frame;
+pub mod generate;
+pub mod metadata;
+pub mod montage;
+pub mod pyramid;
 pub mod track;
 pub mod video;
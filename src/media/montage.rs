@@ -0,0 +1,174 @@
+use std::fmt;
+use std::rc::Rc;
+
+use fontdue::Font;
+
+use crate::media::frame::{composite, BlendMode, Color, Frame, FrameError, Pos};
+use crate::text::{Align, Text, TextError, TextLayout};
+
+// ── Contact sheets ───────────────────────────────────────────────────────────
+//
+// Frames are laid out left to right, top to bottom, one per cell, each scaled to
+// fit its cell without changing shape and centred in it. Shrinking goes through the
+// Gaussian pyramid first, so fine detail averages out instead of aliasing. Captions
+// sit in a band under each cell, centred, and are cut to the cell's width.
+
+/// Grid layout for `Montage::render`. The gap is also left around the edges.
+#[derive(Debug, Clone, Copy)]
+pub struct Montage {
+    pub columns: u32,
+    pub cell_width: u32,
+    pub cell_height: u32,
+    pub gap: u32,
+    pub background: Color,
+}
+
+/// One line of text under each cell, in order; cells past the end of `labels` get none.
+pub struct Captions {
+    pub labels: Vec<String>,
+    pub font: Rc<Font>,
+    /// Tried in order for characters the primary font has no glyph for.
+    pub fallbacks: Vec<Rc<Font>>,
+    pub size: f32,
+    pub color: Color,
+}
+
+#[derive(Debug)]
+pub enum MontageError {
+    NoFrames,
+    Frame(FrameError),
+    Text(TextError),
+}
+
+impl fmt::Display for MontageError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            MontageError::NoFrames => write!(f, "a montage needs at least one frame"),
+            MontageError::Frame(e) => write!(f, "{e}"),
+            MontageError::Text(e) => write!(f, "caption: {e}"),
+        }
+    }
+}
+
+impl std::error::Error for MontageError {}
+
+impl From<FrameError> for MontageError {
+    fn from(e: FrameError) -> Self {
+        MontageError::Frame(e)
+    }
+}
+
+/// Source-over of `src` onto the sheet planes with its top-left corner at `(x, y)`.
+/// Whatever falls outside the sheet is dropped.
+fn paste(sheet: &mut [Vec<f32>; 4], sheet_w: usize, src: &Frame, x: usize, y: usize) -> Result<(), FrameError> {
+    let planes = src.rgba_planes()?;
+    let (w, h) = (src.width() as usize, src.height() as usize);
+    let sheet_h = sheet[3].len() / sheet_w;
+    for row in 0..h.min(sheet_h.saturating_sub(y)) {
+        for col in 0..w.min(sheet_w.saturating_sub(x)) {
+            let (i, j) = (row * w + col, (y + row) * sheet_w + x + col);
+            let color = [planes[0][i], planes[1][i], planes[2][i]];
+            composite(sheet, j, color, planes[3][i].clamp(0.0, 1.0), BlendMode::Normal);
+        }
+    }
+    Ok(())
+}
+
+/// `frame` scaled to fit inside `width` x `height`, keeping its aspect ratio, in sRGB.
+fn fit(frame: &Frame, width: u32, height: u32) -> Result<Frame, FrameError> {
+    let srgb;
+    let frame = if frame.is_linear() {
+        srgb = frame.to_srgb()?;
+        &srgb
+    } else {
+        frame
+    };
+    let scale = (width as f64 / frame.width() as f64).min(height as f64 / frame.height() as f64);
+    let tw = ((frame.width() as f64 * scale).round() as u32).clamp(1, width);
+    let th = ((frame.height() as f64 * scale).round() as u32).clamp(1, height);
+
+    // Halve while the result still covers the target, then resample the rest.
+    let (mut levels, mut w, mut h) = (1, frame.width(), frame.height());
+    while w.div_ceil(2) >= tw && h.div_ceil(2) >= th && (w > tw || h > th) {
+        (w, h) = (w.div_ceil(2), h.div_ceil(2));
+        levels += 1;
+    }
+    let source = match levels {
+        1 => frame.clone(),
+        _ => frame.gaussian_pyramid(levels)?.pop().ok_or(FrameError::EmptyFrame)?,
+    };
+    source.resize(tw, th)
+}
+
+impl Montage {
+    /// Height of the caption band under each cell.
+    fn caption_height(captions: Option<&Captions>) -> u32 {
+        captions.map_or(0, |c| (c.size.max(1.0) * 1.5).ceil() as u32)
+    }
+
+    /// Lays `frames` out on one sheet of the background colour.
+    pub fn render(&self, frames: &[Frame], captions: Option<&Captions>) -> Result<Frame, MontageError> {
+        if frames.is_empty() {
+            return Err(MontageError::NoFrames);
+        }
+        let columns = self.columns.clamp(1, frames.len() as u32);
+        let rows = (frames.len() as u32).div_ceil(columns);
+        let (cell_w, cell_h) = (self.cell_width.max(1), self.cell_height.max(1));
+        let band = Self::caption_height(captions);
+        let width = columns * cell_w + (columns + 1) * self.gap;
+        let height = rows * (cell_h + band) + (rows + 1) * self.gap;
+
+        let background = self.background.channels_f32().map(|c| c / 255.0);
+        let len = width as usize * height as usize;
+        let mut sheet = background.map(|c| vec![c; len]);
+
+        for (index, frame) in frames.iter().enumerate() {
+            let (col, row) = (index as u32 % columns, index as u32 / columns);
+            let (x, y) = (self.gap + col * (cell_w + self.gap), self.gap + row * (cell_h + band + self.gap));
+            let thumb = fit(frame, cell_w, cell_h)?;
+            let (tx, ty) = (x + (cell_w - thumb.width()) / 2, y + (cell_h - thumb.height()) / 2);
+            paste(&mut sheet, width as usize, &thumb, tx as usize, ty as usize)?;
+
+            let Some(captions) = captions else { continue };
+            let Some(label) = captions.labels.get(index).filter(|l| !l.is_empty()) else { continue };
+            let mut text = Text::new(label, captions.font.clone(), captions.size, Pos(0, 0), captions.color);
+            text.set_fallbacks(captions.fallbacks.clone());
+            text.set_layout(TextLayout { max_width: Some(cell_w as f32), align: Align::Center, ..TextLayout::default() });
+            let caption = text.picturize().map_err(MontageError::Text)?;
+            let caption = caption.crop(0, 0, caption.width().min(cell_w), caption.height().min(band))?;
+            let cx = x + (cell_w - caption.width()) / 2;
+            paste(&mut sheet, width as usize, &caption, cx as usize, (y + cell_h) as usize)?;
+        }
+
+        Ok(Frame::from_float_planes(width, height, sheet)?)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::media::generate;
+
+    #[test]
+    fn frames_are_fitted_into_a_grid() {
+        let wide = generate::solid(400, 100, Color::RGBA(255, 0, 0, 255)).unwrap();
+        let tall = generate::solid(50, 200, Color::RGBA(0, 0, 255, 255)).unwrap();
+        let clear = generate::solid(10, 10, Color::RGBA(0, 255, 0, 0)).unwrap();
+        let montage = Montage { columns: 2, cell_width: 80, cell_height: 60, gap: 4, background: Color::RGBA(255, 255, 255, 255) };
+        let sheet = montage.render(&[wide, tall, clear], None).unwrap();
+        assert_eq!((sheet.width(), sheet.height()), (2 * 80 + 3 * 4, 2 * 60 + 3 * 4));
+
+        let at = |x, y| sheet.get_pixel(&Pos(x, y)).unwrap().channels_f32().map(|c| c.round());
+        let white = [255.0; 4];
+        // 400x100 fits as 80x20, centred vertically in the first cell.
+        assert_eq!(at(4, 4 + 20), [255.0, 0.0, 0.0, 255.0]);
+        assert_eq!(at(4, 4 + 19), white);
+        assert_eq!(at(83, 4 + 39), [255.0, 0.0, 0.0, 255.0]);
+        // 50x200 fits as 15x60 in the middle of the second cell.
+        assert_eq!(at(88 + 40, 30), [0.0, 0.0, 255.0, 255.0]);
+        assert_eq!(at(88 + 30, 30), white);
+        // A transparent frame leaves the background showing.
+        assert_eq!(at(4 + 40, 68 + 30), white);
+        assert!(matches!(montage.render(&[], None), Err(MontageError::NoFrames)));
+    }
+}
@@ -13,6 +13,9 @@ pub struct Track {
     buffer: Vec<AudioFrame>,
 }
 
+/// Samples per chunk in tracks built here.
+const CHUNK_SAMPLES: usize = 1024;
+
 #[derive(Debug)]
 pub enum TrackError {
     MixingIsNotPossible,
@@ -29,6 +32,21 @@ impl Track {
         }
     }
 
+    /// A track of `channels`, all the same length, cut into frames of `chunk` samples
+    /// timed in samples from 0.
+    #[cfg(test)]
+    pub(crate) fn from_channels(sample_rate: u32, channels: Vec<Vec<f32>>, chunk: usize) -> Self {
+        let len = channels.first().map_or(0, Vec::len);
+        let buffer = (0..len)
+            .step_by(chunk)
+            .map(|offset| AudioFrame {
+                time: TimeStamp { value: offset as i64, num: 1, den: sample_rate },
+                data: channels.iter().map(|c| c[offset..(offset + chunk).min(len)].to_vec()).collect(),
+            })
+            .collect();
+        Track::new(sample_rate, channels.len() as u16, buffer)
+    }
+
     pub fn sample_rate(&self) -> u32 {
         self.sample_rate
     }
@@ -199,14 +217,18 @@ impl Track {
 
         let mut current = TimeStamp::zero_like(duration);
         let mut buffer = Vec::new();
+        // The last chunk is cut short so the track is exactly as long as asked.
+        let mut remaining = (duration.to_seconds() * sample_rate as f64).round() as usize;
 
-        while current < duration {
+        while remaining > 0 {
+            let len = remaining.min(FRAME_SIZE);
             buffer.push(AudioFrame {
                 time: current,
-                data: vec![vec![0.0; FRAME_SIZE]; channels as usize],
+                data: vec![vec![0.0; len]; channels as usize],
             });
 
             current = current.add(frame_duration);
+            remaining -= len;
         }
 
         Track::new(sample_rate, channels, buffer)
@@ -231,3 +253,145 @@ impl Track {
         last.time.add(frame_duration)
     }
 }
+
+// ── Resampling ───────────────────────────────────────────────────────────────
+//
+// Band-limited interpolation: every output sample is the input convolved with a
+// Kaiser-windowed sinc centred on its position in the input. The sinc is cut off
+// a little below the lower of the two Nyquist frequencies, so downsampling does not
+// alias and upsampling adds no images. The kernel is tabulated once per call and
+// read with linear interpolation between table entries.
+
+/// Zero crossings of the sinc on each side of the centre, at the cutoff frequency.
+const SINC_ZEROS: usize = 32;
+/// Table entries per zero crossing.
+const SINC_STEPS: usize = 512;
+/// Kaiser window shape; about 90 dB of stopband attenuation.
+const KAISER_BETA: f64 = 8.6;
+/// Cutoff as a fraction of the lower Nyquist frequency, leaving room for the transition band.
+const CUTOFF: f64 = 0.95;
+
+/// Zeroth-order modified Bessel function of the first kind, by its power series.
+fn bessel_i0(x: f64) -> f64 {
+    let (mut sum, mut term, mut k) = (1.0, 1.0, 1.0);
+    while term > sum * 1e-12 {
+        term *= (x / (2.0 * k)).powi(2);
+        sum += term;
+        k += 1.0;
+    }
+    sum
+}
+
+/// Windowed sinc from 0 to `SINC_ZEROS` zero crossings, `SINC_STEPS` entries per crossing.
+fn sinc_table() -> Vec<f32> {
+    let norm = bessel_i0(KAISER_BETA);
+    (0..=SINC_ZEROS * SINC_STEPS + 1)
+        .map(|i| {
+            let x = i as f64 / SINC_STEPS as f64;
+            if x >= SINC_ZEROS as f64 {
+                return 0.0;
+            }
+            let sinc = if x == 0.0 { 1.0 } else { (std::f64::consts::PI * x).sin() / (std::f64::consts::PI * x) };
+            let r = x / SINC_ZEROS as f64;
+            (sinc * bessel_i0(KAISER_BETA * (1.0 - r * r).sqrt()) / norm) as f32
+        })
+        .collect()
+}
+
+/// One channel taken from `from` to `to` samples per second.
+fn resample_channel(input: &[f32], from: u32, to: u32, table: &[f32]) -> Vec<f32> {
+    let ratio = to as f64 / from as f64;
+    let cutoff = ratio.min(1.0) * CUTOFF;
+    // Input samples each side of the centre that the kernel reaches.
+    let reach = (SINC_ZEROS as f64 / cutoff).ceil() as isize;
+    let len = (input.len() as f64 * ratio).round() as usize;
+
+    (0..len)
+        .map(|n| {
+            let centre = n as f64 / ratio;
+            let first = centre.floor() as isize - reach + 1;
+            let mut sum = 0.0f64;
+            for k in first.max(0)..(first + 2 * reach).min(input.len() as isize) {
+                let position = (centre - k as f64).abs() * cutoff * SINC_STEPS as f64;
+                let i = position as usize;
+                if i + 1 >= table.len() {
+                    continue;
+                }
+                let t = (position - i as f64) as f32;
+                let weight = table[i] + (table[i + 1] - table[i]) * t;
+                sum += (input[k as usize] * weight) as f64;
+            }
+            (sum * cutoff) as f32
+        })
+        .collect()
+}
+
+impl Track {
+    /// The track converted to `rate` samples per second with a windowed-sinc
+    /// resampler. The result starts at the same time and lasts as long; its chunks are
+    /// `CHUNK_SAMPLES` long and timed in samples.
+    pub fn resample(&self, rate: u32) -> Track {
+        if rate == self.sample_rate || rate == 0 || self.sample_rate == 0 {
+            return self.clone();
+        }
+        let channels: Vec<Vec<f32>> = (0..self.channels as usize)
+            .map(|c| {
+                self.buffer.iter().flat_map(|frame| frame.data.get(c).into_iter().flatten().copied()).collect()
+            })
+            .collect();
+        let table = sinc_table();
+        let converted: Vec<Vec<f32>> = channels
+            .iter()
+            .map(|samples| resample_channel(samples, self.sample_rate, rate, &table))
+            .collect();
+
+        let start = self.buffer.first().map_or(0, |frame| (frame.time.to_seconds() * rate as f64).round() as i64);
+        let len = converted.first().map_or(0, Vec::len);
+        let buffer = (0..len)
+            .step_by(CHUNK_SAMPLES)
+            .map(|offset| AudioFrame {
+                time: TimeStamp { value: start + offset as i64, num: 1, den: rate },
+                data: converted.iter().map(|c| c[offset..(offset + CHUNK_SAMPLES).min(len)].to_vec()).collect(),
+            })
+            .collect();
+        Track::new(rate, self.channels, buffer)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn tone(rate: u32, hz: f64, seconds: f64) -> Track {
+        let len = (rate as f64 * seconds) as usize;
+        let samples: Vec<f32> = (0..len).map(|i| (2.0 * std::f64::consts::PI * hz * i as f64 / rate as f64).sin() as f32 * 0.5).collect();
+        Track::from_channels(rate, vec![samples.clone(), samples], CHUNK_SAMPLES)
+    }
+
+    fn rms(track: &Track, skip: usize) -> f64 {
+        let samples: Vec<f32> = track.buffer().iter().flat_map(|f| f.data[0].iter().copied()).collect();
+        let middle = &samples[skip..samples.len() - skip];
+        (middle.iter().map(|&s| (s as f64).powi(2)).sum::<f64>() / middle.len() as f64).sqrt()
+    }
+
+    #[test]
+    fn resampling_keeps_the_passband_and_removes_what_cannot_be_kept() {
+        let music = tone(44100, 1000.0, 0.5);
+        let converted = music.resample(48000);
+        assert_eq!(converted.sample_rate(), 48000);
+        assert_eq!(converted.buffer().iter().map(|f| f.data[1].len()).sum::<usize>(), 24000);
+        assert!((converted.duration().to_seconds() - 0.5).abs() < 1e-4);
+
+        // The tone comes through at its level and still matches a 48 kHz sine.
+        let expected = tone(48000, 1000.0, 0.5);
+        assert!((rms(&converted, 200) - rms(&expected, 200)).abs() < 1e-3);
+        let (a, b) = (&converted.buffer()[5].data[0], &expected.buffer()[5].data[0]);
+        let error = a.iter().zip(b).map(|(x, y)| (x - y).abs()).fold(0.0, f32::max);
+        assert!(error < 1e-3, "max error {error}");
+        assert!(Track::mix(&converted, &expected).is_ok());
+
+        // 15 kHz is above the 11.025 kHz Nyquist frequency of 22.05 kHz: it is removed, not folded.
+        let high = tone(44100, 15000.0, 0.5).resample(22050);
+        assert!(rms(&high, 200) < 1e-3, "aliased {}", rms(&high, 200));
+    }
+}
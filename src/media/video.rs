@@ -49,10 +49,65 @@ impl TimeStamp {
         }
     }
 }
+#[derive(Debug, Clone)]
 pub struct VideoFrame {
     pub frame: Frame,
     pub pts: TimeStamp,
 }
+
+/// Millisecond time base used for sequence timing; GIF and APNG delays are
+/// whole centiseconds or small fractions, so this loses nothing.
+pub const SEQUENCE_TIME_BASE: (u32, u32) = (1, 1000);
+
+/// An ordered run of frames, such as a decoded GIF. Each frame's `pts` is when it
+/// appears; `end` is when the last one stops showing.
+#[derive(Debug, Clone)]
+pub struct Sequence {
+    pub frames: Vec<VideoFrame>,
+    pub end: TimeStamp,
+}
+
+impl Sequence {
+    pub fn new() -> Self {
+        let (num, den) = SEQUENCE_TIME_BASE;
+        Sequence { frames: Vec::new(), end: TimeStamp { value: 0, num, den } }
+    }
+
+    /// Appends a frame that is shown for `delay_ms` milliseconds.
+    pub fn push(&mut self, frame: Frame, delay_ms: i64) {
+        let pts = self.end;
+        self.end.value += delay_ms.max(0);
+        self.frames.push(VideoFrame { frame, pts });
+    }
+
+    pub fn len(&self) -> usize {
+        self.frames.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.frames.is_empty()
+    }
+
+    /// How long each frame is shown, in milliseconds.
+    pub fn delays_ms(&self) -> Vec<i64> {
+        let (num, den) = SEQUENCE_TIME_BASE;
+        self.frames
+            .iter()
+            .enumerate()
+            .map(|(i, vf)| {
+                let next = self.frames.get(i + 1).map_or(self.end, |n| n.pts);
+                // Frames only ever come from `push`, but convert through seconds in
+                // case a caller built the sequence with another time base.
+                let seconds = next.to_seconds() - vf.pts.to_seconds();
+                (seconds * den as f64 / num as f64).round() as i64
+            })
+            .collect()
+    }
+
+    pub fn duration_seconds(&self) -> f64 {
+        self.end.to_seconds()
+    }
+}
 impl TimeStamp {
     pub fn sub(&self, other: TimeStamp) -> Self {
         let other = other.rescale(self.num, self.den);
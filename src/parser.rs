@@ -55,6 +55,16 @@ pub enum Expr {
     // NEW: Array literals for Kernel matrices like `[[1, 2, 1], [2, 4, 2], [1, 2, 1]]`
     Array(Vec<Expr>),
     Not(Box<Expr>),
+    /// `name: value` inside an argument list, e.g. `quality: 85`.
+    NamedArg {
+        name: String,
+        value: Box<Expr>,
+    },
+    /// Property read such as `img.width` or `song.duration`.
+    Member {
+        base: Box<Expr>,
+        name: String,
+    },
     
     
 }
@@ -86,7 +96,10 @@ pub struct ChannelAssign {
 pub enum Statement {
     Channel(ChannelAssign),
     Let { name: String, value: Expr },
-    
+    /// `state name = init;` in an `af` body: kept from one sample to the next.
+    State { name: String, value: Expr },
+    /// `name = value;` updating a state variable.
+    Assign { name: String, value: Expr },
 
     IfElse {
         cond: Box<Expr>,
@@ -128,7 +141,11 @@ pub enum Item {
     FilterDecl(FilterDecl),
     AudioFilterDecl(AudioFilterDecl),
     KernelDecl { name: String, matrix: Expr },
-    Export { value: Expr, path: Expr },
+    Export {
+        value: Expr,
+        path: Expr,
+        options: Vec<(String, Expr)>,
+    },
     ForLoop {
         variable: String,
         range: Box<Expr>,
@@ -233,6 +250,16 @@ impl<'a> Parser<'a> {
         tok
     }
 
+    /// `state` is only a keyword at the start of `state <name> = ...` in an `af` body,
+    /// so it stays usable as an ordinary name everywhere else.
+    fn at_state_decl(&self) -> bool {
+        let kind_at = |offset: usize| self.tokens.get(self.pos + offset).map(|t| &t.kind);
+        self.peek().kind == TokenKind::Identifier
+            && self.peek().value == "state"
+            && kind_at(1) == Some(&TokenKind::Identifier)
+            && kind_at(2) == Some(&TokenKind::Equal)
+    }
+
     fn check(&self, kind: &TokenKind) -> bool {
         self.peek_kind() == kind
     }
@@ -417,7 +444,16 @@ impl<'a> Parser<'a> {
         self.expect(TokenKind::LeftBrace, "'{'")?;
         let mut body = Vec::new();
         while !self.check(&TokenKind::RightBrace) {
-           body.push(self.parse_statement("af")?);
+            if self.at_state_decl() {
+                self.advance(); // consume 'state'
+                let name = self.expect_identifier("State variable name expected")?;
+                self.expect(TokenKind::Equal, "=")?;
+                let value = self.parse_expr()?;
+                self.expect(TokenKind::SemiColon, ";")?;
+                body.push(Statement::State { name, value });
+            } else {
+                body.push(self.parse_statement("af")?);
+            }
         }
         self.expect(TokenKind::RightBrace, "'}'")?;
 
@@ -515,9 +551,28 @@ impl<'a> Parser<'a> {
         let value = self.parse_expr()?;
         self.expect(TokenKind::Comma, "','")?;
         let path = self.parse_expr()?;
+
+        // Encoder settings follow the path as named arguments: `quality: 85`.
+        let mut options = Vec::new();
+        while self.check(&TokenKind::Comma) {
+            self.advance();
+            let line = self.peek().line;
+            let found = self.peek_kind().clone();
+            match self.parse_arg()? {
+                Expr::NamedArg { name, value } => options.push((name, *value)),
+                _ => {
+                    return Err(ParseError::UnexpectedToken {
+                        expected: "a named export option like 'quality: 85'".to_string(),
+                        found,
+                        line,
+                    });
+                }
+            }
+        }
+
         self.expect(TokenKind::RightParen, "')'")?;
         self.expect(TokenKind::SemiColon, "';'")?;
-        Ok(Item::Export { value, path })
+        Ok(Item::Export { value, path, options })
     }
 
     fn parse_assignment(&mut self) -> PResult<Item> {
@@ -537,7 +592,22 @@ impl<'a> Parser<'a> {
                 self.expect(TokenKind::SemiColon, ";")?;
                 Ok(Statement::Let { name, value })
             }
+            // Declarations are picked up by `parse_audiofilter_decl`; one here sits inside a branch.
+            TokenKind::Identifier if caller == "af" && self.at_state_decl() => Err(ParseError::UnexpectedToken {
+                expected: "a statement (state is declared at the top level of an af body)".to_string(),
+                found: TokenKind::Identifier,
+                line: self.peek().line,
+            }),
             TokenKind::If => self.parse_statement_if_else(caller),
+            // Any other name on the left of `=` in an `af` body is a state update;
+            // the engine checks that it was declared.
+            TokenKind::Identifier if caller == "af" && !matches!(self.peek().value.as_str(), "l" | "r") => {
+                let name = self.advance().value;
+                self.expect(TokenKind::Equal, "=")?;
+                let value = self.parse_expr()?;
+                self.expect(TokenKind::SemiColon, ";")?;
+                Ok(Statement::Assign { name, value })
+            }
             _ => {
                 let assign = self.parse_channel_assign(caller)?;
                 Ok(Statement::Channel(assign))
@@ -737,7 +807,21 @@ impl<'a> Parser<'a> {
             let expr = self.parse_unary()?;
             return Ok(Expr::Neg(Box::new(expr)));
         }
-        self.parse_primary()
+        self.parse_member()
+    }
+
+    /// A primary expression followed by any number of `.name` property reads.
+    fn parse_member(&mut self) -> PResult<Expr> {
+        let mut expr = self.parse_primary()?;
+        while self.check(&TokenKind::Dot) {
+            self.advance();
+            let name = self.expect_identifier("a property name after '.'")?;
+            expr = Expr::Member {
+                base: Box::new(expr),
+                name,
+            };
+        }
+        Ok(expr)
     }
 
     fn parse_primary(&mut self) -> PResult<Expr> {
@@ -874,13 +958,31 @@ impl<'a> Parser<'a> {
         Ok(segments)
     }
 
+    /// One call argument: either a plain expression or `name: expr`.
+    fn parse_arg(&mut self) -> PResult<Expr> {
+        let next_is_colon = self
+            .tokens
+            .get(self.pos + 1)
+            .is_some_and(|t| t.kind == TokenKind::Colon);
+        if self.check(&TokenKind::Identifier) && next_is_colon {
+            let name = self.advance().value;
+            self.advance();
+            let value = self.parse_expr()?;
+            return Ok(Expr::NamedArg {
+                name,
+                value: Box::new(value),
+            });
+        }
+        self.parse_expr()
+    }
+
     fn parse_arg_list(&mut self) -> PResult<Vec<Expr>> {
         let mut args = Vec::new();
         if self.check(&TokenKind::RightParen) {
             return Ok(args);
         }
         loop {
-            args.push(self.parse_expr()?);
+            args.push(self.parse_arg()?);
             if self.check(&TokenKind::Comma) {
                 self.advance();
             } else {
@@ -912,3 +1014,58 @@ impl std::fmt::Display for ParseOrLexError {
         }
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn member_access_binds_tighter_than_arithmetic() {
+        let program = parse("x = (img.width - mark.width) / 2;").unwrap();
+        let Item::Assign { value, .. } = &program.items[0] else {
+            panic!("expected an assignment");
+        };
+        let member = |base: &str, name: &str| Expr::Member {
+            base: Box::new(Expr::Ident(base.to_string())),
+            name: name.to_string(),
+        };
+        let expected = Expr::BinOp {
+            op: BinOp::Div,
+            lhs: Box::new(Expr::BinOp {
+                op: BinOp::Sub,
+                lhs: Box::new(member("img", "width")),
+                rhs: Box::new(member("mark", "width")),
+            }),
+            rhs: Box::new(Expr::Int(2)),
+        };
+        assert_eq!(value, &expected);
+        assert!(parse("x = img.;").is_err());
+    }
+
+    #[test]
+    fn audio_filters_declare_and_update_state() {
+        let program = parse("af hold() { state prev = 0; l = prev; prev = l; }").unwrap();
+        let Item::AudioFilterDecl(decl) = &program.items[0] else {
+            panic!("expected an audio filter");
+        };
+        let ident = |name: &str| Expr::Ident(name.to_string());
+        assert_eq!(decl.body[0], Statement::State { name: "prev".into(), value: Expr::Int(0) });
+        assert_eq!(decl.body[2], Statement::Assign { name: "prev".into(), value: ident("l") });
+        // Only audio filters keep state between samples.
+        assert!(parse("filter f() { state prev = 0; }").is_err());
+    }
+
+    #[test]
+    fn state_is_only_a_keyword_where_it_declares_one() {
+        let program = parse("state = 1; af f(gain) { let state = gain; l = l * state; }").unwrap();
+        assert_eq!(program.items[0], Item::Assign { name: "state".into(), value: Expr::Int(1) });
+        let Item::AudioFilterDecl(decl) = &program.items[1] else {
+            panic!("expected an audio filter");
+        };
+        let state = Statement::Let { name: "state".into(), value: Expr::Ident("gain".into()) };
+        assert_eq!(decl.body[0], state);
+        assert!(parse("filter f(state) { let x = state; r = x; }").is_ok());
+        // A declaration has to sit at the top level of the body.
+        assert!(parse("af f() { if l > 0 { state prev = 0; } }").is_err());
+    }
+}
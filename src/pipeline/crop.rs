@@ -0,0 +1,160 @@
+use crate::media::frame::{Frame, FrameError};
+use crate::pipeline::edge::{Gradient, GradientOperator};
+use crate::pipeline::plane;
+
+// ── Trim ─────────────────────────────────────────────────────────────────────
+
+/// The smallest rectangle `(x, y, width, height)` holding everything that is not
+/// border. Border pixels are nearly transparent, or within `tolerance` (0 - 255, on
+/// every channel) of the top-left pixel. `None` when the whole frame is border.
+pub fn trim_bounds(frame: &Frame, tolerance: f32) -> Result<Option<(u32, u32, u32, u32)>, FrameError> {
+    let (width, height) = (frame.width() as usize, frame.height() as usize);
+    if width == 0 || height == 0 {
+        return Err(FrameError::EmptyFrame);
+    }
+    let planes = frame.rgba_planes()?;
+    let tolerance = tolerance.max(0.0) / 255.0;
+    let corner = planes.each_ref().map(|p| p[0]);
+    let is_border = |i: usize| {
+        planes[3][i] <= tolerance || (0..4).all(|c| (planes[c][i] - corner[c]).abs() <= tolerance)
+    };
+
+    let (mut x0, mut y0, mut x1, mut y1) = (width, height, 0, 0);
+    for y in 0..height {
+        for x in 0..width {
+            if !is_border(y * width + x) {
+                x0 = x0.min(x);
+                y0 = y0.min(y);
+                x1 = x1.max(x + 1);
+                y1 = y1.max(y + 1);
+            }
+        }
+    }
+    if x0 >= x1 {
+        return Ok(None);
+    }
+    Ok(Some((x0 as u32, y0 as u32, (x1 - x0) as u32, (y1 - y0) as u32)))
+}
+
+// ── Smart crop ───────────────────────────────────────────────────────────────
+
+/// What `smart_crop_origin` looks for.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Saliency {
+    /// Sobel gradient magnitude of the luma: detail and outlines.
+    Edges,
+    /// Local entropy of the luma: busy texture over flat areas.
+    Entropy,
+}
+
+impl Saliency {
+    /// Parses the names scripts use: `edges`, `entropy`.
+    pub fn from_name(name: &str) -> Option<Saliency> {
+        match name.to_lowercase().as_str() {
+            "edges" | "edge" => Some(Saliency::Edges),
+            "entropy" => Some(Saliency::Entropy),
+            _ => None,
+        }
+    }
+
+    /// Per-pixel interest of a luma plane.
+    fn map(&self, luma: &[f32], width: usize, height: usize) -> Vec<f32> {
+        match self {
+            Saliency::Edges => Gradient::compute(luma, width, height, GradientOperator::Sobel).magnitude(),
+            Saliency::Entropy => local_entropy(luma, width, height),
+        }
+    }
+}
+
+/// Luma levels per entropy histogram and the window radius they are counted over.
+const ENTROPY_BINS: usize = 16;
+const ENTROPY_RADIUS: usize = 4;
+
+/// Shannon entropy, in bits, of the luma histogram around each pixel.
+fn local_entropy(luma: &[f32], width: usize, height: usize) -> Vec<f32> {
+    let bins: Vec<usize> = luma
+        .iter()
+        .map(|v| ((v.clamp(0.0, 1.0) * ENTROPY_BINS as f32) as usize).min(ENTROPY_BINS - 1))
+        .collect();
+    let mut entropy = vec![0.0f32; luma.len()];
+    for bin in 0..ENTROPY_BINS {
+        let indicator: Vec<f32> = bins.iter().map(|&b| if b == bin { 1.0 } else { 0.0 }).collect();
+        let share = plane::box_mean(&indicator, width, height, ENTROPY_RADIUS);
+        for (e, p) in entropy.iter_mut().zip(share) {
+            if p > 0.0 {
+                *e -= p * p.log2();
+            }
+        }
+    }
+    entropy
+}
+
+/// Top-left corner of the `crop_w` x `crop_h` window with the most saliency. Equal
+/// windows, as on a flat image, go to the one nearest the centre.
+pub fn smart_crop_origin(frame: &Frame, crop_w: u32, crop_h: u32, saliency: Saliency) -> Result<(u32, u32), FrameError> {
+    let (width, height) = (frame.width() as usize, frame.height() as usize);
+    if width == 0 || height == 0 {
+        return Err(FrameError::EmptyFrame);
+    }
+    let (cw, ch) = ((crop_w as usize).clamp(1, width), (crop_h as usize).clamp(1, height));
+    let map = saliency.map(&frame.luma_plane()?, width, height);
+
+    let stride = width + 1;
+    let mut integral = vec![0.0f64; stride * (height + 1)];
+    for y in 0..height {
+        let mut row_sum = 0.0f64;
+        for x in 0..width {
+            row_sum += map[y * width + x] as f64;
+            integral[(y + 1) * stride + x + 1] = integral[y * stride + x + 1] + row_sum;
+        }
+    }
+
+    let centre = ((width - cw) as f64 / 2.0, (height - ch) as f64 / 2.0);
+    let mut best = (f64::NEG_INFINITY, f64::INFINITY, 0, 0);
+    for y in 0..=height - ch {
+        for x in 0..=width - cw {
+            let sum = integral[(y + ch) * stride + x + cw] - integral[y * stride + x + cw] - integral[(y + ch) * stride + x]
+                + integral[y * stride + x];
+            let distance = (x as f64 - centre.0).powi(2) + (y as f64 - centre.1).powi(2);
+            // Sums are compared with a little slack so float noise does not beat the centre rule.
+            let slack = 1e-9 * best.0.abs().max(1.0);
+            if sum > best.0 + slack || (sum >= best.0 - slack && distance < best.1) {
+                best = (sum, distance, x, y);
+            }
+        }
+    }
+    Ok((best.2 as u32, best.3 as u32))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::media::frame::{BlendMode, Color};
+    use crate::media::generate;
+    use crate::pipeline::draw::{Drawing, Shape};
+
+    #[test]
+    fn trim_finds_the_content_and_smart_crop_follows_it() {
+        let mut frame = generate::solid(120, 80, Color::RGBA(250, 250, 250, 255)).unwrap();
+        let dot = Drawing {
+            shape: Shape::Circle { cx: 95.0, cy: 25.0, radius: 10.0 },
+            fill: Some(Color::RGBA(20, 40, 200, 255)),
+            stroke: None,
+            stroke_width: 0.0,
+            mode: BlendMode::Normal,
+        };
+        dot.apply(&mut frame).unwrap();
+
+        let (x, y, w, h) = trim_bounds(&frame, 2.0).unwrap().unwrap();
+        assert!((84..=85).contains(&x) && (14..=15).contains(&y), "{x},{y}");
+        assert!((20..=22).contains(&w) && (20..=22).contains(&h), "{w}x{h}");
+        assert_eq!(trim_bounds(&frame, 255.0).unwrap(), None);
+
+        for saliency in [Saliency::Edges, Saliency::Entropy] {
+            let (x, y) = smart_crop_origin(&frame, 40, 40, saliency).unwrap();
+            assert!(x <= 85 && x + 40 >= 105 && y <= 15 && y + 40 >= 35, "{saliency:?} {x},{y}");
+        }
+        let flat = generate::solid(100, 60, Color::RGBA(0, 0, 0, 255)).unwrap();
+        assert_eq!(smart_crop_origin(&flat, 40, 40, Saliency::Edges).unwrap(), (30, 10));
+    }
+}
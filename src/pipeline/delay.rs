@@ -0,0 +1,240 @@
+use std::f32::consts::{FRAC_PI_2, TAU};
+
+use crate::media::track::Track;
+
+// ── Delay-based effects ──────────────────────────────────────────────────────
+//
+// Each channel gets a voice that lives for the whole track: its delay lines, filter
+// state and LFO run on from one chunk to the next, and the LFOs are driven by the
+// sample's index in the track, so the result does not depend on how the buffer is cut
+// up. The track keeps its length; echoes and reverb tails past the end are dropped.
+
+/// Parameters as given in the pipeline. Times are in milliseconds, rates in Hz and
+/// everything else from 0 to 1, `mix` being the share of the effected signal.
+#[derive(Debug, Clone, Copy)]
+pub enum DelayEffect {
+    /// Repeats the sound every `ms`, each repeat `feedback` times the last.
+    Echo { ms: f32, feedback: f32, mix: f32 },
+    /// A copy delayed by 15 ms plus up to `depth` ms, swept at `rate`.
+    Chorus { rate: f32, depth: f32, mix: f32 },
+    /// A copy delayed by 0.5 ms plus up to `depth` ms, swept at `rate` and fed back.
+    Flanger { rate: f32, depth: f32, feedback: f32, mix: f32 },
+    /// Six allpass stages swept between 200 Hz and 200 Hz × 16^depth.
+    Phaser { rate: f32, depth: f32, feedback: f32, mix: f32 },
+    /// Freeverb: eight damped combs into four allpasses per channel.
+    Reverb { room: f32, damping: f32, wet: f32 },
+}
+
+/// Chorus and phaser sweeps on odd channels run a quarter cycle ahead, for width.
+fn lfo_offset(channel: usize) -> f32 {
+    if channel % 2 == 1 { FRAC_PI_2 } else { 0.0 }
+}
+
+/// Ring buffer read a fractional number of samples back.
+struct DelayLine {
+    buffer: Vec<f32>,
+    write: usize,
+}
+
+impl DelayLine {
+    fn new(max_samples: f32) -> Self {
+        Self { buffer: vec![0.0; max_samples.ceil() as usize + 2], write: 0 }
+    }
+
+    /// The sample written `delay` samples ago, linearly interpolated; `delay` is at least 1.
+    fn read(&self, delay: f32) -> f32 {
+        let len = self.buffer.len();
+        let delay = delay.clamp(1.0, (len - 1) as f32);
+        let whole = delay.floor();
+        let frac = delay - whole;
+        let newer = (self.write + len - whole as usize) % len;
+        let older = (newer + len - 1) % len;
+        self.buffer[newer] * (1.0 - frac) + self.buffer[older] * frac
+    }
+
+    fn write(&mut self, sample: f32) {
+        self.buffer[self.write] = sample;
+        self.write = (self.write + 1) % self.buffer.len();
+    }
+}
+
+// Freeverb's tunings at 44.1 kHz, scaled to the track's rate.
+const COMB_TUNING: [usize; 8] = [1116, 1188, 1277, 1356, 1422, 1491, 1557, 1617];
+const ALLPASS_TUNING: [usize; 4] = [556, 441, 341, 225];
+const STEREO_SPREAD: usize = 23;
+const FIXED_GAIN: f32 = 0.015;
+const SCALE_WET: f32 = 3.0;
+
+struct Comb {
+    buffer: Vec<f32>,
+    index: usize,
+    filter: f32,
+}
+
+impl Comb {
+    fn process(&mut self, input: f32, feedback: f32, damp: f32) -> f32 {
+        let out = self.buffer[self.index];
+        self.filter = out * (1.0 - damp) + self.filter * damp;
+        self.buffer[self.index] = input + self.filter * feedback;
+        self.index = (self.index + 1) % self.buffer.len();
+        out
+    }
+}
+
+struct Allpass {
+    buffer: Vec<f32>,
+    index: usize,
+}
+
+impl Allpass {
+    fn process(&mut self, input: f32) -> f32 {
+        let delayed = self.buffer[self.index];
+        self.buffer[self.index] = input + delayed * 0.5;
+        self.index = (self.index + 1) % self.buffer.len();
+        delayed - input
+    }
+}
+
+/// What one channel keeps between samples.
+enum Voice {
+    Line { line: DelayLine, offset: f32 },
+    Phaser { stages: [f32; 6], last: f32, offset: f32 },
+    Reverb { combs: Vec<Comb>, allpasses: Vec<Allpass> },
+}
+
+impl DelayEffect {
+    fn voice(&self, sr: f32, channel: usize) -> Voice {
+        let samples = |ms: f32| ms * sr / 1000.0;
+        match *self {
+            DelayEffect::Echo { ms, .. } => Voice::Line { line: DelayLine::new(samples(ms)), offset: 0.0 },
+            DelayEffect::Chorus { depth, .. } => {
+                Voice::Line { line: DelayLine::new(samples(15.0 + depth)), offset: lfo_offset(channel) }
+            }
+            DelayEffect::Flanger { depth, .. } => Voice::Line { line: DelayLine::new(samples(0.5 + depth)), offset: 0.0 },
+            DelayEffect::Phaser { .. } => Voice::Phaser { stages: [0.0; 6], last: 0.0, offset: lfo_offset(channel) },
+            DelayEffect::Reverb { .. } => {
+                let spread = if channel % 2 == 1 { STEREO_SPREAD } else { 0 };
+                let length = |tuning: usize| (((tuning + spread) as f32 * sr / 44100.0).round() as usize).max(1);
+                Voice::Reverb {
+                    combs: COMB_TUNING.iter().map(|&t| Comb { buffer: vec![0.0; length(t)], index: 0, filter: 0.0 }).collect(),
+                    allpasses: ALLPASS_TUNING.iter().map(|&t| Allpass { buffer: vec![0.0; length(t)], index: 0 }).collect(),
+                }
+            }
+        }
+    }
+
+    /// One output sample for input `x` at sample `n` of the track. `mono` is the mean of
+    /// all channels at `n`, which the reverb is fed from.
+    fn process(&self, voice: &mut Voice, x: f32, mono: f32, n: usize, sr: f32) -> f32 {
+        let samples = |ms: f32| ms * sr / 1000.0;
+        let sweep = |rate: f32, offset: f32| ((TAU * rate * (n as f64 / sr as f64) as f32 + offset).sin() + 1.0) / 2.0;
+        match (*self, voice) {
+            (DelayEffect::Echo { ms, feedback, mix }, Voice::Line { line, .. }) => {
+                let delayed = line.read(samples(ms));
+                line.write(x + delayed * feedback);
+                x * (1.0 - mix) + delayed * mix
+            }
+            (DelayEffect::Chorus { rate, depth, mix }, Voice::Line { line, offset }) => {
+                line.write(x);
+                let delayed = line.read(samples(15.0 + depth * sweep(rate, *offset)));
+                x * (1.0 - mix) + delayed * mix
+            }
+            (DelayEffect::Flanger { rate, depth, feedback, mix }, Voice::Line { line, .. }) => {
+                let delayed = line.read(samples(0.5 + depth * sweep(rate, 0.0)));
+                line.write(x + delayed * feedback);
+                x * (1.0 - mix) + delayed * mix
+            }
+            (DelayEffect::Phaser { rate, depth, feedback, mix }, Voice::Phaser { stages, last, offset }) => {
+                let freq = 200.0 * 16f32.powf(depth * sweep(rate, *offset));
+                let t = (std::f32::consts::PI * freq.min(sr * 0.45) / sr).tan();
+                let a = (t - 1.0) / (t + 1.0);
+                let mut y = x + *last * feedback;
+                for z in stages.iter_mut() {
+                    let out = a * y + *z;
+                    *z = y - a * out;
+                    y = out;
+                }
+                *last = y;
+                x * (1.0 - mix) + y * mix
+            }
+            (DelayEffect::Reverb { room, damping, wet }, Voice::Reverb { combs, allpasses }) => {
+                let (feedback, damp) = (room * 0.28 + 0.7, damping * 0.4);
+                let input = mono * 2.0 * FIXED_GAIN;
+                let mut y: f32 = combs.iter_mut().map(|comb| comb.process(input, feedback, damp)).sum();
+                for allpass in allpasses.iter_mut() {
+                    y = allpass.process(y);
+                }
+                x * (1.0 - wet) + y * wet * SCALE_WET
+            }
+            _ => x,
+        }
+    }
+
+    /// Runs the effect over every channel of `track` in place.
+    pub fn apply(&self, track: &mut Track) {
+        let sr = track.sample_rate() as f32;
+        let mut voices: Vec<Voice> = (0..track.channels() as usize).map(|c| self.voice(sr, c)).collect();
+        let mut n = 0;
+        for frame in track.buffer_mut() {
+            let len = frame.data.first().map_or(0, Vec::len);
+            let channels = frame.data.len().max(1) as f32;
+            for i in 0..len {
+                let mono = frame.data.iter().map(|ch| ch[i]).sum::<f32>() / channels;
+                for (samples, voice) in frame.data.iter_mut().zip(voices.iter_mut()) {
+                    samples[i] = self.process(voice, samples[i], mono, n, sr);
+                }
+                n += 1;
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// Two channels: a click at the start on the left, a tone of `hz` on the right.
+    fn source(chunk: usize, hz: f32) -> Track {
+        let left = (0..24000).map(|i| if i == 0 { 1.0 } else { 0.0 }).collect();
+        let right = (0..24000).map(|i| (TAU * hz * i as f32 / 48000.0).sin() * 0.5).collect();
+        Track::from_channels(48000, vec![left, right], chunk)
+    }
+
+    fn channel(track: &Track, c: usize) -> Vec<f32> {
+        track.buffer().iter().flat_map(|f| f.data[c].iter().copied()).collect()
+    }
+
+    #[test]
+    fn effects_ring_on_across_chunk_boundaries() {
+        let effects = [
+            DelayEffect::Echo { ms: 10.0, feedback: 0.5, mix: 0.5 },
+            DelayEffect::Chorus { rate: 1.5, depth: 3.0, mix: 0.5 },
+            DelayEffect::Flanger { rate: 0.5, depth: 2.0, feedback: 0.6, mix: 0.5 },
+            DelayEffect::Phaser { rate: 0.8, depth: 1.0, feedback: 0.5, mix: 0.5 },
+            DelayEffect::Reverb { room: 0.8, damping: 0.5, wet: 0.3 },
+        ];
+        for effect in effects {
+            let mut whole = source(24000, 300.0);
+            let mut pieces = source(100, 300.0);
+            effect.apply(&mut whole);
+            effect.apply(&mut pieces);
+            assert_eq!(channel(&whole, 0), channel(&pieces, 0), "{effect:?}");
+            assert_eq!(channel(&whole, 1), channel(&pieces, 1), "{effect:?}");
+        }
+
+        // 10 ms at 48 kHz is 480 samples; each repeat is half the one before.
+        let mut echo = source(1024, 0.0);
+        effects[0].apply(&mut echo);
+        let left = channel(&echo, 0);
+        assert_eq!((left[0], left[480], left[960], left[1440]), (0.5, 0.5, 0.25, 0.125));
+        assert_eq!(left[479], 0.0);
+
+        // The click alone leaves a tail that dies away.
+        let mut hall = source(1024, 0.0);
+        effects[4].apply(&mut hall);
+        let left = channel(&hall, 0);
+        let energy = |range: std::ops::Range<usize>| left[range].iter().map(|s| s * s).sum::<f32>();
+        assert!(energy(2000..6000) > 1e-4);
+        assert!(energy(20000..24000) < energy(2000..6000) / 10.0);
+    }
+}
@@ -0,0 +1,501 @@
+use std::f32::consts::TAU;
+
+use crate::media::frame::{BlendMode, Color, Frame, FrameError};
+
+// ── Vector drawing ───────────────────────────────────────────────────────────
+//
+// Every shape is flattened to polylines in pixel space, where pixel (x, y) covers
+// the square [x, x+1) × [y, y+1). Fills use exact area coverage, accumulated
+// per row from the signed area each edge sweeps; strokes use the distance from
+// each pixel centre to the nearest segment, so they get round joins and caps.
+
+/// Largest distance a flattened curve may stray from the true curve, in pixels.
+const TOLERANCE: f32 = 0.1;
+
+/// One flattened sub-path. Open contours are closed for filling, not for stroking.
+#[derive(Debug, Clone)]
+pub struct Contour {
+    pub points: Vec<(f32, f32)>,
+    pub closed: bool,
+}
+
+#[derive(Debug, Clone)]
+pub enum Shape {
+    /// Corners are rounded with `radius`, clamped to half the shorter side.
+    Rect { x: f32, y: f32, width: f32, height: f32, radius: f32 },
+    Line { x0: f32, y0: f32, x1: f32, y1: f32 },
+    Circle { cx: f32, cy: f32, radius: f32 },
+    Ellipse { cx: f32, cy: f32, rx: f32, ry: f32 },
+    Polygon(Vec<(f32, f32)>),
+    /// Already flattened Bézier path, see `parse_path`.
+    Path(Vec<Contour>),
+}
+
+impl Shape {
+    pub fn contours(&self) -> Vec<Contour> {
+        match self {
+            Shape::Rect { x, y, width, height, radius } => {
+                let (x, y, w, h) = (*x, *y, width.max(0.0), height.max(0.0));
+                let r = radius.clamp(0.0, w.min(h) / 2.0);
+                if r <= 0.0 {
+                    let points = vec![(x, y), (x + w, y), (x + w, y + h), (x, y + h)];
+                    return vec![Contour { points, closed: true }];
+                }
+                // Quarter arcs around each corner centre, clockwise from the top right.
+                let mut points = Vec::new();
+                let corners = [(x + w - r, y + r), (x + w - r, y + h - r), (x + r, y + h - r), (x + r, y + r)];
+                for (i, (cx, cy)) in corners.into_iter().enumerate() {
+                    let start = -TAU / 4.0 + i as f32 * TAU / 4.0;
+                    arc(&mut points, cx, cy, r, r, start, TAU / 4.0);
+                }
+                vec![Contour { points, closed: true }]
+            }
+            Shape::Line { x0, y0, x1, y1 } => vec![Contour { points: vec![(*x0, *y0), (*x1, *y1)], closed: false }],
+            Shape::Circle { cx, cy, radius } => ellipse(*cx, *cy, *radius, *radius),
+            Shape::Ellipse { cx, cy, rx, ry } => ellipse(*cx, *cy, *rx, *ry),
+            Shape::Polygon(points) => vec![Contour { points: points.clone(), closed: true }],
+            Shape::Path(contours) => contours.clone(),
+        }
+    }
+}
+
+fn ellipse(cx: f32, cy: f32, rx: f32, ry: f32) -> Vec<Contour> {
+    let mut points = Vec::new();
+    arc(&mut points, cx, cy, rx.abs(), ry.abs(), 0.0, TAU);
+    points.pop();
+    vec![Contour { points, closed: true }]
+}
+
+/// Appends the arc from angle `start` sweeping `sweep` radians, endpoints included.
+fn arc(points: &mut Vec<(f32, f32)>, cx: f32, cy: f32, rx: f32, ry: f32, start: f32, sweep: f32) {
+    let r = rx.max(ry);
+    // Chord length whose sagitta is TOLERANCE.
+    let step = if r > TOLERANCE { 2.0 * (1.0 - TOLERANCE / r).acos() } else { TAU / 4.0 };
+    let n = ((sweep.abs() / step).ceil() as usize).max(2);
+    // Chords cut inside the curve, so the vertices sit just outside it to keep the area.
+    let theta = sweep.abs() / n as f32;
+    let scale = (theta / theta.sin()).sqrt();
+    for i in 0..=n {
+        let t = start + sweep * i as f32 / n as f32;
+        points.push((cx + scale * rx * t.cos(), cy + scale * ry * t.sin()));
+    }
+}
+
+/// A shape with its paint, drawn by the `rect`, `line`, `circle`, `ellipse`,
+/// `polygon` and `bezier` stages.
+#[derive(Debug, Clone)]
+pub struct Drawing {
+    pub shape: Shape,
+    pub fill: Option<Color>,
+    pub stroke: Option<Color>,
+    pub stroke_width: f32,
+    pub mode: BlendMode,
+}
+
+impl Drawing {
+    /// Paints the fill, then the stroke on top of it.
+    pub fn apply(&self, frame: &mut Frame) -> Result<(), FrameError> {
+        self.apply_at(frame, 0, 0)
+    }
+
+    /// `apply` on a tile whose top-left corner sits at (x, y) of the full canvas.
+    pub fn apply_at(&self, frame: &mut Frame, x: u32, y: u32) -> Result<(), FrameError> {
+        let (width, height) = (frame.width() as usize, frame.height() as usize);
+        let mut contours = self.shape.contours();
+        if x != 0 || y != 0 {
+            for point in contours.iter_mut().flat_map(|c| c.points.iter_mut()) {
+                point.0 -= x as f32;
+                point.1 -= y as f32;
+            }
+        }
+        if let Some(fill) = self.fill {
+            frame.paint(&fill_coverage(&contours, width, height), fill, self.mode)?;
+        }
+        if let Some(stroke) = self.stroke {
+            if self.stroke_width > 0.0 {
+                let coverage = stroke_coverage(&contours, self.stroke_width, width, height);
+                frame.paint(&coverage, stroke, self.mode)?;
+            }
+        }
+        Ok(())
+    }
+}
+
+/// Area of each pixel inside the contours, non-zero style: overlapping parts of a
+/// self-intersecting outline are filled once.
+pub fn fill_coverage(contours: &[Contour], width: usize, height: usize) -> Vec<f32> {
+    // Two spare columns take the right-hand spill of edges on the last pixel.
+    let stride = width + 2;
+    let mut acc = vec![0.0f32; stride * height];
+    for contour in contours {
+        let n = contour.points.len();
+        if n < 2 {
+            continue;
+        }
+        for i in 0..n {
+            let (p0, p1) = (contour.points[i], contour.points[(i + 1) % n]);
+            // Edges left or right of the frame still change the winding of the pixels
+            // beside them, so they are split at the borders and pressed onto them.
+            let mut cuts = vec![0.0, 1.0];
+            for border in [0.0, width as f32] {
+                if (p0.0 - border) * (p1.0 - border) < 0.0 {
+                    cuts.push((border - p0.0) / (p1.0 - p0.0));
+                }
+            }
+            cuts.sort_by(f32::total_cmp);
+            let at = |t: f32| ((p0.0 + (p1.0 - p0.0) * t).clamp(0.0, width as f32), p0.1 + (p1.1 - p0.1) * t);
+            for pair in cuts.windows(2) {
+                accumulate_edge(&mut acc, stride, height, at(pair[0]), at(pair[1]));
+            }
+        }
+    }
+
+    let mut coverage = vec![0.0f32; width * height];
+    for y in 0..height {
+        let mut sum = 0.0;
+        for x in 0..width {
+            sum += acc[y * stride + x];
+            coverage[y * width + x] = sum.abs().min(1.0);
+        }
+    }
+    coverage
+}
+
+/// Adds the signed area the edge sweeps in each row to the accumulation buffer. A
+/// running sum along the row then gives each pixel's coverage.
+fn accumulate_edge(acc: &mut [f32], stride: usize, height: usize, p0: (f32, f32), p1: (f32, f32)) {
+    if (p0.1 - p1.1).abs() < f32::EPSILON {
+        return;
+    }
+    let (dir, p0, p1) = if p0.1 < p1.1 { (1.0, p0, p1) } else { (-1.0, p1, p0) };
+    let dxdy = (p1.0 - p0.0) / (p1.1 - p0.1);
+    let y_start = p0.1.max(0.0);
+    let mut x = p0.0 + (y_start - p0.1) * dxdy;
+    let y_end = (p1.1.ceil().max(0.0) as usize).min(height);
+
+    for y in (y_start as usize)..y_end {
+        let row = y * stride;
+        let dy = ((y + 1) as f32).min(p1.1) - (y as f32).max(p0.1);
+        let x_next = x + dxdy * dy;
+        let d = dy * dir;
+        let (x0, x1) = if x < x_next { (x, x_next) } else { (x_next, x) };
+        let x0_floor = x0.floor();
+        let x0i = x0_floor as usize;
+        let x1_ceil = x1.ceil();
+        let x1i = x1_ceil as usize;
+
+        if x1i <= x0i + 1 {
+            // The edge stays within one pixel column in this row.
+            let xmf = 0.5 * (x + x_next) - x0_floor;
+            acc[row + x0i] += d - d * xmf;
+            acc[row + x0i + 1] += d * xmf;
+        } else {
+            let s = (x1 - x0).recip();
+            let x0f = x0 - x0_floor;
+            let a0 = 0.5 * s * (1.0 - x0f) * (1.0 - x0f);
+            let x1f = x1 - x1_ceil + 1.0;
+            let am = 0.5 * s * x1f * x1f;
+            acc[row + x0i] += d * a0;
+            if x1i == x0i + 2 {
+                acc[row + x0i + 1] += d * (1.0 - a0 - am);
+            } else {
+                let a1 = s * (1.5 - x0f);
+                acc[row + x0i + 1] += d * (a1 - a0);
+                for xi in x0i + 2..x1i - 1 {
+                    acc[row + xi] += d * s;
+                }
+                let a2 = a1 + (x1i - x0i - 3) as f32 * s;
+                acc[row + x1i - 1] += d * (1.0 - a2 - am);
+            }
+            acc[row + x1i] += d * am;
+        }
+        x = x_next;
+    }
+}
+
+/// Coverage of a stroke `stroke_width` pixels wide along every segment. Each segment
+/// only visits the pixels near it, so long thin paths stay cheap.
+pub fn stroke_coverage(contours: &[Contour], stroke_width: f32, width: usize, height: usize) -> Vec<f32> {
+    let mut coverage = vec![0.0f32; width * height];
+    let half = stroke_width / 2.0;
+    // Strokes thinner than a pixel are drawn a pixel wide and faded instead.
+    let (reach, fade) = if stroke_width >= 1.0 { (half + 0.5, 1.0) } else { (1.0, stroke_width) };
+
+    for contour in contours {
+        let points = &contour.points;
+        let mut segments: Vec<_> = points.windows(2).map(|pair| (pair[0], pair[1])).collect();
+        match points[..] {
+            [only] => segments.push((only, only)),
+            [first, .., last] if contour.closed && points.len() > 2 => segments.push((last, first)),
+            _ => {}
+        }
+        for (a, b) in segments {
+            let x_lo = (a.0.min(b.0) - reach).floor().max(0.0) as usize;
+            let y_lo = (a.1.min(b.1) - reach).floor().max(0.0) as usize;
+            let x_hi = ((a.0.max(b.0) + reach).ceil().max(0.0) as usize).min(width);
+            let y_hi = ((a.1.max(b.1) + reach).ceil().max(0.0) as usize).min(height);
+            for y in y_lo..y_hi {
+                for x in x_lo..x_hi {
+                    let d = segment_distance((x as f32 + 0.5, y as f32 + 0.5), a, b);
+                    let c = (reach - d).clamp(0.0, 1.0) * fade;
+                    let cell = &mut coverage[y * width + x];
+                    *cell = cell.max(c);
+                }
+            }
+        }
+    }
+    coverage
+}
+
+fn segment_distance(p: (f32, f32), a: (f32, f32), b: (f32, f32)) -> f32 {
+    let (dx, dy) = (b.0 - a.0, b.1 - a.1);
+    let len2 = dx * dx + dy * dy;
+    let t = if len2 > 0.0 { (((p.0 - a.0) * dx + (p.1 - a.1) * dy) / len2).clamp(0.0, 1.0) } else { 0.0 };
+    let (qx, qy) = (a.0 + t * dx - p.0, a.1 + t * dy - p.1);
+    (qx * qx + qy * qy).sqrt()
+}
+
+/// Parses `"x,y x,y ..."` (commas and spaces are interchangeable) into points.
+pub fn parse_points(text: &str) -> Result<Vec<(f32, f32)>, String> {
+    let numbers = text
+        .split(|c: char| c == ',' || c.is_whitespace())
+        .filter(|t| !t.is_empty())
+        .map(|t| t.parse::<f32>().map_err(|_| format!("'{t}' is not a number")))
+        .collect::<Result<Vec<f32>, String>>()?;
+    if numbers.len() % 2 != 0 {
+        return Err("points need an x and a y each".into());
+    }
+    if numbers.len() < 6 {
+        return Err("a polygon needs at least 3 points".into());
+    }
+    Ok(numbers.chunks(2).map(|p| (p[0], p[1])).collect())
+}
+
+enum PathToken {
+    Command(char),
+    Number(f32),
+}
+
+fn tokenize_path(text: &str) -> Result<Vec<PathToken>, String> {
+    let chars: Vec<char> = text.chars().collect();
+    let mut tokens = Vec::new();
+    let mut i = 0;
+    while i < chars.len() {
+        let c = chars[i];
+        if c.is_whitespace() || c == ',' {
+            i += 1;
+        } else if "MmLlHhVvCcSsQqTtZz".contains(c) {
+            tokens.push(PathToken::Command(c));
+            i += 1;
+        } else if c.is_ascii_digit() || c == '-' || c == '+' || c == '.' {
+            // A sign only starts a number; a second '.' or a sign after a digit starts the next one.
+            let start = i;
+            i += 1;
+            let mut seen_dot = c == '.';
+            while i < chars.len() {
+                let d = chars[i];
+                let after_exponent = matches!(chars[i - 1], 'e' | 'E');
+                if d.is_ascii_digit() || ((d == 'e' || d == 'E') && !after_exponent) || ((d == '-' || d == '+') && after_exponent) {
+                    i += 1;
+                } else if d == '.' && !seen_dot {
+                    seen_dot = true;
+                    i += 1;
+                } else {
+                    break;
+                }
+            }
+            let number: String = chars[start..i].iter().collect();
+            tokens.push(PathToken::Number(number.parse().map_err(|_| format!("'{number}' is not a number"))?));
+        } else {
+            return Err(format!("unexpected '{c}' in path"));
+        }
+    }
+    Ok(tokens)
+}
+
+/// Parses SVG-style path data into flattened contours. Supports `M L H V C S Q T Z`,
+/// absolute (upper case) and relative (lower case); numbers after a command repeat it.
+pub fn parse_path(text: &str) -> Result<Vec<Contour>, String> {
+    let tokens = tokenize_path(text)?;
+    let mut contours = Vec::new();
+    let mut current: Vec<(f32, f32)> = Vec::new();
+    let (mut pen, mut start) = ((0.0f32, 0.0f32), (0.0f32, 0.0f32));
+    // The last control point, reflected by the smooth `S` and `T` commands.
+    let mut last_cubic: Option<(f32, f32)> = None;
+    let mut last_quad: Option<(f32, f32)> = None;
+    let mut command = None;
+    let mut i = 0;
+
+    fn finish(points: &mut Vec<(f32, f32)>, closed: bool, contours: &mut Vec<Contour>) {
+        if points.len() > 1 {
+            contours.push(Contour { points: std::mem::take(points), closed });
+        }
+        points.clear();
+    }
+
+    while i < tokens.len() {
+        if let PathToken::Command(c) = tokens[i] {
+            command = Some(c);
+            i += 1;
+            if c == 'Z' || c == 'z' {
+                finish(&mut current, true, &mut contours);
+                pen = start;
+                last_cubic = None;
+                last_quad = None;
+                continue;
+            }
+        }
+        let Some(cmd) = command else {
+            return Err("path data must start with a command such as M".into());
+        };
+        if cmd == 'Z' || cmd == 'z' {
+            return Err("numbers cannot follow Z".into());
+        }
+
+        let arity = match cmd.to_ascii_uppercase() {
+            'H' | 'V' => 1,
+            'M' | 'L' | 'T' => 2,
+            'S' | 'Q' => 4,
+            _ => 6,
+        };
+        let mut args = [0.0f32; 6];
+        for slot in args.iter_mut().take(arity) {
+            match tokens.get(i) {
+                Some(PathToken::Number(n)) => *slot = *n,
+                _ => return Err(format!("'{cmd}' needs {arity} numbers")),
+            }
+            i += 1;
+        }
+
+        let relative = cmd.is_ascii_lowercase();
+        let point = |k: usize| if relative { (pen.0 + args[k], pen.1 + args[k + 1]) } else { (args[k], args[k + 1]) };
+        let (mut cubic, mut quad) = (None, None);
+        match cmd.to_ascii_uppercase() {
+            'M' => {
+                finish(&mut current, false, &mut contours);
+                pen = point(0);
+                start = pen;
+                current.push(pen);
+                // Further pairs after a move are line segments.
+                command = Some(if relative { 'l' } else { 'L' });
+            }
+            'L' => pen = point(0),
+            'H' => pen.0 = if relative { pen.0 + args[0] } else { args[0] },
+            'V' => pen.1 = if relative { pen.1 + args[0] } else { args[0] },
+            'C' | 'S' => {
+                let (c1, c2, end) = if cmd.to_ascii_uppercase() == 'C' {
+                    (point(0), point(2), point(4))
+                } else {
+                    let c1 = last_cubic.map_or(pen, |c| (2.0 * pen.0 - c.0, 2.0 * pen.1 - c.1));
+                    (c1, point(0), point(2))
+                };
+                flatten_cubic(&mut current, pen, c1, c2, end);
+                cubic = Some(c2);
+                pen = end;
+            }
+            _ => {
+                let (c, end) = if cmd.to_ascii_uppercase() == 'Q' {
+                    (point(0), point(2))
+                } else {
+                    (last_quad.map_or(pen, |c| (2.0 * pen.0 - c.0, 2.0 * pen.1 - c.1)), point(0))
+                };
+                flatten_quadratic(&mut current, pen, c, end);
+                quad = Some(c);
+                pen = end;
+            }
+        }
+        if current.is_empty() {
+            current.push(start);
+        }
+        if !matches!(cmd.to_ascii_uppercase(), 'M' | 'C' | 'S' | 'Q' | 'T') {
+            current.push(pen);
+        }
+        last_cubic = cubic;
+        last_quad = quad;
+    }
+    finish(&mut current, false, &mut contours);
+
+    if contours.is_empty() {
+        return Err("path draws nothing".into());
+    }
+    Ok(contours)
+}
+
+fn flatten_cubic(points: &mut Vec<(f32, f32)>, p0: (f32, f32), p1: (f32, f32), p2: (f32, f32), p3: (f32, f32)) {
+    let dd = |a: (f32, f32), b: (f32, f32), c: (f32, f32)| (a.0 - 2.0 * b.0 + c.0).hypot(a.1 - 2.0 * b.1 + c.1);
+    let n = ((0.75 * dd(p0, p1, p2).max(dd(p1, p2, p3)) / TOLERANCE).sqrt().ceil() as usize).clamp(1, 512);
+    if points.is_empty() {
+        points.push(p0);
+    }
+    for i in 1..=n {
+        let t = i as f32 / n as f32;
+        let u = 1.0 - t;
+        let (a, b, c, d) = (u * u * u, 3.0 * u * u * t, 3.0 * u * t * t, t * t * t);
+        points.push((a * p0.0 + b * p1.0 + c * p2.0 + d * p3.0, a * p0.1 + b * p1.1 + c * p2.1 + d * p3.1));
+    }
+}
+
+fn flatten_quadratic(points: &mut Vec<(f32, f32)>, p0: (f32, f32), p1: (f32, f32), p2: (f32, f32)) {
+    let dd = (p0.0 - 2.0 * p1.0 + p2.0).hypot(p0.1 - 2.0 * p1.1 + p2.1);
+    let n = ((0.25 * dd / TOLERANCE).sqrt().ceil() as usize).clamp(1, 512);
+    if points.is_empty() {
+        points.push(p0);
+    }
+    for i in 1..=n {
+        let t = i as f32 / n as f32;
+        let u = 1.0 - t;
+        let (a, b, c) = (u * u, 2.0 * u * t, t * t);
+        points.push((a * p0.0 + b * p1.0 + c * p2.0, a * p0.1 + b * p1.1 + c * p2.1));
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::media::frame::Pos;
+
+    #[test]
+    fn fill_coverage_matches_shape_area_and_clips_to_the_frame() {
+        let area = |shape: Shape, w: usize, h: usize| fill_coverage(&shape.contours(), w, h).iter().sum::<f32>();
+
+        let rect = Shape::Rect { x: 2.25, y: 3.0, width: 10.5, height: 4.0, radius: 0.0 };
+        assert!((area(rect, 20, 20) - 42.0).abs() < 1e-3);
+
+        let circle = Shape::Circle { cx: 16.0, cy: 16.0, radius: 10.0 };
+        assert!((area(circle, 32, 32) - std::f32::consts::PI * 100.0).abs() < 0.5);
+
+        // Half of this circle hangs off the left edge.
+        let clipped = Shape::Circle { cx: 0.0, cy: 16.0, radius: 10.0 };
+        assert!((area(clipped, 32, 32) - std::f32::consts::PI * 50.0).abs() < 0.5);
+
+        let path = parse_path("M 4 4 h 8 v 8 h -8 z").unwrap();
+        assert!((fill_coverage(&path, 16, 16).iter().sum::<f32>() - 64.0).abs() < 1e-3);
+        assert!(parse_path("4 4 L 8 8").is_err());
+    }
+
+    #[test]
+    fn strokes_and_blend_modes_paint_through_coverage() {
+        let mut frame = Frame::blank(8, 8);
+        let background = Drawing {
+            shape: Shape::Rect { x: 0.0, y: 0.0, width: 8.0, height: 8.0, radius: 0.0 },
+            fill: Some(Color::RGBA(255, 128, 0, 255)),
+            stroke: None,
+            stroke_width: 1.0,
+            mode: BlendMode::Normal,
+        };
+        background.apply(&mut frame).unwrap();
+
+        let line = Drawing {
+            shape: Shape::Line { x0: 0.0, y0: 4.0, x1: 8.0, y1: 4.0 },
+            fill: None,
+            stroke: Some(Color::RGBA(128, 128, 128, 255)),
+            stroke_width: 2.0,
+            mode: BlendMode::Multiply,
+        };
+        line.apply(&mut frame).unwrap();
+
+        // Rows 3 and 4 lie inside the 2 px line; rows 0 and 7 are untouched.
+        assert_eq!(frame.get_pixel(&Pos(4, 3)).unwrap().channels_f32(), [128.0, 64.0, 0.0, 255.0]);
+        assert_eq!(frame.get_pixel(&Pos(4, 0)).unwrap().channels_f32(), [255.0, 128.0, 0.0, 255.0]);
+    }
+}
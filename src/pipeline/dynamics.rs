@@ -0,0 +1,239 @@
+use std::collections::VecDeque;
+
+use crate::media::track::{Track, TrackError};
+
+// ── Dynamics ─────────────────────────────────────────────────────────────────
+//
+// Every processor turns a detector signal into one gain per sample, shared by all
+// channels so the stereo image stays put. The detector is the loudest channel of the
+// track itself or of a sidechain track, lined up sample for sample. Gains are worked
+// out over the whole track before any chunk is touched, so envelopes and the
+// limiter's lookahead run straight across chunk boundaries.
+
+/// Levels are in dBFS and times in milliseconds.
+#[derive(Debug, Clone, Copy)]
+pub enum Dynamics {
+    /// Above `threshold`, every `ratio` dB in gives 1 dB out; `makeup` is added after.
+    Compressor { threshold: f32, ratio: f32, attack: f32, release: f32, makeup: f32 },
+    /// Below `threshold`, every 1 dB in gives `ratio` dB out.
+    Expander { threshold: f32, ratio: f32, attack: f32, release: f32 },
+    /// Mutes below `threshold`, staying open for `hold` after the level drops.
+    Gate { threshold: f32, attack: f32, hold: f32, release: f32 },
+    /// Keeps every sample at or under `ceiling`, turning down over `lookahead` before a peak.
+    Limiter { ceiling: f32, lookahead: f32 },
+}
+
+/// How long the limiter takes to let go after a peak.
+const LIMITER_RELEASE_MS: f32 = 50.0;
+
+/// Quietest level the detectors tell apart, about -120 dBFS.
+const FLOOR: f32 = 1e-6;
+
+fn db_to_gain(db: f32) -> f32 {
+    10f32.powf(db / 20.0)
+}
+
+/// One-pole smoothing coefficient that gets 63% of the way in `ms`.
+fn coefficient(ms: f32, sr: f32) -> f32 {
+    if ms <= 0.0 { 0.0 } else { (-1000.0 / (ms * sr)).exp() }
+}
+
+/// The loudest channel's absolute value at every sample of `track`.
+fn peaks(track: &Track) -> Vec<f32> {
+    let mut peaks = Vec::new();
+    for frame in track.buffer() {
+        let len = frame.data.first().map_or(0, Vec::len);
+        peaks.extend((0..len).map(|i| frame.data.iter().fold(0.0f32, |m, ch| m.max(ch[i].abs()))));
+    }
+    peaks
+}
+
+/// `out[k]` is the smallest of `values[k..k + window]`, cut short at the end.
+fn sliding_min(values: &[f32], window: usize) -> Vec<f32> {
+    let mut out = vec![0.0; values.len()];
+    let mut queue: VecDeque<usize> = VecDeque::new();
+    for k in (0..values.len()).rev() {
+        while queue.back().is_some_and(|&j| values[j] >= values[k]) {
+            queue.pop_back();
+        }
+        queue.push_back(k);
+        while queue.front().is_some_and(|&j| j >= k + window) {
+            queue.pop_front();
+        }
+        out[k] = values[queue[0]];
+    }
+    out
+}
+
+impl Dynamics {
+    /// Gain curve of the compressor and expander, in dB for a level in dB.
+    fn curve(&self, level_db: f32) -> f32 {
+        match *self {
+            Dynamics::Compressor { threshold, ratio, .. } if level_db > threshold => {
+                (threshold - level_db) * (1.0 - 1.0 / ratio)
+            }
+            Dynamics::Expander { threshold, ratio, .. } if level_db < threshold => {
+                ((level_db - threshold) * (ratio - 1.0)).max(-120.0)
+            }
+            _ => 0.0,
+        }
+    }
+
+    /// One gain per sample for the detector levels in `detector`.
+    fn gains(&self, detector: &[f32], sr: f32) -> Vec<f32> {
+        match *self {
+            Dynamics::Compressor { attack, release, .. } | Dynamics::Expander { attack, release, .. } => {
+                let makeup = match *self {
+                    Dynamics::Compressor { makeup, .. } => makeup,
+                    _ => 0.0,
+                };
+                let (attack, release) = (coefficient(attack, sr), coefficient(release, sr));
+                // The envelope rises with the attack and falls with the release, so it
+                // rides over the zero crossings of anything slower than the release.
+                let mut envelope = 0.0;
+                detector
+                    .iter()
+                    .map(|&level| {
+                        let a = if level > envelope { attack } else { release };
+                        envelope = a * envelope + (1.0 - a) * level;
+                        db_to_gain(self.curve(20.0 * envelope.max(FLOOR).log10()) + makeup)
+                    })
+                    .collect()
+            }
+            Dynamics::Gate { threshold, attack, hold, release } => {
+                let (open_at, hold) = (db_to_gain(threshold), (hold * sr / 1000.0).round() as usize);
+                let (attack, release) = (coefficient(attack, sr), coefficient(release, sr));
+                let (mut gain, mut held) = (0.0, 0);
+                detector
+                    .iter()
+                    .map(|&level| {
+                        let open = if level >= open_at {
+                            held = hold;
+                            true
+                        } else if held > 0 {
+                            held -= 1;
+                            true
+                        } else {
+                            false
+                        };
+                        let (target, a) = if open { (1.0, attack) } else { (0.0, release) };
+                        gain = a * gain + (1.0 - a) * target;
+                        gain
+                    })
+                    .collect()
+            }
+            Dynamics::Limiter { ceiling, lookahead } => {
+                let ceiling = db_to_gain(ceiling);
+                let ahead = ((lookahead * sr / 1000.0).round() as usize).max(1);
+                let needed: Vec<f32> = detector.iter().map(|&p| if p > ceiling { ceiling / p } else { 1.0 }).collect();
+                // Averaging the minimum over the window behind each sample, each term of
+                // which already covers that sample, ramps down ahead of a peak but never
+                // lets it through.
+                let held = sliding_min(&needed, ahead + 1);
+                let first = held.first().copied().unwrap_or(1.0);
+                let release = coefficient(LIMITER_RELEASE_MS, sr);
+                let (mut sum, mut gain) = (first as f64 * (ahead + 1) as f64, 1.0f32);
+                (0..held.len())
+                    .map(|n| {
+                        sum += held[n] as f64 - if n > ahead { held[n - ahead - 1] } else { first } as f64;
+                        let ramp = (sum / (ahead + 1) as f64) as f32;
+                        gain = ramp.min(release * gain + (1.0 - release) * ramp);
+                        gain
+                    })
+                    .collect()
+            }
+        }
+    }
+
+    /// Processes `track` in place, detecting on `sidechain` when one is given. A
+    /// sidechain must have the track's sample rate and counts as silent past its end.
+    pub fn apply(&self, track: &mut Track, sidechain: Option<&Track>) -> Result<(), TrackError> {
+        let mut detector = match sidechain {
+            Some(side) if side.sample_rate() != track.sample_rate() => return Err(TrackError::MixingIsNotPossible),
+            Some(side) => peaks(side),
+            None => peaks(track),
+        };
+        let len = track.buffer().iter().map(|f| f.data.first().map_or(0, Vec::len)).sum();
+        detector.resize(len, 0.0);
+
+        let gains = self.gains(&detector, track.sample_rate() as f32);
+        let mut n = 0;
+        for frame in track.buffer_mut() {
+            let len = frame.data.first().map_or(0, Vec::len);
+            for channel in &mut frame.data {
+                for (sample, gain) in channel.iter_mut().zip(&gains[n..n + len]) {
+                    *sample *= gain;
+                }
+            }
+            n += len;
+        }
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::f32::consts::TAU;
+
+    /// A mono track of `f(i)` for 48000 samples at 48 kHz, cut into `chunk`s.
+    fn track(chunk: usize, f: impl Fn(usize) -> f32) -> Track {
+        Track::from_channels(48000, vec![(0..48000).map(f).collect()], chunk)
+    }
+
+    fn samples(track: &Track) -> Vec<f32> {
+        track.buffer().iter().flat_map(|f| f.data[0].iter().copied()).collect()
+    }
+
+    fn peak_db(samples: &[f32]) -> f32 {
+        20.0 * samples.iter().fold(0.0f32, |m, s| m.max(s.abs())).log10()
+    }
+
+    #[test]
+    fn compressor_and_limiter_hold_their_levels() {
+        // -6 dBFS is 14 dB over a -20 threshold; at 4:1 it comes out 3.5 dB over.
+        let tone = |i: usize| (TAU * 440.0 * i as f32 / 48000.0).sin() * 0.5;
+        let mut squeezed = track(1024, tone);
+        let compressor = Dynamics::Compressor { threshold: -20.0, ratio: 4.0, attack: 5.0, release: 200.0, makeup: 0.0 };
+        compressor.apply(&mut squeezed, None).unwrap();
+        assert!((peak_db(&samples(&squeezed)[24000..]) + 16.5).abs() < 0.5);
+
+        // Isolated spikes over a quiet bed: none gets past the ceiling, however the
+        // track is cut up, and the bed well away from them is left alone.
+        let spiky = |i: usize| if i % 9000 == 4500 { 0.95 } else { tone(i) * 0.2 };
+        let limiter = Dynamics::Limiter { ceiling: -6.0, lookahead: 5.0 };
+        let (mut whole, mut pieces) = (track(48000, spiky), track(100, spiky));
+        limiter.apply(&mut whole, None).unwrap();
+        limiter.apply(&mut pieces, None).unwrap();
+        let out = samples(&whole);
+        assert_eq!(out, samples(&pieces));
+        assert!(peak_db(&out) <= -6.0 + 1e-4);
+        assert!((out[4500] - db_to_gain(-6.0)).abs() < 1e-4);
+        assert!((out[2000] - spiky(2000)).abs() < 1e-6);
+    }
+
+    #[test]
+    fn gate_holds_through_zero_crossings_and_sidechain_ducks() {
+        // A 50 Hz tone at -20 dBFS for the first half second, then silence.
+        let burst = |i: usize| if i < 24000 { (TAU * 50.0 * i as f32 / 48000.0).sin() * 0.1 } else { 0.0 };
+        let gate = Dynamics::Gate { threshold: -30.0, attack: 1.0, hold: 30.0, release: 5.0 };
+        let mut gated = track(1024, burst);
+        gate.apply(&mut gated, None).unwrap();
+        let out = samples(&gated);
+        // Open all the way through the tone, even where it crosses zero.
+        assert!((2400..24000).all(|i| (out[i] - burst(i)).abs() < 1e-4));
+
+        // Music ducks while the voice is speaking and comes back after.
+        let music = |i: usize| (TAU * 220.0 * i as f32 / 48000.0).sin() * 0.5;
+        let voice = track(1024, |i| if (12000..24000).contains(&i) { music(i * 3) } else { 0.0 });
+        let ducker = Dynamics::Compressor { threshold: -30.0, ratio: 10.0, attack: 5.0, release: 50.0, makeup: 0.0 };
+        let mut ducked = track(1024, music);
+        ducker.apply(&mut ducked, Some(&voice)).unwrap();
+        let out = samples(&ducked);
+        assert!((peak_db(&out[..12000]) - peak_db(&out[44000..])).abs() < 0.1);
+        assert!(peak_db(&out[16000..24000]) < peak_db(&out[..12000]) - 20.0);
+
+        let other_rate = Track::new(44100, 1, voice.buffer().to_vec());
+        assert!(ducker.apply(&mut ducked, Some(&other_rate)).is_err());
+    }
+}
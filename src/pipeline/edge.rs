@@ -0,0 +1,323 @@
+use crate::media::frame::{Frame, FrameError};
+use crate::pipeline::plane::{self, sample};
+
+// ── Gradients ────────────────────────────────────────────────────────────────
+
+#[derive(Debug, Clone, Copy)]
+pub enum GradientOperator {
+    Sobel,
+    Scharr,
+}
+
+impl GradientOperator {
+    /// Horizontal and vertical derivative kernels, row-major.
+    fn kernels(&self) -> ([f32; 9], [f32; 9]) {
+        match self {
+            GradientOperator::Sobel => (
+                [-1.0, 0.0, 1.0, -2.0, 0.0, 2.0, -1.0, 0.0, 1.0],
+                [-1.0, -2.0, -1.0, 0.0, 0.0, 0.0, 1.0, 2.0, 1.0],
+            ),
+            // Scharr weights sum to 16 on each side instead of Sobel's 4, so scale them
+            // down to keep both operators on the same response range.
+            GradientOperator::Scharr => (
+                [-0.75, 0.0, 0.75, -2.5, 0.0, 2.5, -0.75, 0.0, 0.75],
+                [-0.75, -2.5, -0.75, 0.0, 0.0, 0.0, 0.75, 2.5, 0.75],
+            ),
+        }
+    }
+
+    /// Gradient magnitude at one pixel, equal to `Gradient::compute(..).magnitude()` there.
+    pub fn magnitude_at(&self, plane: &[f32], width: usize, height: usize, x: usize, y: usize) -> f32 {
+        let (kx, ky) = self.kernels();
+        let (mut gx, mut gy) = (0.0, 0.0);
+        for (i, (wx, wy)) in kx.iter().zip(&ky).enumerate() {
+            let v = sample(plane, width, height, x as isize + (i % 3) as isize - 1, y as isize + (i / 3) as isize - 1);
+            gx += v * wx;
+            gy += v * wy;
+        }
+        (gx * gx + gy * gy).sqrt()
+    }
+}
+
+/// Horizontal and vertical derivatives of a single plane.
+pub struct Gradient {
+    pub gx: Vec<f32>,
+    pub gy: Vec<f32>,
+}
+
+impl Gradient {
+    pub fn compute(plane: &[f32], width: usize, height: usize, operator: GradientOperator) -> Self {
+        let (kx, ky) = operator.kernels();
+        Self {
+            gx: plane::convolve3(plane, width, height, &kx),
+            gy: plane::convolve3(plane, width, height, &ky),
+        }
+    }
+
+    pub fn magnitude(&self) -> Vec<f32> {
+        self.gx
+            .iter()
+            .zip(&self.gy)
+            .map(|(gx, gy)| (gx * gx + gy * gy).sqrt())
+            .collect()
+    }
+
+    /// Gradient angle in radians, in the range -PI..PI.
+    pub fn direction(&self) -> Vec<f32> {
+        self.gx.iter().zip(&self.gy).map(|(gx, gy)| gy.atan2(*gx)).collect()
+    }
+}
+
+// ── Detectors ────────────────────────────────────────────────────────────────
+
+#[derive(Debug, Clone)]
+pub enum EdgeDetector {
+    Sobel,
+    Scharr,
+    Laplacian,
+    /// Thresholds are on the 0-255 gradient scale.
+    Canny { low: f32, high: f32 },
+    /// `threshold` is a fraction (0.0 - 1.0) of the strongest corner response.
+    Harris { k: f32, threshold: f32 },
+}
+
+impl EdgeDetector {
+    /// Runs the detector on the frame's luma and returns an opaque grayscale map
+    /// of the same size.
+    pub fn apply(&self, frame: &Frame) -> Result<Frame, FrameError> {
+        let width = frame.width() as usize;
+        let height = frame.height() as usize;
+        if width == 0 || height == 0 {
+            return Err(FrameError::EmptyFrame);
+        }
+        let luma = frame.luma_plane()?;
+
+        let map = match self {
+            EdgeDetector::Sobel => Gradient::compute(&luma, width, height, GradientOperator::Sobel).magnitude(),
+            EdgeDetector::Scharr => Gradient::compute(&luma, width, height, GradientOperator::Scharr).magnitude(),
+            EdgeDetector::Laplacian => {
+                let kernel = [0.0, 1.0, 0.0, 1.0, -4.0, 1.0, 0.0, 1.0, 0.0];
+                plane::convolve3(&luma, width, height, &kernel)
+                    .into_iter()
+                    .map(f32::abs)
+                    .collect()
+            }
+            EdgeDetector::Canny { low, high } => canny(&luma, width, height, *low, *high),
+            EdgeDetector::Harris { k, threshold } => harris(&luma, width, height, *k, *threshold),
+        };
+
+        gray_map(frame, map)
+    }
+}
+
+/// Wraps a single-channel map as an opaque frame at the source frame's precision.
+fn gray_map(frame: &Frame, map: Vec<f32>) -> Result<Frame, FrameError> {
+    let alpha = vec![1.0; map.len()];
+    frame.with_planes([map.clone(), map.clone(), map, alpha])
+}
+
+fn canny(luma: &[f32], width: usize, height: usize, low: f32, high: f32) -> Vec<f32> {
+    let smoothed = plane::gaussian_blur(luma, width, height, 1.4);
+    let gradient = Gradient::compute(&smoothed, width, height, GradientOperator::Sobel);
+    let magnitude: Vec<f32> = gradient.magnitude().iter().map(|m| m * 255.0).collect();
+    let direction = gradient.direction();
+
+    // ── Non-maximum suppression ──────────────────────────────────────────────
+    // Keep a pixel only if it is the peak across the edge, i.e. along the
+    // gradient direction quantised to 0, 45, 90 or 135 degrees.
+    let mut thin = vec![0.0f32; magnitude.len()];
+    for y in 0..height {
+        for x in 0..width {
+            let idx = y * width + x;
+            let m = magnitude[idx];
+            if m < low {
+                continue;
+            }
+
+            let mut angle = direction[idx].to_degrees();
+            if angle < 0.0 {
+                angle += 180.0;
+            }
+            let (dx, dy) = if !(22.5..157.5).contains(&angle) {
+                (1, 0)
+            } else if angle < 67.5 {
+                (1, 1)
+            } else if angle < 112.5 {
+                (0, 1)
+            } else {
+                (-1, 1)
+            };
+
+            let (x, y) = (x as isize, y as isize);
+            let ahead = sample(&magnitude, width, height, x + dx, y + dy);
+            let behind = sample(&magnitude, width, height, x - dx, y - dy);
+            if m >= ahead && m >= behind {
+                thin[idx] = m;
+            }
+        }
+    }
+
+    // ── Hysteresis ───────────────────────────────────────────────────────────
+    // Strong pixels seed the edge map; weak pixels survive only when connected
+    // to a strong one through their 8-neighbourhood.
+    let mut edges = vec![0.0f32; thin.len()];
+    let mut stack: Vec<usize> = (0..thin.len()).filter(|&i| thin[i] >= high).collect();
+    for &i in &stack {
+        edges[i] = 1.0;
+    }
+
+    while let Some(idx) = stack.pop() {
+        let (x, y) = ((idx % width) as isize, (idx / width) as isize);
+        for ny in y - 1..=y + 1 {
+            for nx in x - 1..=x + 1 {
+                if nx < 0 || ny < 0 || nx >= width as isize || ny >= height as isize {
+                    continue;
+                }
+                let n = ny as usize * width + nx as usize;
+                if edges[n] == 0.0 && thin[n] >= low {
+                    edges[n] = 1.0;
+                    stack.push(n);
+                }
+            }
+        }
+    }
+
+    edges
+}
+
+fn harris(luma: &[f32], width: usize, height: usize, k: f32, threshold: f32) -> Vec<f32> {
+    let gradient = Gradient::compute(luma, width, height, GradientOperator::Sobel);
+
+    let xx: Vec<f32> = gradient.gx.iter().map(|g| g * g).collect();
+    let yy: Vec<f32> = gradient.gy.iter().map(|g| g * g).collect();
+    let xy: Vec<f32> = gradient.gx.iter().zip(&gradient.gy).map(|(a, b)| a * b).collect();
+
+    // Structure tensor averaged over a gaussian window.
+    let sxx = plane::gaussian_blur(&xx, width, height, 1.0);
+    let syy = plane::gaussian_blur(&yy, width, height, 1.0);
+    let sxy = plane::gaussian_blur(&xy, width, height, 1.0);
+
+    let response: Vec<f32> = (0..luma.len())
+        .map(|i| {
+            let det = sxx[i] * syy[i] - sxy[i] * sxy[i];
+            let trace = sxx[i] + syy[i];
+            det - k * trace * trace
+        })
+        .collect();
+
+    let mut corners = vec![0.0f32; response.len()];
+    let peak = response.iter().cloned().fold(0.0f32, f32::max);
+    if peak <= 0.0 {
+        return corners;
+    }
+    let cutoff = threshold.clamp(0.0, 1.0) * peak;
+
+    for y in 0..height {
+        for x in 0..width {
+            let idx = y * width + x;
+            let r = response[idx];
+            if r <= cutoff {
+                continue;
+            }
+
+            let mut is_peak = true;
+            for ny in y as isize - 1..=y as isize + 1 {
+                for nx in x as isize - 1..=x as isize + 1 {
+                    if (nx, ny) != (x as isize, y as isize) && sample(&response, width, height, nx, ny) > r {
+                        is_peak = false;
+                    }
+                }
+            }
+            if is_peak {
+                corners[idx] = 1.0;
+            }
+        }
+    }
+
+    corners
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::media::frame::PixelData;
+
+    /// A 32x32 black frame with a white 12x12 square starting at (10, 10).
+    fn square() -> Frame {
+        let mut v = vec![0u8; 32 * 32];
+        for y in 10..22 {
+            for x in 10..22 {
+                v[y * 32 + x] = 255;
+            }
+        }
+        Frame::new(32, 32, PixelData::GRAY(v)).unwrap()
+    }
+
+    fn lit(frame: &Frame) -> Vec<(usize, usize)> {
+        let luma = frame.luma_plane().unwrap();
+        (0..luma.len())
+            .filter(|&i| luma[i] > 0.5)
+            .map(|i| (i % 32, i / 32))
+            .collect()
+    }
+
+    /// A float frame of `f(x, y)`, so detector responses above 1 are not clipped.
+    fn plane(width: usize, height: usize, f: impl Fn(usize, usize) -> f32) -> Frame {
+        let v: Vec<f32> = (0..width * height).map(|i| f(i % width, i / width)).collect();
+        Frame::from_float_planes(width as u32, height as u32, [v.clone(), v.clone(), v, vec![1.0; width * height]]).unwrap()
+    }
+
+    fn response(detector: EdgeDetector, frame: &Frame) -> Vec<f32> {
+        detector.apply(frame).unwrap().luma_plane().unwrap()
+    }
+
+    #[test]
+    fn sobel_and_laplacian_peak_on_a_vertical_step() {
+        let step = plane(16, 8, |x, _| if x < 8 { 0.0 } else { 1.0 });
+        let near = |a: f32, b: f32| (a - b).abs() < 1e-4;
+        // Both columns next to the step see the full 1 + 2 + 1; the flat areas see nothing.
+        let sobel = response(EdgeDetector::Sobel, &step);
+        for (i, &m) in sobel.iter().enumerate() {
+            let expected = if matches!(i % 16, 7 | 8) { 4.0 } else { 0.0 };
+            assert!(near(m, expected), "sobel at {i}: {m}");
+        }
+        let laplacian = response(EdgeDetector::Laplacian, &step);
+        for (i, &m) in laplacian.iter().enumerate() {
+            let expected = if matches!(i % 16, 7 | 8) { 1.0 } else { 0.0 };
+            assert!(near(m, expected), "laplacian at {i}: {m}");
+        }
+    }
+
+    #[test]
+    fn scharr_matches_sobel_on_axes_and_answers_more_on_diagonals() {
+        let step = plane(16, 8, |x, _| if x < 8 { 0.0 } else { 1.0 });
+        let (sobel, scharr) = (response(EdgeDetector::Sobel, &step), response(EdgeDetector::Scharr, &step));
+        assert!(sobel.iter().zip(&scharr).all(|(a, b)| (a - b).abs() < 1e-4));
+
+        let diagonal = plane(16, 16, |x, y| if x + y > 15 { 1.0 } else { 0.0 });
+        let (sobel, scharr) = (response(EdgeDetector::Sobel, &diagonal), response(EdgeDetector::Scharr, &diagonal));
+        let centre = 8 * 16 + 7;
+        assert!(scharr[centre] > sobel[centre] + 0.3, "{} vs {}", scharr[centre], sobel[centre]);
+        assert_eq!((sobel[0], scharr[0]), (0.0, 0.0));
+    }
+
+    #[test]
+    fn canny_traces_square_outline() {
+        let edges = EdgeDetector::Canny { low: 50.0, high: 150.0 }.apply(&square()).unwrap();
+        let lit = lit(&edges);
+        assert!(!lit.is_empty());
+        // Every edge pixel sits on the square's boundary, never deep inside or far outside.
+        assert!(lit.iter().all(|&(x, y)| (8..=23).contains(&x) && (8..=23).contains(&y)));
+        assert!(lit.iter().all(|&(x, y)| !((12..=19).contains(&x) && (12..=19).contains(&y))));
+    }
+
+    #[test]
+    fn harris_finds_the_four_corners() {
+        let corners = EdgeDetector::Harris { k: 0.05, threshold: 0.1 }.apply(&square()).unwrap();
+        let lit = lit(&corners);
+        for (cx, cy) in [(10, 10), (21, 10), (10, 21), (21, 21)] {
+            assert!(lit.iter().any(|&(x, y)| x.abs_diff(cx) <= 2 && y.abs_diff(cy) <= 2));
+        }
+        assert!(lit.len() <= 8);
+    }
+}
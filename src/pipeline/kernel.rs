@@ -32,11 +32,20 @@ impl Kernel {
     }
 
     /// Applies a spatial convolution around a specific (x, y) coordinate.
+    ///
+    /// Colour is weighted by alpha (premultiplied) and divided back out afterwards,
+    /// so transparent neighbours, whatever colour they hold, do not bleed into the
+    /// result; alpha itself is averaged. Opaque neighbourhoods and kernels whose
+    /// weights sum to zero give the plain weighted sum, as do pixels left all but
+    /// transparent: with negative weights the coverage can cancel to almost nothing,
+    /// and dividing by it would blow the colour up.
     pub fn apply_to_pixel(&self, x: u32, y: u32, original_frame: &Frame) -> Color {
         let half = (self.size / 2) as i32;
-        let mut r_sum = 0.0;
-        let mut g_sum = 0.0;
-        let mut b_sum = 0.0;
+        let mut sums = [0.0f32; 3];
+        let mut premultiplied = [0.0f32; 3];
+        let mut a_sum = 0.0;
+        let mut coverage = 0.0;
+        let mut weight_sum = 0.0;
 
         let width = original_frame.width() as i32;
         let height = original_frame.height() as i32;
@@ -50,9 +59,15 @@ impl Kernel {
                 let weight = self.matrix[(ky * self.size as i32 + kx) as usize];
 
                 if let Ok(color) = original_frame.get_pixel(&Pos(px, py)) {
-                    r_sum += color.r() as f32 * weight;
-                    g_sum += color.g() as f32 * weight;
-                    b_sum += color.b() as f32 * weight;
+                    let [r, g, b, a] = color.channels_f32();
+                    let alpha = a / 255.0;
+                    for (i, c) in [r, g, b].into_iter().enumerate() {
+                        sums[i] += c * weight;
+                        premultiplied[i] += c * alpha * weight;
+                    }
+                    a_sum += a * weight;
+                    coverage += alpha * weight;
+                    weight_sum += weight;
                 }
             }
         }
@@ -63,11 +78,67 @@ impl Kernel {
             self.divisor
         };
 
-        Color::RGB(
-            (r_sum / div).clamp(0.0, 255.0) as u8,
-            (g_sum / div).clamp(0.0, 255.0) as u8,
-            (b_sum / div).clamp(0.0, 255.0) as u8,
-        )
-        .to_rgba()
+        let [r, g, b] = if weight_sum != 0.0 && coverage > weight_sum.abs() * 1e-3 {
+            premultiplied.map(|v| v / coverage * weight_sum / div)
+        } else {
+            sums.map(|v| v / div)
+        };
+        let q = |v: f32| v.clamp(0.0, 255.0) as u8;
+
+        // The result keeps the centre pixel's layout. Alpha is the weighted average,
+        // whatever the divisor does to colour; zero-sum kernels keep the centre's.
+        let centre = original_frame.get_pixel(&Pos(x, y));
+        let a = if weight_sum != 0.0 {
+            (a_sum / weight_sum).clamp(0.0, 255.0)
+        } else {
+            centre.as_ref().map_or(255.0, |c| c.channels_f32()[3])
+        };
+        match centre {
+            Ok(Color::Gray(_)) => Color::Gray(q(0.299 * r + 0.587 * g + 0.114 * b)),
+            Ok(Color::RGBA(..)) => Color::RGBA(q(r), q(g), q(b), q(a)),
+            Ok(Color::RGBAF(..)) => Color::RGBAF(r, g, b, a),
+            _ => Color::RGB(q(r), q(g), q(b)),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::media::frame::PixelData;
+
+    #[test]
+    fn transparent_neighbours_do_not_darken_a_blur() {
+        // White, then fully transparent black, then white.
+        let data = PixelData::RGBA(vec![255, 0, 255], vec![255, 0, 255], vec![255, 0, 255], vec![255, 0, 255]);
+        let frame = Frame::new(3, 1, data).unwrap();
+        let Color::RGBA(r, g, b, a) = Kernel::generate_blur("blur", 3).apply_to_pixel(1, 0, &frame) else {
+            panic!("expected an RGBA pixel");
+        };
+        assert_eq!([r, g, b], [255, 255, 255]);
+        assert_eq!(a, 170);
+    }
+
+    #[test]
+    fn sharpening_an_antialiased_edge_stays_bounded() {
+        // Transparent black, a red edge pixel at a third coverage, then opaque gray.
+        // Across one row the sharpen weights are -1, 3, -1, so the edge pixel's
+        // coverage all but cancels out.
+        let data = PixelData::RGBAF32(vec![0.0, 1.0, 0.8], vec![0.0, 0.0, 0.8], vec![0.0, 0.0, 0.8], vec![0.0, 0.3334, 1.0]);
+        let frame = Frame::new(3, 1, data).unwrap();
+        let sharpen = Kernel {
+            name: "sharpen".into(),
+            matrix: vec![0.0, -1.0, 0.0, -1.0, 5.0, -1.0, 0.0, -1.0, 0.0],
+            size: 3,
+            divisor: 1.0,
+        };
+        for x in 0..3 {
+            let Color::RGBAF(r, g, b, a) = sharpen.apply_to_pixel(x, 0, &frame) else {
+                panic!("expected a float pixel");
+            };
+            // No result can be further out than full scale times the absolute weights.
+            assert!([r, g, b].iter().all(|v| v.is_finite() && v.abs() <= 255.0 * 9.0), "{x}: {r} {g} {b}");
+            assert!((0.0..=255.0).contains(&a));
+        }
     }
 }
@@ -0,0 +1,165 @@
+use crate::media::frame::{Frame, FrameError};
+use crate::pipeline::plane;
+
+// ── Keying ───────────────────────────────────────────────────────────────────
+//
+// Keys work on straight (unpremultiplied) colour and only ever lower alpha, so a
+// key can be stacked on a frame that is already partly transparent. Chroma keys
+// measure distance in the CbCr plane of BT.601 YCbCr, which leaves out brightness:
+// a shadow on a green screen is still green. Tolerance and softness are on the
+// 0 - 255 scale of that distance; the largest possible distance is about 180.
+
+/// One keying or matte operation, run by the `NativeKey` stage.
+#[derive(Debug, Clone, Copy)]
+pub enum Keyer {
+    /// Removes colours whose chroma is within `tolerance` of the key colour, with a
+    /// `softness`-wide ramp to opaque, and takes `despill` (0 - 1) of the key colour's
+    /// cast out of what is left.
+    Chroma { color: [f32; 3], tolerance: f32, softness: f32, despill: f32 },
+    /// Removes pixels with luma below `threshold` (above it with `invert`), with a
+    /// `softness`-wide ramp. Both are 0 - 255.
+    Luma { threshold: f32, softness: f32, invert: bool },
+    /// Takes `strength` (0 - 1) of the key colour's cast out of every pixel, keeping luma.
+    Despill { color: [f32; 3], strength: f32 },
+    /// Shrinks the matte by `radius` pixels.
+    Choke { radius: u32 },
+    /// Grows the matte by `radius` pixels.
+    Grow { radius: u32 },
+}
+
+fn luma(r: f32, g: f32, b: f32) -> f32 {
+    0.299 * r + 0.587 * g + 0.114 * b
+}
+
+fn chroma(r: f32, g: f32, b: f32) -> (f32, f32) {
+    (-0.168736 * r - 0.331264 * g + 0.5 * b, 0.5 * r - 0.418688 * g - 0.081312 * b)
+}
+
+/// 0 below `low`, 1 above `low + width`, a smoothstep between; a hard edge at width 0.
+fn ramp(value: f32, low: f32, width: f32) -> f32 {
+    if width <= 0.0 {
+        return if value > low { 1.0 } else { 0.0 };
+    }
+    let t = ((value - low) / width).clamp(0.0, 1.0);
+    t * t * (3.0 - 2.0 * t)
+}
+
+/// Removes `strength` of the component of the pixel's chroma that points at the key's
+/// chroma, rebuilding RGB around the unchanged luma.
+fn despill(rgb: [f32; 3], key: (f32, f32), strength: f32) -> [f32; 3] {
+    let length = (key.0 * key.0 + key.1 * key.1).sqrt();
+    if length <= f32::EPSILON || strength <= 0.0 {
+        return rgb;
+    }
+    let direction = (key.0 / length, key.1 / length);
+    let [r, g, b] = rgb;
+    let (cb, cr) = chroma(r, g, b);
+    let along = cb * direction.0 + cr * direction.1;
+    if along <= 0.0 {
+        return rgb;
+    }
+    let (cb, cr) = (cb - strength * along * direction.0, cr - strength * along * direction.1);
+    let y = luma(r, g, b);
+    [y + 1.402 * cr, y - 0.344136 * cb - 0.714136 * cr, y + 1.772 * cb]
+}
+
+/// Lowest (`choke`) or highest alpha within a disc of `radius` around each pixel.
+fn morph(alpha: &[f32], width: usize, height: usize, radius: u32, choke: bool) -> Vec<f32> {
+    let r = radius as isize;
+    let spans: Vec<(isize, isize)> = (-r..=r)
+        .map(|dy| (dy, ((r * r - dy * dy) as f32).sqrt() as isize))
+        .collect();
+    plane::par_pixels(width, height, |x, y| {
+        let mut out = alpha[y * width + x];
+        for &(dy, half) in &spans {
+            for dx in -half..=half {
+                let v = plane::sample(alpha, width, height, x as isize + dx, y as isize + dy);
+                out = if choke { out.min(v) } else { out.max(v) };
+            }
+        }
+        out
+    })
+}
+
+impl Keyer {
+    /// Returns the keyed copy of the frame in its own layout.
+    pub fn apply(&self, frame: &Frame) -> Result<Frame, FrameError> {
+        let (width, height) = (frame.width() as usize, frame.height() as usize);
+        if width == 0 || height == 0 {
+            return Err(FrameError::EmptyFrame);
+        }
+        let [mut r, mut g, mut b, mut a] = frame.rgba_planes()?;
+
+        match *self {
+            Keyer::Chroma { color, tolerance, softness, despill: strength } => {
+                let key = chroma(color[0], color[1], color[2]);
+                for i in 0..a.len() {
+                    let (cb, cr) = chroma(r[i], g[i], b[i]);
+                    let distance = ((cb - key.0).powi(2) + (cr - key.1).powi(2)).sqrt() * 255.0;
+                    a[i] *= ramp(distance, tolerance, softness);
+                    [r[i], g[i], b[i]] = despill([r[i], g[i], b[i]], key, strength);
+                }
+            }
+            Keyer::Luma { threshold, softness, invert } => {
+                for i in 0..a.len() {
+                    let y = luma(r[i], g[i], b[i]) * 255.0;
+                    a[i] *= if invert { 1.0 - ramp(y, threshold - softness, softness) } else { ramp(y, threshold, softness) };
+                }
+            }
+            Keyer::Despill { color, strength } => {
+                let key = chroma(color[0], color[1], color[2]);
+                for i in 0..a.len() {
+                    [r[i], g[i], b[i]] = despill([r[i], g[i], b[i]], key, strength);
+                }
+            }
+            Keyer::Choke { radius } => a = morph(&a, width, height, radius, true),
+            Keyer::Grow { radius } => a = morph(&a, width, height, radius, false),
+        }
+
+        frame.with_planes([r, g, b, a])
+    }
+
+    /// How far from a pixel the operation reads, for tiled execution.
+    pub fn radius(&self) -> u32 {
+        match self {
+            Keyer::Choke { radius } | Keyer::Grow { radius } => *radius,
+            _ => 0,
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::media::frame::{PixelData, Pos};
+
+    #[test]
+    fn chroma_key_gives_a_soft_matte_without_green_fringe() {
+        // Green screen, a greenish fringe pixel, and the subject.
+        let r = vec![0.0, 0.3, 0.8];
+        let g = vec![0.9, 0.75, 0.6];
+        let b = vec![0.1, 0.3, 0.5];
+        let frame = Frame::from_float_planes(3, 1, [r, g, b, vec![1.0; 3]]).unwrap();
+
+        let key = Keyer::Chroma { color: [0.0, 1.0, 0.0], tolerance: 40.0, softness: 60.0, despill: 1.0 };
+        let keyed = key.apply(&frame).unwrap();
+        let alpha = |x| keyed.get_pixel(&Pos(x, 0)).unwrap().channels_f32()[3] / 255.0;
+        assert!(alpha(0) < 0.01, "screen is removed");
+        assert!(alpha(1) > 0.05 && alpha(1) < 0.95, "fringe is partly transparent: {}", alpha(1));
+        assert!(alpha(2) > 0.99, "subject stays");
+        let [fr, fg, fb, _] = keyed.get_pixel(&Pos(1, 0)).unwrap().channels_f32();
+        assert!(fg <= (fr + fb) / 2.0 + 1.0, "green cast is removed: {fr} {fg} {fb}");
+
+        let mut dot = vec![0.0; 49];
+        dot[24] = 1.0;
+        let matte = Frame::new(7, 7, PixelData::RGBAF32(vec![1.0; 49], vec![1.0; 49], vec![1.0; 49], dot)).unwrap();
+        let grown = Keyer::Grow { radius: 2 }.apply(&matte).unwrap();
+        let opaque = |f: &Frame| (0..49).filter(|i| f.get_pixel(&Pos(i % 7, i / 7)).unwrap().channels_f32()[3] > 127.0).count();
+        assert_eq!(opaque(&grown), 13);
+        assert_eq!(opaque(&Keyer::Choke { radius: 1 }.apply(&grown).unwrap()), 5);
+
+        let dark = Frame::new(1, 1, PixelData::RGBA(vec![10], vec![10], vec![10], vec![255])).unwrap();
+        let luma_key = Keyer::Luma { threshold: 30.0, softness: 10.0, invert: false };
+        assert_eq!(luma_key.apply(&dark).unwrap().get_pixel(&Pos(0, 0)).unwrap().channels_f32()[3], 0.0);
+    }
+}
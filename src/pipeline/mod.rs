@@ -1,2 +1,14 @@
+pub mod biquad;
+pub mod crop;
+pub mod delay;
+pub mod dynamics;
+pub mod denoise;
+pub mod draw;
+pub mod edge;
 pub mod kernel;
+pub mod key;
+pub mod palette;
 pub mod pipeline;
+pub mod plane;
+pub mod seam;
+pub mod tiled;
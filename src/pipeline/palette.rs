@@ -0,0 +1,605 @@
+use std::collections::HashMap;
+use std::sync::OnceLock;
+
+use crate::media::frame::{Frame, FrameError};
+use crate::range::Mask;
+
+// ── Palette quantisation and dithering ───────────────────────────────────────
+//
+// Colours are handled on the 0-255 scale. Error diffusion carries fractional
+// error from pixel to pixel, so it runs serially in scan order; the ordered
+// dithers only add a position-dependent offset before the lookup.
+
+/// Upper bound on the pixels a palette is built from; larger inputs are strided.
+const MAX_SAMPLES: usize = 1 << 16;
+
+/// k-means refines on a smaller sample: each pass is a nearest lookup per sample.
+/// Smooth gradients take a few dozen passes to settle; most images far fewer.
+const KMEANS_SAMPLES: usize = 1 << 14;
+const KMEANS_PASSES: usize = 32;
+
+/// Pixels with alpha below this are treated as fully transparent when indexing.
+const ALPHA_CUTOFF: f32 = 0.5;
+
+/// Side of the tiled blue-noise threshold map.
+const BLUE_NOISE_SIZE: usize = 64;
+
+/// Most cluster-to-void swaps made relaxing the blue-noise seed. It settles in a
+/// few hundred; the cap only guards against a pattern that cycles.
+const RELAX_SWAPS: usize = BLUE_NOISE_SIZE * BLUE_NOISE_SIZE;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Dither {
+    None,
+    FloydSteinberg,
+    Atkinson,
+    /// 8×8 ordered Bayer matrix.
+    Bayer,
+    /// Ordered dither with a 64×64 void-and-cluster threshold map.
+    BlueNoise,
+}
+
+impl Dither {
+    /// Parses the names scripts use: `none`, `floyd`, `atkinson`, `bayer`, `bluenoise`.
+    pub fn from_name(name: &str) -> Option<Dither> {
+        match name.to_lowercase().as_str() {
+            "none" => Some(Dither::None),
+            "floyd" | "floyd_steinberg" | "fs" => Some(Dither::FloydSteinberg),
+            "atkinson" => Some(Dither::Atkinson),
+            "bayer" | "ordered" => Some(Dither::Bayer),
+            "bluenoise" | "blue_noise" => Some(Dither::BlueNoise),
+            _ => None,
+        }
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum PaletteMethod {
+    MedianCut,
+    /// Median cut refined by a few Lloyd iterations.
+    KMeans,
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub struct Palette {
+    colors: Vec<[u8; 3]>,
+}
+
+impl Palette {
+    pub fn new(colors: Vec<[u8; 3]>) -> Self {
+        Palette { colors }
+    }
+
+    pub fn colors(&self) -> &[[u8; 3]] {
+        &self.colors
+    }
+
+    pub fn len(&self) -> usize {
+        self.colors.len()
+    }
+
+    /// Median cut: starting from one box holding every sample, repeatedly split the
+    /// box with the widest channel range at its median until there are `max_colors`
+    /// boxes or nothing left to split. Each box becomes its mean colour.
+    pub fn median_cut(samples: &[[u8; 3]], max_colors: usize) -> Palette {
+        if samples.is_empty() || max_colors == 0 {
+            return Palette::new(vec![[0, 0, 0]]);
+        }
+
+        let mut boxes: Vec<Vec<[u8; 3]>> = vec![samples.to_vec()];
+        while boxes.len() < max_colors {
+            let widest = boxes
+                .iter()
+                .enumerate()
+                .map(|(i, b)| (i, widest_channel(b)))
+                .filter(|(_, (_, range))| *range > 0)
+                .max_by_key(|(_, (_, range))| *range);
+            let Some((index, (channel, _))) = widest else { break };
+
+            let mut cut = boxes.swap_remove(index);
+            cut.sort_unstable_by_key(|c| c[channel]);
+            let upper = cut.split_off(cut.len() / 2);
+            boxes.push(cut);
+            boxes.push(upper);
+        }
+
+        Palette::new(boxes.iter().map(|b| mean_color(b)).collect())
+    }
+
+    /// k-means seeded with the median-cut palette, run until no colour moves. Empty
+    /// clusters keep their seed.
+    pub fn kmeans(samples: &[[u8; 3]], max_colors: usize) -> Palette {
+        let mut palette = Palette::median_cut(samples, max_colors);
+        let stride = samples.len().div_ceil(KMEANS_SAMPLES).max(1);
+        let samples: Vec<[u8; 3]> = samples.iter().step_by(stride).copied().collect();
+
+        for _ in 0..KMEANS_PASSES {
+            let mut sums = vec![[0u64; 4]; palette.len()];
+            for s in &samples {
+                let k = palette.nearest(s.map(|v| v as f32));
+                for c in 0..3 {
+                    sums[k][c] += s[c] as u64;
+                }
+                sums[k][3] += 1;
+            }
+            let moved: Vec<[u8; 3]> = sums
+                .iter()
+                .zip(&palette.colors)
+                .map(|(sum, &seed)| {
+                    if sum[3] == 0 {
+                        seed
+                    } else {
+                        [0, 1, 2].map(|c| ((sum[c] + sum[3] / 2) / sum[3]) as u8)
+                    }
+                })
+                .collect();
+            if moved == palette.colors {
+                break;
+            }
+            palette.colors = moved;
+        }
+        palette
+    }
+
+    /// Builds one palette shared by every frame, from their visible pixels.
+    /// Sharing a palette keeps colours from flickering between frames of an animation.
+    pub fn from_frames(frames: &[&Frame], max_colors: usize, method: PaletteMethod) -> Result<Palette, FrameError> {
+        let total: usize = frames.iter().map(|f| (f.width() * f.height()) as usize).sum();
+        let stride = total.div_ceil(MAX_SAMPLES).max(1);
+
+        let mut samples = Vec::with_capacity(total.min(MAX_SAMPLES));
+        let mut n = 0usize;
+        for frame in frames {
+            let [r, g, b, a] = frame.rgba_planes()?;
+            for i in 0..r.len() {
+                n += 1;
+                if n % stride != 0 || a[i] < ALPHA_CUTOFF {
+                    continue;
+                }
+                samples.push([to_u8(r[i]), to_u8(g[i]), to_u8(b[i])]);
+            }
+        }
+        Ok(match method {
+            PaletteMethod::MedianCut => Palette::median_cut(&samples, max_colors),
+            PaletteMethod::KMeans => Palette::kmeans(&samples, max_colors),
+        })
+    }
+
+    /// Index of the closest palette entry by squared RGB distance.
+    pub fn nearest(&self, color: [f32; 3]) -> usize {
+        let mut best = 0;
+        let mut best_dist = f32::MAX;
+        for (i, c) in self.colors.iter().enumerate() {
+            let dr = color[0] - c[0] as f32;
+            let dg = color[1] - c[1] as f32;
+            let db = color[2] - c[2] as f32;
+            let dist = dr * dr + dg * dg + db * db;
+            if dist < best_dist {
+                best = i;
+                best_dist = dist;
+            }
+        }
+        best
+    }
+
+    /// Ordered-dither offset range: the step of a per-channel grid with as many
+    /// cells as the palette has colours.
+    fn spread(&self) -> f32 {
+        255.0 / ((self.len() as f32).cbrt() - 1.0).max(1.0)
+    }
+
+    /// Maps every pixel to a palette index. Transparent pixels give `None` and
+    /// take no diffused error.
+    pub fn index_frame(&self, frame: &Frame, dither: Dither) -> Result<Vec<Option<u8>>, FrameError> {
+        let [r, g, b, a] = frame.rgba_planes()?;
+        let rgb = [r, g, b].map(|p| p.iter().map(|v| v * 255.0).collect::<Vec<f32>>());
+
+        let mut cache: HashMap<[u8; 3], u8> = HashMap::new();
+        Ok(dither_map(
+            frame.width() as usize,
+            frame.height() as usize,
+            &rgb,
+            dither,
+            self.spread(),
+            |i| a[i] < ALPHA_CUTOFF,
+            |color| {
+                let key = color.map(to_u8_255);
+                let index = *cache.entry(key).or_insert_with(|| self.nearest(key.map(|v| v as f32)) as u8);
+                (index, self.colors[index as usize].map(|v| v as f32))
+            },
+        ))
+    }
+}
+
+/// What a `Quantize` stage snaps colours to.
+#[derive(Debug, Clone)]
+pub enum QuantizeTarget {
+    Palette(Palette),
+    /// A palette of up to `colors` entries built from the frame being processed.
+    Adaptive { colors: usize, method: PaletteMethod },
+    /// Evenly spaced levels per channel, 2 - 256.
+    Levels(u32),
+}
+
+/// Native stage behind `palette`, `palette_from` and `dither`.
+#[derive(Debug, Clone)]
+pub struct Quantize {
+    pub target: QuantizeTarget,
+    pub dither: Dither,
+}
+
+impl Quantize {
+    /// Returns a copy with every colour snapped to the target. Alpha is kept, and
+    /// pixels outside `mask` neither change nor receive diffused error.
+    pub fn apply(&self, frame: &Frame, mask: Option<&Mask>) -> Result<Frame, FrameError> {
+        let width = frame.width() as usize;
+        let height = frame.height() as usize;
+        if width == 0 || height == 0 {
+            return Err(FrameError::EmptyFrame);
+        }
+
+        let [r, g, b, a] = frame.rgba_planes()?;
+        let rgb = [&r, &g, &b].map(|p| p.iter().map(|v| v * 255.0).collect::<Vec<f32>>());
+        let outside = |i: usize| mask.is_some_and(|m| !m.contains(i % width, i / width));
+
+        let adaptive;
+        let palette = match &self.target {
+            QuantizeTarget::Palette(palette) => palette,
+            QuantizeTarget::Adaptive { colors, method } => {
+                adaptive = Palette::from_frames(&[frame], *colors, *method)?;
+                &adaptive
+            }
+            QuantizeTarget::Levels(levels) => {
+                let step = 255.0 / (*levels).clamp(2, 256).saturating_sub(1) as f32;
+                let mapped = dither_map(width, height, &rgb, self.dither, step, outside, |color| {
+                    let chosen = color.map(|v| ((v / step).round() * step).clamp(0.0, 255.0));
+                    (chosen, chosen)
+                });
+                return frame.with_planes(write_back([r, g, b, a], &mapped));
+            }
+        };
+
+        let mut cache: HashMap<[u8; 3], [f32; 3]> = HashMap::new();
+        let mapped = dither_map(width, height, &rgb, self.dither, palette.spread(), outside, |color| {
+            let key = color.map(to_u8_255);
+            let chosen = *cache
+                .entry(key)
+                .or_insert_with(|| palette.colors[palette.nearest(key.map(|v| v as f32))].map(|v| v as f32));
+            (chosen, chosen)
+        });
+        frame.with_planes(write_back([r, g, b, a], &mapped))
+    }
+}
+
+/// Writes mapped 0-255 colours into normalised planes, leaving skipped pixels as they were.
+fn write_back(mut planes: [Vec<f32>; 4], mapped: &[Option<[f32; 3]>]) -> [Vec<f32>; 4] {
+    for (i, color) in mapped.iter().enumerate() {
+        if let Some(color) = color {
+            for c in 0..3 {
+                planes[c][i] = color[c] / 255.0;
+            }
+        }
+    }
+    planes
+}
+
+/// Runs `pick` on every pixel in scan order, with the chosen dither applied to its
+/// input. `pick` returns its result and the colour it stands for, from which the
+/// diffusion error is measured. Skipped pixels give `None`.
+fn dither_map<T: Copy>(
+    width: usize,
+    height: usize,
+    rgb: &[Vec<f32>; 3],
+    dither: Dither,
+    spread: f32,
+    skip: impl Fn(usize) -> bool,
+    mut pick: impl FnMut([f32; 3]) -> (T, [f32; 3]),
+) -> Vec<Option<T>> {
+    // (dx, dy, weight) of each neighbour that receives error.
+    let diffusion: &[(isize, usize, f32)] = match dither {
+        Dither::FloydSteinberg => &[(1, 0, 7.0 / 16.0), (-1, 1, 3.0 / 16.0), (0, 1, 5.0 / 16.0), (1, 1, 1.0 / 16.0)],
+        // Atkinson spreads only 6/8 of the error, which keeps highlights and shadows clean.
+        Dither::Atkinson => &[(1, 0, 0.125), (2, 0, 0.125), (-1, 1, 0.125), (0, 1, 0.125), (1, 1, 0.125), (0, 2, 0.125)],
+        _ => &[],
+    };
+
+    // Error for the current row and the two below, padded by two on each side.
+    let pad = 2;
+    let mut rows = vec![vec![[0.0f32; 3]; width + 2 * pad]; 3];
+    let mut out = vec![None; width * height];
+
+    for y in 0..height {
+        for x in 0..width {
+            let i = y * width + x;
+            if skip(i) {
+                continue;
+            }
+
+            let mut color = [rgb[0][i], rgb[1][i], rgb[2][i]];
+            match dither {
+                Dither::Bayer | Dither::BlueNoise => {
+                    let offset = (threshold(dither, x, y) - 0.5) * spread;
+                    color = color.map(|v| v + offset);
+                }
+                Dither::FloydSteinberg | Dither::Atkinson => {
+                    for c in 0..3 {
+                        color[c] += rows[0][x + pad][c];
+                    }
+                }
+                Dither::None => {}
+            }
+
+            let (result, chosen) = pick(color);
+            out[i] = Some(result);
+
+            for &(dx, dy, weight) in diffusion {
+                let target = (x + pad).wrapping_add_signed(dx);
+                for c in 0..3 {
+                    let err = color[c].clamp(0.0, 255.0) - chosen[c];
+                    rows[dy][target][c] += err * weight;
+                }
+            }
+        }
+        rows.rotate_left(1);
+        rows[2].iter_mut().for_each(|e| *e = [0.0; 3]);
+    }
+    out
+}
+
+/// Ordered-dither threshold in 0.0 - 1.0 for a pixel position.
+fn threshold(dither: Dither, x: usize, y: usize) -> f32 {
+    match dither {
+        Dither::Bayer => (bayer8(x % 8, y % 8) as f32 + 0.5) / 64.0,
+        _ => blue_noise()[(y % BLUE_NOISE_SIZE) * BLUE_NOISE_SIZE + x % BLUE_NOISE_SIZE],
+    }
+}
+
+/// Entry of the 8×8 Bayer matrix, built by interleaving the bits of `x ^ y` and `y`.
+fn bayer8(x: usize, y: usize) -> usize {
+    let xy = x ^ y;
+    let mut value = 0;
+    for bit in 0..3 {
+        value |= ((xy >> bit) & 1) << (2 * (2 - bit) + 1);
+        value |= ((y >> bit) & 1) << (2 * (2 - bit));
+    }
+    value
+}
+
+/// Tileable blue-noise thresholds, generated once with Ulichney's void-and-cluster
+/// method: a seed pattern is relaxed until no pixel is both the tightest cluster and
+/// the largest void, then pixels are ranked by removing clusters and filling voids.
+fn blue_noise() -> &'static [f32] {
+    static MAP: OnceLock<Vec<f32>> = OnceLock::new();
+    MAP.get_or_init(|| {
+        let n = BLUE_NOISE_SIZE;
+        let len = n * n;
+
+        // Gaussian falloff with sigma 1.5; beyond 7 pixels it is below 1e-4 and is
+        // left out. Positions wrap so the finished map tiles seamlessly.
+        const REACH: isize = 7;
+        let sigma2 = 2.0 * 1.5f32 * 1.5;
+        let splat = |energy: &mut [f32], at: usize, sign: f32| {
+            let (ay, ax) = ((at / n) as isize, (at % n) as isize);
+            for dy in -REACH..=REACH {
+                for dx in -REACH..=REACH {
+                    let y = (ay + dy).rem_euclid(n as isize) as usize;
+                    let x = (ax + dx).rem_euclid(n as isize) as usize;
+                    energy[y * n + x] += sign * (-((dx * dx + dy * dy) as f32) / sigma2).exp();
+                }
+            }
+        };
+        let tightest = |pattern: &[bool], energy: &[f32]| {
+            (0..len).filter(|&i| pattern[i]).max_by(|&a, &b| energy[a].total_cmp(&energy[b]))
+        };
+        let largest_void = |pattern: &[bool], energy: &[f32]| {
+            (0..len).filter(|&i| !pattern[i]).min_by(|&a, &b| energy[a].total_cmp(&energy[b]))
+        };
+
+        // Seed: about a tenth of the pixels, from a fixed LCG so the map never changes.
+        let mut state: u32 = 0x2545_F491;
+        let mut pattern = vec![false; len];
+        let mut energy = vec![0.0f32; len];
+        let mut ones = 0;
+        while ones < len / 10 {
+            state = state.wrapping_mul(1_664_525).wrapping_add(1_013_904_223);
+            let i = (state >> 8) as usize % len;
+            if !pattern[i] {
+                pattern[i] = true;
+                splat(&mut energy, i, 1.0);
+                ones += 1;
+            }
+        }
+
+        // Relax until moving the tightest cluster would put it straight back.
+        for _ in 0..RELAX_SWAPS {
+            let Some(cluster) = tightest(&pattern, &energy) else { break };
+            pattern[cluster] = false;
+            splat(&mut energy, cluster, -1.0);
+            let Some(void) = largest_void(&pattern, &energy) else { break };
+            pattern[void] = true;
+            splat(&mut energy, void, 1.0);
+            if void == cluster {
+                break;
+            }
+        }
+
+        let mut rank = vec![0usize; len];
+
+        // Phase 1: peel clusters off a copy of the seed to rank its pixels.
+        let (mut p, mut e, mut count) = (pattern.clone(), energy.clone(), ones);
+        while let Some(cluster) = tightest(&p, &e) {
+            count -= 1;
+            rank[cluster] = count;
+            p[cluster] = false;
+            splat(&mut e, cluster, -1.0);
+        }
+
+        // Phase 2: fill the largest voids until every pixel is ranked.
+        let mut count = ones;
+        while let Some(void) = largest_void(&pattern, &energy) {
+            rank[void] = count;
+            count += 1;
+            pattern[void] = true;
+            splat(&mut energy, void, 1.0);
+        }
+
+        rank.iter().map(|&r| (r as f32 + 0.5) / len as f32).collect()
+    })
+}
+
+fn to_u8(v: f32) -> u8 {
+    (v * 255.0).round().clamp(0.0, 255.0) as u8
+}
+
+fn to_u8_255(v: f32) -> u8 {
+    v.round().clamp(0.0, 255.0) as u8
+}
+
+/// The channel with the largest spread in `colors`, and that spread.
+fn widest_channel(colors: &[[u8; 3]]) -> (usize, u8) {
+    (0..3)
+        .map(|c| {
+            let (lo, hi) = colors
+                .iter()
+                .fold((u8::MAX, u8::MIN), |(lo, hi), col| (lo.min(col[c]), hi.max(col[c])));
+            (c, hi.saturating_sub(lo))
+        })
+        .max_by_key(|&(_, range)| range)
+        .unwrap_or((0, 0))
+}
+
+fn mean_color(colors: &[[u8; 3]]) -> [u8; 3] {
+    let mut sum = [0u64; 3];
+    for col in colors {
+        for c in 0..3 {
+            sum[c] += col[c] as u64;
+        }
+    }
+    let n = colors.len().max(1) as u64;
+    sum.map(|s| ((s + n / 2) / n) as u8)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::media::frame::PixelData;
+    use crate::range::Circle;
+
+    /// 64×64 opaque frame whose channels ramp in different directions.
+    fn colorful() -> Frame {
+        let plane = |f: fn(usize, usize) -> usize| (0..4096).map(|i| f(i % 64, i / 64) as u8).collect::<Vec<u8>>();
+        let data = PixelData::RGBA(plane(|x, _| x * 4), plane(|_, y| y * 4), plane(|x, y| (x + y) * 2), vec![255; 4096]);
+        Frame::new(64, 64, data).unwrap()
+    }
+
+    /// 64×64 gray ramp from 20% to 80%.
+    fn ramp() -> Frame {
+        let gray: Vec<u8> = (0..4096).map(|i| (51.0 + (i % 64) as f32 * 153.0 / 63.0).round() as u8).collect();
+        Frame::new(64, 64, PixelData::RGBA(gray.clone(), gray.clone(), gray, vec![255; 4096])).unwrap()
+    }
+
+    fn pixels(frame: &Frame) -> Vec<[u8; 3]> {
+        let [r, g, b, _] = frame.rgba_planes().unwrap();
+        (0..r.len()).map(|i| [to_u8(r[i]), to_u8(g[i]), to_u8(b[i])]).collect()
+    }
+
+    fn mean(frame: &Frame) -> f32 {
+        let [r, _, _, _] = frame.rgba_planes().unwrap();
+        r.iter().sum::<f32>() / r.len() as f32
+    }
+
+    #[test]
+    fn adaptive_palettes_stay_within_size_and_settle_on_clusters() {
+        let frame = colorful();
+        for method in [PaletteMethod::MedianCut, PaletteMethod::KMeans] {
+            for colors in [2, 5, 16] {
+                let quantize = Quantize { target: QuantizeTarget::Adaptive { colors, method }, dither: Dither::FloydSteinberg };
+                let mut seen = pixels(&quantize.apply(&frame, None).unwrap());
+                seen.sort_unstable();
+                seen.dedup();
+                assert!(seen.len() <= colors, "{method:?} gave {} of {colors} colours", seen.len());
+            }
+        }
+
+        // Four tight clusters: both methods find one colour for each.
+        let centers = [[20, 40, 200], [200, 30, 30], [90, 220, 90], [240, 240, 240]];
+        let samples: Vec<[u8; 3]> = (0..400).map(|i| centers[i % 4].map(|c: u8| c - 2 + (i / 4 % 5) as u8)).collect();
+        for palette in [Palette::median_cut(&samples, 4), Palette::kmeans(&samples, 4)] {
+            assert_eq!(palette.len(), 4);
+            for center in centers {
+                let found = palette.colors()[palette.nearest(center.map(|c| c as f32))];
+                assert_eq!(found, center, "{palette:?}");
+            }
+        }
+
+        // On overlapping data k-means ends at a fixed point: re-centring each colour
+        // on the samples nearest to it moves nothing.
+        let samples = pixels(&frame);
+        let palette = Palette::kmeans(&samples, 8);
+        let mut sums = vec![[0u64; 4]; palette.len()];
+        for s in &samples {
+            let k = palette.nearest(s.map(|v| v as f32));
+            for c in 0..3 {
+                sums[k][c] += s[c] as u64;
+            }
+            sums[k][3] += 1;
+        }
+        for (sum, color) in sums.iter().zip(palette.colors()) {
+            let centred = [0, 1, 2].map(|c| ((sum[c] + sum[3] / 2) / sum[3]) as u8);
+            assert_eq!(&centred, color);
+        }
+    }
+
+    #[test]
+    fn palette_from_maps_to_the_given_colours() {
+        let colors = vec![[0, 0, 0], [255, 0, 0], [0, 0, 255], [250, 250, 250]];
+        for dither in [Dither::None, Dither::FloydSteinberg, Dither::Atkinson, Dither::Bayer, Dither::BlueNoise] {
+            let quantize = Quantize { target: QuantizeTarget::Palette(Palette::new(colors.clone())), dither };
+            let out = quantize.apply(&colorful(), None).unwrap();
+            assert!(pixels(&out).iter().all(|p| colors.contains(p)), "{dither:?}");
+            assert!(out.rgba_planes().unwrap()[3].iter().all(|&a| a == 1.0));
+        }
+    }
+
+    #[test]
+    fn error_diffusion_keeps_mean_intensity() {
+        let source = ramp();
+        let black_and_white = Palette::new(vec![[0, 0, 0], [255, 255, 255]]);
+        for dither in [Dither::FloydSteinberg, Dither::Atkinson] {
+            let quantize = Quantize { target: QuantizeTarget::Palette(black_and_white.clone()), dither };
+            let out = quantize.apply(&source, None).unwrap();
+            assert!((mean(&out) - mean(&source)).abs() < 0.01, "{dither:?}: {} vs {}", mean(&out), mean(&source));
+        }
+        // Without dithering every pixel just rounds, which is far off for a ramp.
+        let plain = Quantize { target: QuantizeTarget::Palette(black_and_white), dither: Dither::None };
+        assert_eq!(pixels(&plain.apply(&source, None).unwrap())[20], [0, 0, 0]);
+    }
+
+    #[test]
+    fn pixels_outside_the_mask_are_left_alone() {
+        let source = ramp();
+        let mask = Mask::Circle(Circle { cx: 32, cy: 32, radius: 16 });
+        let quantize = Quantize { target: QuantizeTarget::Levels(2), dither: Dither::FloydSteinberg };
+        let out = quantize.apply(&source, Some(&mask)).unwrap();
+        let (before, after) = (pixels(&source), pixels(&out));
+        for i in 0..4096 {
+            if mask.contains(i % 64, i / 64) {
+                assert!(after[i] == [0, 0, 0] || after[i] == [255, 255, 255]);
+            } else {
+                assert_eq!(after[i], before[i]);
+            }
+        }
+    }
+
+    #[test]
+    fn bayer_and_blue_noise_maps_are_permutations() {
+        let mut bayer: Vec<usize> = (0..64).map(|i| bayer8(i % 8, i / 8)).collect();
+        bayer.sort_unstable();
+        assert_eq!(bayer, (0..64).collect::<Vec<_>>());
+
+        let len = BLUE_NOISE_SIZE * BLUE_NOISE_SIZE;
+        let mut ranks: Vec<usize> = blue_noise().iter().map(|t| (t * len as f32) as usize).collect();
+        ranks.sort_unstable();
+        assert_eq!(ranks, (0..len).collect::<Vec<_>>());
+    }
+}
@@ -1,6 +1,16 @@
 use crate::filter::{Filter,AudioFilter,AudioContext, FilterVM};
-use crate::media::{frame::{Color, Frame, Pos},track::{Track,TrackError}};
+use crate::media::{frame::{BitDepth, BlendMode, Color, Frame, FrameError, PixelFormat, Pos},track::{Track,TrackError}};
+use crate::pipeline::biquad::Biquad;
+use crate::pipeline::crop::{self, Saliency};
+use crate::pipeline::delay::DelayEffect;
+use crate::pipeline::dynamics::Dynamics;
+use crate::pipeline::denoise::Denoiser;
+use crate::pipeline::draw::Drawing;
+use crate::pipeline::edge::EdgeDetector;
 use crate::pipeline::kernel::Kernel;
+use crate::pipeline::key::Keyer;
+use crate::pipeline::palette::Quantize;
+use crate::pipeline::seam;
 use crate::range::Mask;
 
 pub enum Operation {
@@ -27,15 +37,102 @@ pub enum Operation {
         width: u32,
         height: u32,
     },
+    /// Crops away uniform or transparent borders, see `crop::trim_bounds`.
+    NativeTrim {
+        tolerance: f32,
+    },
+
+    /// Crops to the most salient `width` x `height` window.
+    NativeSmartCrop {
+        width: u32,
+        height: u32,
+        saliency: Saliency,
+    },
+
+    /// Content-aware resize by seam carving, steered by optional guide frames.
+    NativeSeamResize {
+        width: u32,
+        height: u32,
+        protect: Option<Frame>,
+        remove: Option<Frame>,
+    },
     Blend{
         x:u32,
         y:u32,
         frame2: Frame,
         alpha: f64,
+        mode: BlendMode,
+    },
+
+    /// An anti-aliased shape painted onto the frame.
+    NativeDraw {
+        drawing: Drawing,
+    },
+
+    NativeEdge {
+        detector: EdgeDetector,
+        mask: Option<Mask>,
+    },
+
+    NativeDenoise {
+        denoiser: Denoiser,
+        mask: Option<Mask>,
+    },
+
+    /// A chroma or luma key, despill, or matte choke/grow, see `key::Keyer`.
+    NativeKey {
+        keyer: Keyer,
+        mask: Option<Mask>,
+    },
+
+    NativeDepth {
+        depth: BitDepth,
+    },
+
+    NativeQuantize {
+        quantize: Quantize,
+        mask: Option<Mask>,
+    },
+
+    NativeFormat {
+        format: PixelFormat,
+    },
+
+    /// `linear()` decodes to linear light, `srgb()` encodes back.
+    NativeTransfer {
+        linear: bool,
     },
     
 }
 
+/// Writes `result` back into `frame`. With a mask only the selected pixels are taken
+/// from `result`; everything else keeps its current value. The mask is in canvas
+/// coordinates and `frame` sits at `origin` on the canvas.
+fn apply_masked(frame: &mut Frame, result: Frame, mask: &Option<Mask>, origin: Pos) -> Result<(), PipelineError> {
+    let Some(mask) = mask else {
+        *frame = result;
+        return Ok(());
+    };
+
+    if frame.data().bytes_per_pixel() != result.data().bytes_per_pixel() {
+        *frame = frame.to_rgba().map_err(|_| PipelineError::PixelError)?;
+    }
+
+    for y in 0..frame.height().min(result.height()) {
+        for x in 0..frame.width().min(result.width()) {
+            if !mask.contains((origin.0 + x) as usize, (origin.1 + y) as usize) {
+                continue;
+            }
+            let pos = Pos(x, y);
+            let color = result.get_pixel(&pos).map_err(|_| PipelineError::PixelError)?;
+            frame
+                .set_pixel(&pos, &color)
+                .map_err(|_| PipelineError::PixelError)?;
+        }
+    }
+    Ok(())
+}
+
 
 
 #[derive(Debug)]
@@ -43,6 +140,12 @@ pub enum PipelineError {
     InvalidData,
     PixelError,
     NotFeasible,
+    /// A frame operation refused the frame, e.g. a layout it does not support.
+    Frame(FrameError),
+    /// Reading or writing the rows of a streamed image failed.
+    Stream(String),
+    /// A track operation refused its input, e.g. a sidechain at another sample rate.
+    Track(TrackError),
 }
 
 pub trait Pipeline {
@@ -64,17 +167,26 @@ pub enum AudioOperation {
         params: Vec<f32>,
     },
     Gain(f32),
-   
+    /// Converts the track to this sample rate, see `Track::resample`.
+    Resample(u32),
+    Biquad(Biquad),
+    /// Echo, chorus, flanger, phaser or reverb, see `DelayEffect`.
+    Delay(DelayEffect),
+    /// Compressor, expander, gate or limiter, detecting on `sidechain` when given.
+    Dynamics { dynamics: Dynamics, sidechain: Option<Track> },
 }
 
 impl AudioPipeline {
     pub fn execute(&self, track: &mut Track) -> Result<(), PipelineError> {
-        let sr = track.sample_rate() as f32;
         let mut vm = FilterVM::new();
 
         for op in &self.operations {
+            // Read per stage: a resample changes the rate for the stages after it.
+            let sr = track.sample_rate() as f32;
             match op {
                 AudioOperation::PointFilter { filter, params } => {
+                    // One state for the whole track, so it runs on across chunks.
+                    let mut state = filter.initial_state(sr, params, &mut vm);
                     for frame in track.buffer_mut() {
                         let base_time = frame.time.to_seconds() as f32;
                         let samples_len = frame.data.first().map_or(0, |ch| ch.len());
@@ -85,7 +197,7 @@ impl AudioPipeline {
                             let l_in = frame.data[0][i];
                             let r_in = if frame.data.len() > 1 { frame.data[1][i] } else { l_in };
 
-                            let (l_out, r_out) = filter.apply(l_in, r_in, exact_t, sr, params, &mut vm);
+                            let (l_out, r_out) = filter.apply(l_in, r_in, exact_t, sr, params, &mut vm, &mut state);
 
                             frame.data[0][i] = l_out;
                             if frame.data.len() > 1 {
@@ -96,6 +208,12 @@ impl AudioPipeline {
                 }
             
                 AudioOperation::Gain(db) => track.gain(*db),
+                AudioOperation::Resample(rate) => *track = track.resample(*rate),
+                AudioOperation::Biquad(biquad) => biquad.apply(track),
+                AudioOperation::Delay(effect) => effect.apply(track),
+                AudioOperation::Dynamics { dynamics, sidechain } => {
+                    dynamics.apply(track, sidechain.as_ref()).map_err(PipelineError::Track)?
+                }
             }
         }
         Ok(())
@@ -104,16 +222,69 @@ impl AudioPipeline {
 
 impl Pipeline for EffectPipeline {
     fn execute(&self, frame: &mut Frame) -> Result<(), PipelineError> {
+        let canvas = (frame.width(), frame.height());
+        self.execute_at(frame, Pos(0, 0), canvas)
+    }
+}
+
+/// Runs a stage that writes one pixel at a time. A YUV420 pixel shares its chroma
+/// with the rest of its 2×2 block, so writing them one by one would leave each block
+/// the colour of its last pixel; those frames are processed as float RGBA instead and
+/// converted back once, which averages the chroma of every block.
+fn per_pixel(frame: &mut Frame, stage: impl FnOnce(&mut Frame) -> Result<(), PipelineError>) -> Result<(), PipelineError> {
+    if frame.format() != PixelFormat::YUV420 {
+        return stage(frame);
+    }
+    let mut rgba = frame
+        .to_format(PixelFormat::RGBA)
+        .and_then(|f| f.to_depth(BitDepth::Float))
+        .map_err(PipelineError::Frame)?;
+    stage(&mut rgba)?;
+    *frame = rgba.to_format(PixelFormat::YUV420).map_err(PipelineError::Frame)?;
+    Ok(())
+}
+
+impl EffectPipeline {
+    /// Runs the operations on `frame` as the piece of a `canvas`-sized image whose
+    /// top-left corner is at `origin`: filters and masks see canvas coordinates, and
+    /// blends and shapes land where they would on the whole canvas. `execute` is the
+    /// whole canvas; tiled execution (`tiled.rs`) passes one tile at a time.
+    pub(crate) fn execute_at(&self, frame: &mut Frame, origin: Pos, canvas: (u32, u32)) -> Result<(), PipelineError> {
+        let Pos(ox, oy) = origin;
+        let at_origin = ox == 0 && oy == 0;
+
         // NOTE: We DO NOT declare width and height out here anymore!
         // The frame size might change mid-pipeline, so we must ask for it on every pass!
 
+        // Stages build new frames, so the source file's metadata is put back at the end.
+        let metadata = frame.metadata().cloned();
+        // Likewise the precision to export at, which only a `depth` stage changes.
+        let export_depth = self.export_depth(frame.export_depth());
+
+        // Stages run on float planes so nothing is rounded between them; the frame
+        // is only quantised again when it is exported or explicitly converted, and
+        // then back to the depth it came in at. Gray and RGB only come from explicit
+        // conversions and keep their layout at 16 bits; YUV420 is stored as it is.
+        let entry_depth = match frame.format() {
+            PixelFormat::RGBA if !frame.data().is_float() => Some(BitDepth::Float),
+            PixelFormat::Gray | PixelFormat::RGB if !frame.data().is_deep() => Some(BitDepth::Sixteen),
+            _ => None,
+        };
+        if let Some(depth) = entry_depth {
+            *frame = frame
+                .to_depth(depth)
+                .map_err(|_| PipelineError::InvalidData)?;
+        }
+
         for operation in &self.operations {
+            // Stages that build a new frame come back marked sRGB; only a transfer stage may change that.
+            let linear = frame.is_linear();
             match operation {
                 Operation::PointFilter {
                     filter,
                     params,
                     mask,
-                } => {
+                } => per_pixel(frame, |frame| {
                     let mut vm = FilterVM::new();
                     let width = frame.width();
                     let height = frame.height();
@@ -121,7 +292,7 @@ impl Pipeline for EffectPipeline {
                     for y in 0..height {
                         for x in 0..width {
                             if let Some(mask) = mask {
-                                if !mask.contains(x as usize, y as usize) {
+                                if !mask.contains((ox + x) as usize, (oy + y) as usize) {
                                     continue;
                                 }
                             }
@@ -130,16 +301,17 @@ impl Pipeline for EffectPipeline {
 
                             let color = frame.get_pixel(&pos).unwrap_or(Color::RGB(0, 0, 0));
 
-                            let result = filter.apply(color, x, y, width, height, params, &mut vm);
+                            let result = filter.apply(color, ox + x, oy + y, canvas.0, canvas.1, params, &mut vm);
 
                             frame
                                 .set_pixel(&pos, &result)
-                                .map_err(|_| PipelineError::PixelError)?;
+                                .map_err(PipelineError::Frame)?;
                         }
                     }
-                }
+                    Ok(())
+                })?,
 
-                Operation::Convolution { kernel, mask } => {
+                Operation::Convolution { kernel, mask } => per_pixel(frame, |frame| {
                     let width = frame.width();
                     let height = frame.height();
                     // Snapshot BEFORE this kernel pass
@@ -148,7 +320,7 @@ impl Pipeline for EffectPipeline {
                     for y in 0..height {
                         for x in 0..width {
                             if let Some(mask) = mask {
-                                if !mask.contains(x as usize, y as usize) {
+                                if !mask.contains((ox + x) as usize, (oy + y) as usize) {
                                     continue;
                                 }
                             }
@@ -159,31 +331,163 @@ impl Pipeline for EffectPipeline {
 
                             frame
                                 .set_pixel(&pos, &result)
-                                .map_err(|_| PipelineError::PixelError)?;
+                                .map_err(PipelineError::Frame)?;
                         }
                     }
-                }
-                Operation::Blend {x,y, frame2, alpha } =>{
-                    frame.blend_on(&Pos(*x, *y),frame2, *alpha).map_err(|_| PipelineError::NotFeasible)?;
+                    Ok(())
+                })?,
+                Operation::Blend {x,y, frame2, alpha, mode } if at_origin && canvas == (frame.width(), frame.height()) =>{
+                    frame.blend_on_with(&Pos(*x, *y),frame2, *alpha, *mode).map_err(PipelineError::Frame)?;
                     
                 }
 
+                Operation::Blend { x, y, frame2, alpha, mode } => {
+                    // Only the part of the overlay over this piece of the canvas is blended.
+                    let (fw, fh) = (frame2.width(), frame2.height());
+                    if x + fw > canvas.0 || y + fh > canvas.1 {
+                        return Err(PipelineError::Frame(FrameError::BlitFailed));
+                    }
+                    let (left, top) = ((*x).max(ox), (*y).max(oy));
+                    let (right, bottom) = ((x + fw).min(ox + frame.width()), (y + fh).min(oy + frame.height()));
+                    if left < right && top < bottom {
+                        let part = frame2
+                            .crop(left - x, top - y, right - left, bottom - top)
+                            .map_err(PipelineError::Frame)?;
+                        frame
+                            .blend_on_with(&Pos(left - ox, top - oy), &part, *alpha, *mode)
+                            .map_err(PipelineError::Frame)?;
+                    }
+                }
+
+                Operation::NativeDraw { drawing } => {
+                    drawing.apply_at(frame, ox, oy).map_err(PipelineError::Frame)?;
+                }
+
                 Operation::NativeResize { width, height } => {
                     
-                    let new_frame = frame.resize(*width, *height).map_err(|_| PipelineError::NotFeasible)?;
+                    let new_frame = frame.resize(*width, *height).map_err(PipelineError::Frame)?;
                     
                     
                     *frame = new_frame; 
                 }
 
                 Operation::NativeCrop { x, y, width, height } => {
-                    let new_frame = frame.crop(*x, *y, *width, *height).map_err(|_| PipelineError::NotFeasible)?;
+                    let new_frame = frame.crop(*x, *y, *width, *height).map_err(PipelineError::Frame)?;
                     *frame = new_frame;
                 } 
+
+                Operation::NativeTrim { tolerance } => {
+                    // A frame that is all border is left as it is.
+                    if let Some((x, y, width, height)) = crop::trim_bounds(frame, *tolerance).map_err(PipelineError::Frame)? {
+                        *frame = frame.crop(x, y, width, height).map_err(PipelineError::Frame)?;
+                    }
+                }
+
+                Operation::NativeSmartCrop { width, height, saliency } => {
+                    let (width, height) = ((*width).min(frame.width()), (*height).min(frame.height()));
+                    let (x, y) = crop::smart_crop_origin(frame, width, height, *saliency).map_err(PipelineError::Frame)?;
+                    *frame = frame.crop(x, y, width, height).map_err(PipelineError::Frame)?;
+                }
+
+                Operation::NativeSeamResize { width, height, protect, remove } => {
+                    *frame = seam::seam_resize(frame, *width, *height, protect.as_ref(), remove.as_ref())
+                        .map_err(PipelineError::Frame)?;
+                }
+
+                Operation::NativeEdge { detector, mask } => {
+                    let edges = detector.apply(frame).map_err(PipelineError::Frame)?;
+                    apply_masked(frame, edges, mask, origin)?;
+                }
+
+                Operation::NativeDenoise { denoiser, mask } if at_origin => {
+                    *frame = denoiser.apply(frame, mask.as_ref()).map_err(PipelineError::Frame)?;
+                }
+
+                // Away from the origin the whole piece is processed and the mask applied after.
+                Operation::NativeDenoise { denoiser, mask } => {
+                    let result = denoiser.apply(frame, None).map_err(PipelineError::Frame)?;
+                    apply_masked(frame, result, mask, origin)?;
+                }
+
+                Operation::NativeKey { keyer, mask } => {
+                    let keyed = keyer.apply(frame).map_err(PipelineError::Frame)?;
+                    apply_masked(frame, keyed, mask, origin)?;
+                }
+
+                Operation::NativeDepth { depth } => {
+                    *frame = frame.to_depth(*depth).map_err(PipelineError::Frame)?;
+                }
+
+                Operation::NativeQuantize { quantize, mask } if at_origin => {
+                    *frame = quantize.apply(frame, mask.as_ref()).map_err(PipelineError::Frame)?;
+                }
+
+                Operation::NativeQuantize { quantize, mask } => {
+                    let result = quantize.apply(frame, None).map_err(PipelineError::Frame)?;
+                    apply_masked(frame, result, mask, origin)?;
+                }
+
+                Operation::NativeFormat { format } => {
+                    *frame = frame.to_format(*format).map_err(PipelineError::Frame)?;
+                }
+
+                Operation::NativeTransfer { linear } => {
+                    *frame = if *linear { frame.to_linear() } else { frame.to_srgb() }.map_err(PipelineError::Frame)?;
+                }
                 
             }
+            if !matches!(operation, Operation::NativeTransfer { .. }) {
+                frame.set_linear(linear);
+            }
         }
 
+        frame.set_metadata(metadata);
+        frame.set_export_depth(Some(export_depth));
         Ok(())
     }
-}
\ No newline at end of file
+
+    /// The precision a frame exported at `source` is exported at after this pipeline:
+    /// that of its last `depth` stage, if it has one.
+    pub(crate) fn export_depth(&self, source: BitDepth) -> BitDepth {
+        self.operations
+            .iter()
+            .rev()
+            .find_map(|op| match op {
+                Operation::NativeDepth { depth } => Some(*depth),
+                _ => None,
+            })
+            .unwrap_or(source)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::media::frame::PixelData;
+
+    #[test]
+    fn per_pixel_stages_average_the_chroma_of_yuv420_blocks() {
+        // Every pixel of every 2×2 block a different colour.
+        let plane = |f: fn(usize) -> usize| (0..16).map(|i| f(i) as u8).collect::<Vec<u8>>();
+        let data = PixelData::RGBA(plane(|i| i * 16), plane(|i| 255 - i * 12), plane(|i| (i % 3) * 120), vec![255; 16]);
+        let source = Frame::new(4, 4, data).and_then(|f| f.to_format(PixelFormat::YUV420)).unwrap();
+
+        // A blur gives each pixel of a block different chroma. Written one pixel at a
+        // time the block would keep only the last; it should get their average, as
+        // blurring the decoded colours and converting once does.
+        let pipeline = EffectPipeline {
+            operations: vec![Operation::Convolution { kernel: Kernel::generate_blur("blur", 3), mask: None }],
+        };
+        let mut frame = source.clone();
+        pipeline.execute(&mut frame).unwrap();
+        let mut expected = source.to_format(PixelFormat::RGBA).unwrap();
+        pipeline.execute(&mut expected).unwrap();
+        let expected = expected.to_format(PixelFormat::YUV420).unwrap();
+
+        let (PixelData::YUV420(y0, u0, v0), PixelData::YUV420(y1, u1, v1)) = (expected.data(), frame.data()) else {
+            panic!("the frame left YUV420");
+        };
+        let near = |a: &[u8], b: &[u8]| a.iter().zip(b).all(|(a, b)| a.abs_diff(*b) <= 1);
+        assert!(near(y0, y1) && near(u0, u1) && near(v0, v1), "expected {u0:?} {v0:?}, got {u1:?} {v1:?}");
+    }
+}
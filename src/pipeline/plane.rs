@@ -0,0 +1,120 @@
+// ── Plane helpers ────────────────────────────────────────────────────────────
+//
+// Native stages work on single-channel f32 planes laid out like the frame's own
+// planes: `plane[y * width + x]`. Reads outside the plane clamp to the nearest edge,
+// the same boundary rule `Kernel::apply_to_pixel` uses.
+
+/// Reads a plane value, clamping the coordinates to the plane edges.
+pub fn sample(plane: &[f32], width: usize, height: usize, x: isize, y: isize) -> f32 {
+    let cx = x.clamp(0, width as isize - 1) as usize;
+    let cy = y.clamp(0, height as isize - 1) as usize;
+    plane[cy * width + cx]
+}
+
+/// Convolves a plane with a 3x3 kernel (row-major), without any normalisation.
+pub fn convolve3(plane: &[f32], width: usize, height: usize, kernel: &[f32; 9]) -> Vec<f32> {
+    let mut out = vec![0.0; plane.len()];
+
+    for y in 0..height {
+        for x in 0..width {
+            let mut sum = 0.0;
+            for ky in 0..3 {
+                for kx in 0..3 {
+                    let v = sample(plane, width, height, x as isize + kx - 1, y as isize + ky - 1);
+                    sum += v * kernel[(ky * 3 + kx) as usize];
+                }
+            }
+            out[y * width + x] = sum;
+        }
+    }
+
+    out
+}
+
+/// Normalised 1D gaussian weights covering three sigmas on each side.
+pub fn gaussian_weights(sigma: f32) -> Vec<f32> {
+    let sigma = sigma.max(0.1);
+    let radius = (sigma * 3.0).ceil() as isize;
+    let mut weights: Vec<f32> = (-radius..=radius)
+        .map(|i| (-((i * i) as f32) / (2.0 * sigma * sigma)).exp())
+        .collect();
+    let sum: f32 = weights.iter().sum();
+    weights.iter_mut().for_each(|w| *w /= sum);
+    weights
+}
+
+/// Separable gaussian blur of a single plane.
+pub fn gaussian_blur(plane: &[f32], width: usize, height: usize, sigma: f32) -> Vec<f32> {
+    let weights = gaussian_weights(sigma);
+    let radius = (weights.len() / 2) as isize;
+
+    let mut horizontal = vec![0.0; plane.len()];
+    for y in 0..height {
+        for x in 0..width {
+            let mut sum = 0.0;
+            for (i, w) in weights.iter().enumerate() {
+                sum += w * sample(plane, width, height, x as isize + i as isize - radius, y as isize);
+            }
+            horizontal[y * width + x] = sum;
+        }
+    }
+
+    let mut out = vec![0.0; plane.len()];
+    for y in 0..height {
+        for x in 0..width {
+            let mut sum = 0.0;
+            for (i, w) in weights.iter().enumerate() {
+                sum += w * sample(&horizontal, width, height, x as isize, y as isize + i as isize - radius);
+            }
+            out[y * width + x] = sum;
+        }
+    }
+
+    out
+}
+
+/// Mean over the (2r+1)x(2r+1) window around every pixel, computed from an integral
+/// image. Windows are cut at the plane edges and averaged over the pixels they cover.
+pub fn box_mean(plane: &[f32], width: usize, height: usize, radius: usize) -> Vec<f32> {
+    let stride = width + 1;
+    let mut integral = vec![0.0f64; stride * (height + 1)];
+    for y in 0..height {
+        let mut row_sum = 0.0f64;
+        for x in 0..width {
+            row_sum += plane[y * width + x] as f64;
+            integral[(y + 1) * stride + x + 1] = integral[y * stride + x + 1] + row_sum;
+        }
+    }
+
+    let mut out = vec![0.0; plane.len()];
+    for y in 0..height {
+        let y0 = y.saturating_sub(radius);
+        let y1 = (y + radius + 1).min(height);
+        for x in 0..width {
+            let x0 = x.saturating_sub(radius);
+            let x1 = (x + radius + 1).min(width);
+            let sum = integral[y1 * stride + x1] - integral[y0 * stride + x1] - integral[y1 * stride + x0]
+                + integral[y0 * stride + x0];
+            out[y * width + x] = (sum / ((y1 - y0) * (x1 - x0)) as f64) as f32;
+        }
+    }
+    out
+}
+
+/// Evaluates `f(x, y)` for every pixel, one row per rayon task, and returns the
+/// results in plane order.
+pub fn par_pixels<T, F>(width: usize, height: usize, f: F) -> Vec<T>
+where
+    T: Send + Default + Clone,
+    F: Fn(usize, usize) -> T + Sync,
+{
+    use rayon::prelude::*;
+
+    let mut out = vec![T::default(); width * height];
+    out.par_chunks_mut(width.max(1)).enumerate().for_each(|(y, row)| {
+        for (x, value) in row.iter_mut().enumerate() {
+            *value = f(x, y);
+        }
+    });
+    out
+}
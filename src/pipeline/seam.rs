@@ -0,0 +1,224 @@
+use crate::media::frame::{Frame, FrameError};
+use crate::pipeline::edge::{Gradient, GradientOperator};
+
+// ── Seam carving ─────────────────────────────────────────────────────────────
+//
+// A seam is a path of one pixel per row, each within one column of the last, that
+// crosses the least energy: the Sobel magnitude of the luma, as the edge detectors
+// use. Narrowing removes the cheapest seam until the width fits; widening finds the
+// cheapest seams the same way on a copy and doubles each of them, in batches of at
+// most half the width so one seam is not stretched again and again. Heights are
+// changed by carving the transposed image.
+
+/// Energy added under protected pixels and taken away under pixels to remove; far
+/// above any gradient, so seams go around or through them whenever they can.
+const GUIDE_ENERGY: f32 = 1000.0;
+
+/// Resizes `frame` to `width` x `height` by removing or doubling low-energy seams.
+/// Pixels marked in `protect` are kept while there is anything else to remove, and
+/// pixels marked in `remove` go first. A guide frame marks the pixels where it is
+/// opaque and bright (alpha times luma above one half) and must match the frame's size.
+pub fn seam_resize(
+    frame: &Frame,
+    width: u32,
+    height: u32,
+    protect: Option<&Frame>,
+    remove: Option<&Frame>,
+) -> Result<Frame, FrameError> {
+    let (w, h) = (frame.width() as usize, frame.height() as usize);
+    if w == 0 || h == 0 || width == 0 || height == 0 {
+        return Err(FrameError::EmptyFrame);
+    }
+    let mut bias = vec![0.0f32; w * h];
+    for (guide, sign) in [(protect, 1.0), (remove, -1.0)] {
+        let Some(guide) = guide else { continue };
+        if (guide.width(), guide.height()) != (frame.width(), frame.height()) {
+            return Err(FrameError::SizeMismatch((guide.width(), guide.height()), (frame.width(), frame.height())));
+        }
+        let [_, _, _, a] = guide.rgba_planes()?;
+        for ((bias, luma), a) in bias.iter_mut().zip(guide.luma_plane()?).zip(a) {
+            if a * luma > 0.5 {
+                *bias += sign * GUIDE_ENERGY;
+            }
+        }
+    }
+
+    let [r, g, b, a] = frame.rgba_planes()?;
+    let luma = frame.luma_plane()?;
+    let mut carving = Carving { width: w, height: h, planes: vec![r, g, b, a, bias, luma], energy: None };
+    carving.fit_width(width as usize);
+    carving.transpose();
+    carving.fit_width(height as usize);
+    carving.transpose();
+
+    let mut planes = carving.planes.into_iter();
+    let mut next = || planes.next().unwrap_or_default();
+    frame.blank_like(width, height)?.with_planes([next(), next(), next(), next()])
+}
+
+/// Row-major planes being carved: red, green, blue, alpha, the guide bias and luma,
+/// plus the source column of every pixel while seams are being chosen for widening.
+/// The energy map is kept between seams and only redone next to the one removed.
+#[derive(Clone)]
+struct Carving {
+    width: usize,
+    height: usize,
+    planes: Vec<Vec<f32>>,
+    energy: Option<Vec<f32>>,
+}
+
+const BIAS: usize = 4;
+const LUMA: usize = 5;
+
+impl Carving {
+    fn fit_width(&mut self, target: usize) {
+        while self.width > target {
+            let seam = self.cheapest_seam();
+            self.remove_seam(&seam);
+        }
+        while self.width < target {
+            let batch = (target - self.width).min(self.width.div_ceil(2));
+            self.widen(batch);
+        }
+    }
+
+    /// Gradient magnitude of the luma plus the guide bias.
+    fn energy(&mut self) -> &[f32] {
+        let (w, h, planes) = (self.width, self.height, &self.planes);
+        self.energy.get_or_insert_with(|| {
+            let mut energy = Gradient::compute(&planes[LUMA], w, h, GradientOperator::Sobel).magnitude();
+            for (e, bias) in energy.iter_mut().zip(&planes[BIAS]) {
+                *e += bias;
+            }
+            energy
+        })
+    }
+
+    /// The column of the cheapest seam in each row, found by dynamic programming.
+    fn cheapest_seam(&mut self) -> Vec<usize> {
+        let (w, h) = (self.width, self.height);
+        let mut cost = self.energy().to_vec();
+        for y in 1..h {
+            for x in 0..w {
+                let above = &cost[(y - 1) * w..y * w];
+                let best = above[x.saturating_sub(1)..(x + 2).min(w)].iter().copied().fold(f32::INFINITY, f32::min);
+                cost[y * w + x] += best;
+            }
+        }
+
+        let mut seam = vec![0; h];
+        let last = &cost[(h - 1) * w..];
+        seam[h - 1] = (0..w).fold(0, |best, x| if last[x] < last[best] { x } else { best });
+        for y in (0..h - 1).rev() {
+            let below = seam[y + 1];
+            let row = &cost[y * w..(y + 1) * w];
+            seam[y] = (below.saturating_sub(1)..(below + 2).min(w)).fold(below, |best, x| if row[x] < row[best] { x } else { best });
+        }
+        seam
+    }
+
+    fn remove_seam(&mut self, seam: &[usize]) {
+        let (w, h) = (self.width, self.height);
+        for plane in self.planes.iter_mut().chain(self.energy.as_mut()) {
+            let mut out = Vec::with_capacity((w - 1) * h);
+            for (y, &skip) in seam.iter().enumerate() {
+                out.extend_from_slice(&plane[y * w..y * w + skip]);
+                out.extend_from_slice(&plane[y * w + skip + 1..(y + 1) * w]);
+            }
+            *plane = out;
+        }
+        self.width -= 1;
+
+        // Only pixels whose 3x3 window held a seam pixel have new neighbours.
+        let (w, luma, bias) = (self.width, &self.planes[LUMA], &self.planes[BIAS]);
+        if let Some(energy) = self.energy.as_mut() {
+            for y in 0..h {
+                let near = &seam[y.saturating_sub(1)..(y + 2).min(h)];
+                let from = near.iter().min().map_or(0, |x| x.saturating_sub(2));
+                let to = near.iter().max().map_or(0, |x| (x + 2).min(w));
+                for x in from..to {
+                    energy[y * w + x] = GradientOperator::Sobel.magnitude_at(luma, w, h, x, y) + bias[y * w + x];
+                }
+            }
+        }
+    }
+
+    /// Adds `count` columns by doubling the `count` seams narrowing would remove first.
+    /// Each new pixel is the mean of the seam pixel and its right-hand neighbour.
+    fn widen(&mut self, count: usize) {
+        let (w, h) = (self.width, self.height);
+        let mut trial = self.clone();
+        // Pixels to remove only steer narrowing; widening should not copy them.
+        trial.planes[BIAS].iter_mut().for_each(|bias| *bias = bias.max(0.0));
+        trial.planes.push((0..w * h).map(|i| (i % w) as f32).collect());
+
+        let mut doubled = vec![0u32; w * h];
+        for _ in 0..count {
+            let seam = trial.cheapest_seam();
+            let columns = &trial.planes[trial.planes.len() - 1];
+            for (y, &x) in seam.iter().enumerate() {
+                doubled[y * w + columns[y * trial.width + x] as usize] += 1;
+            }
+            trial.remove_seam(&seam);
+        }
+
+        for plane in &mut self.planes {
+            let mut out = Vec::with_capacity((w + count) * h);
+            for y in 0..h {
+                let row = &plane[y * w..(y + 1) * w];
+                for x in 0..w {
+                    out.push(row[x]);
+                    let mean = (row[x] + row[(x + 1).min(w - 1)]) / 2.0;
+                    out.extend(std::iter::repeat_n(mean, doubled[y * w + x] as usize));
+                }
+            }
+            *plane = out;
+        }
+        self.width += count;
+        self.energy = None;
+    }
+
+    fn transpose(&mut self) {
+        let (w, h) = (self.width, self.height);
+        for plane in &mut self.planes {
+            *plane = (0..w * h).map(|i| plane[(i % h) * w + i / h]).collect();
+        }
+        (self.width, self.height) = (h, w);
+        self.energy = None;
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::media::frame::{Color, Pos};
+    use crate::media::generate;
+
+    fn run(frame: &Frame, y: u32, color: Color) -> u32 {
+        (0..frame.width()).filter(|&x| frame.get_pixel(&Pos(x, y)).unwrap().channels_f32() == color.channels_f32()).count() as u32
+    }
+
+    #[test]
+    fn seams_go_around_objects_and_through_marked_pixels() {
+        let red = Color::RGBA(220, 30, 30, 255);
+        let blue = Color::RGBA(30, 30, 220, 255);
+        let mut frame = generate::solid(60, 20, Color::RGBA(128, 128, 128, 255)).unwrap();
+        frame.blit(&Pos(10, 5), &generate::solid(10, 10, red).unwrap()).unwrap();
+        frame.blit(&Pos(40, 5), &generate::solid(10, 10, blue).unwrap()).unwrap();
+
+        let narrow = seam_resize(&frame, 40, 16, None, None).unwrap();
+        assert_eq!((narrow.width(), narrow.height()), (40, 16));
+        assert_eq!((run(&narrow, 8, red), run(&narrow, 8, blue)), (10, 10));
+
+        let wide = seam_resize(&frame, 100, 20, None, None).unwrap();
+        assert_eq!((run(&wide, 10, red), run(&wide, 10, blue)), (10, 10));
+
+        let mut logo = generate::solid(60, 20, Color::RGBA(0, 0, 0, 255)).unwrap();
+        logo.blit(&Pos(40, 0), &generate::solid(10, 20, Color::RGBA(255, 255, 255, 255)).unwrap()).unwrap();
+        let cut = seam_resize(&frame, 50, 20, None, Some(&logo)).unwrap();
+        assert_eq!((run(&cut, 10, red), run(&cut, 10, blue)), (10, 0));
+        // Narrower than the background allows: the protected square is the one kept.
+        let kept = seam_resize(&frame, 15, 20, Some(&logo), None).unwrap();
+        assert_eq!(run(&kept, 10, blue), 10);
+    }
+}
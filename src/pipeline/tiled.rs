@@ -0,0 +1,294 @@
+use crate::media::frame::{Frame, FrameError, PixelFormat, Pos};
+use crate::pipeline::denoise::Denoiser;
+use crate::pipeline::edge::EdgeDetector;
+use crate::pipeline::palette::{Dither, QuantizeTarget};
+use crate::pipeline::pipeline::{EffectPipeline, Operation, PipelineError};
+
+// ── Tiled execution ──────────────────────────────────────────────────────────
+//
+// Large images go through the pipeline one tile at a time. Each tile is cut with a
+// halo of extra pixels on every side, wide enough to hold everything its core reads,
+// so the core comes out exactly as it would from the whole frame and the halo is
+// thrown away. Only operations that read a bounded neighbourhood can run like this:
+// resizes, crops, adaptive palettes, error diffusion, Canny and Harris need the
+// whole frame.
+//
+// Streamed execution reads the source top to bottom a band of rows at a time, so
+// only one band (with its halo rows) and one tile are held at once: memory grows
+// with the width and the tile size, never with the height.
+
+/// Tile edge used when none is given, in pixels.
+pub const DEFAULT_TILE: u32 = 512;
+
+impl Operation {
+    /// How far from a pixel this operation reads, or `None` when it needs the whole frame.
+    pub fn halo(&self) -> Option<u32> {
+        match self {
+            Operation::PointFilter { .. }
+            | Operation::Blend { .. }
+            | Operation::NativeDraw { .. }
+            | Operation::NativeDepth { .. }
+            | Operation::NativeTransfer { .. } => Some(0),
+            Operation::Convolution { kernel, .. } => Some((kernel.size / 2) as u32),
+            Operation::NativeEdge { detector, .. } => match detector {
+                EdgeDetector::Sobel | EdgeDetector::Scharr | EdgeDetector::Laplacian => Some(1),
+                EdgeDetector::Canny { .. } | EdgeDetector::Harris { .. } => None,
+            },
+            Operation::NativeDenoise { denoiser, .. } => {
+                let radius = match denoiser {
+                    Denoiser::Bilateral { radius, .. } => *radius,
+                    Denoiser::NonLocalMeans { patch, search, .. } => patch / 2 + search / 2,
+                    // Box means of box means.
+                    Denoiser::Guided { radius, .. } => 2 * radius,
+                };
+                Some(radius as u32)
+            }
+            Operation::NativeKey { keyer, .. } => Some(keyer.radius()),
+            Operation::NativeQuantize { quantize, .. } => match (&quantize.target, quantize.dither) {
+                (QuantizeTarget::Adaptive { .. }, _) => None,
+                (_, Dither::FloydSteinberg | Dither::Atkinson) => None,
+                _ => Some(0),
+            },
+            Operation::NativeFormat { format } => (*format != PixelFormat::YUV420).then_some(0),
+            Operation::NativeResize { .. }
+            | Operation::NativeCrop { .. }
+            | Operation::NativeTrim { .. }
+            | Operation::NativeSmartCrop { .. }
+            | Operation::NativeSeamResize { .. } => None,
+        }
+    }
+
+    /// Tiles must start on multiples of this for the result to line up: ordered
+    /// dithers repeat a threshold map from the tile's corner.
+    fn alignment(&self) -> u32 {
+        match self {
+            Operation::NativeQuantize { quantize, .. } => match quantize.dither {
+                Dither::Bayer => 8,
+                Dither::BlueNoise => 64,
+                _ => 1,
+            },
+            _ => 1,
+        }
+    }
+}
+
+/// Image rows read top to bottom, for `EffectPipeline::execute_streamed`.
+pub trait RowSource {
+    fn width(&self) -> u32;
+    fn height(&self) -> u32;
+    /// The next `rows` rows, as a frame `width` wide.
+    fn read_rows(&mut self, rows: u32) -> Result<Frame, PipelineError>;
+}
+
+/// Receives streamed output a band of full-width rows at a time, top to bottom.
+pub trait RowSink {
+    fn write_rows(&mut self, rows: &Frame) -> Result<(), PipelineError>;
+}
+
+/// Reads the rows of a frame already in memory.
+pub struct FrameRows<'a> {
+    frame: &'a Frame,
+    next: u32,
+}
+
+impl<'a> FrameRows<'a> {
+    pub fn new(frame: &'a Frame) -> Self {
+        FrameRows { frame, next: 0 }
+    }
+}
+
+impl RowSource for FrameRows<'_> {
+    fn width(&self) -> u32 {
+        self.frame.width()
+    }
+
+    fn height(&self) -> u32 {
+        self.frame.height()
+    }
+
+    fn read_rows(&mut self, rows: u32) -> Result<Frame, PipelineError> {
+        let rows = self.frame
+            .crop(0, self.next, self.frame.width(), rows)
+            .map_err(PipelineError::Frame)?;
+        self.next += rows.height();
+        Ok(rows)
+    }
+}
+
+/// Assembles streamed rows into one frame of the given height.
+pub struct FrameSink {
+    height: u32,
+    next: u32,
+    frame: Option<Frame>,
+}
+
+impl FrameSink {
+    pub fn new(height: u32) -> Self {
+        FrameSink { height, next: 0, frame: None }
+    }
+
+    /// The assembled frame; an error if no rows were written.
+    pub fn into_frame(self) -> Result<Frame, PipelineError> {
+        self.frame.ok_or(PipelineError::Frame(FrameError::EmptyFrame))
+    }
+}
+
+impl RowSink for FrameSink {
+    fn write_rows(&mut self, rows: &Frame) -> Result<(), PipelineError> {
+        if self.frame.is_none() {
+            self.frame = Some(rows.blank_like(rows.width(), self.height).map_err(PipelineError::Frame)?);
+        }
+        if let Some(frame) = self.frame.as_mut() {
+            frame.blit(&Pos(0, self.next), rows).map_err(PipelineError::Frame)?;
+        }
+        self.next += rows.height();
+        Ok(())
+    }
+}
+
+impl EffectPipeline {
+    /// Pixels a tile needs beyond its core on every side, or `None` when an operation
+    /// needs the whole frame. Each operation widens what the ones before it must cover.
+    pub fn halo(&self) -> Option<u32> {
+        self.operations.iter().map(Operation::halo).sum()
+    }
+
+    /// Whether the pipeline can run tile by tile.
+    pub fn is_tileable(&self) -> bool {
+        self.halo().is_some()
+    }
+
+    fn alignment(&self) -> u32 {
+        self.operations.iter().map(Operation::alignment).max().unwrap_or(1)
+    }
+
+    /// `execute` one `tile`-sized piece at a time, giving the same result without
+    /// copying the whole frame at every stage. `NotFeasible` when `is_tileable` is false.
+    pub fn execute_tiled(&self, frame: &Frame, tile: u32) -> Result<Frame, PipelineError> {
+        let mut sink = FrameSink::new(frame.height());
+        self.execute_streamed(&mut FrameRows::new(frame), &mut sink, tile)?;
+        let mut result = sink.into_frame()?;
+        result.set_metadata(frame.metadata().cloned());
+        result.set_export_depth(Some(self.export_depth(frame.export_depth())));
+        Ok(result)
+    }
+
+    /// Runs the pipeline from `source` to `sink` a band of `tile` rows at a time, cut
+    /// into `tile`-wide pieces, so neither image is ever whole in memory.
+    /// `NotFeasible` when `is_tileable` is false.
+    pub fn execute_streamed(&self, source: &mut dyn RowSource, sink: &mut dyn RowSink, tile: u32) -> Result<(), PipelineError> {
+        let halo = self.halo().ok_or(PipelineError::NotFeasible)?;
+        let align = self.alignment();
+        let tile = tile.max(1).div_ceil(align) * align;
+        let (width, height) = (source.width(), source.height());
+
+        // `band` holds source rows `read - band.height() .. read`.
+        let mut band: Option<Frame> = None;
+        let mut read = 0;
+        for y0 in (0..height).step_by(tile as usize) {
+            let y1 = (y0 + tile).min(height);
+            let top = y0.saturating_sub(halo) / align * align;
+            let bottom = (y1 + halo).min(height);
+
+            // Drop the rows above `top` and read down to `bottom`.
+            let kept = match band.take() {
+                Some(old) if read > top => {
+                    let skip = top - (read - old.height());
+                    Some(old.crop(0, skip, width, old.height() - skip).map_err(PipelineError::Frame)?)
+                }
+                _ => None,
+            };
+            let fresh = if bottom > read { Some(source.read_rows(bottom - read)?) } else { None };
+            read = bottom;
+            let current = match (kept, fresh) {
+                (Some(kept), Some(fresh)) => {
+                    let mut joined = kept.blank_like(width, kept.height() + fresh.height()).map_err(PipelineError::Frame)?;
+                    joined.blit(&Pos(0, 0), &kept).map_err(PipelineError::Frame)?;
+                    joined.blit(&Pos(0, kept.height()), &fresh).map_err(PipelineError::Frame)?;
+                    joined
+                }
+                (Some(rows), None) | (None, Some(rows)) => rows,
+                (None, None) => return Err(PipelineError::InvalidData),
+            };
+
+            let mut out: Option<Frame> = None;
+            for x0 in (0..width).step_by(tile as usize) {
+                let x1 = (x0 + tile).min(width);
+                let left = x0.saturating_sub(halo) / align * align;
+                let right = (x1 + halo).min(width);
+
+                let mut piece = current
+                    .crop(left, top - (read - current.height()), right - left, bottom - top)
+                    .map_err(PipelineError::Frame)?;
+                self.execute_at(&mut piece, Pos(left, top), (width, height))?;
+                let core = piece
+                    .crop(x0 - left, y0 - top, x1 - x0, y1 - y0)
+                    .map_err(PipelineError::Frame)?;
+
+                if out.is_none() {
+                    out = Some(core.blank_like(width, y1 - y0).map_err(PipelineError::Frame)?);
+                }
+                if let Some(out) = out.as_mut() {
+                    out.blit(&Pos(x0, 0), &core).map_err(PipelineError::Frame)?;
+                }
+            }
+            if let Some(out) = out {
+                sink.write_rows(&out)?;
+            }
+            band = Some(current);
+        }
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::media::compare::{self, Tolerance};
+    use crate::media::frame::{BlendMode, Color};
+    use crate::media::generate::{self, Noise, NoiseKind};
+    use crate::pipeline::draw::{Drawing, Shape};
+    use crate::pipeline::kernel::Kernel;
+    use crate::pipeline::palette::Quantize;
+    use crate::pipeline::pipeline::Pipeline;
+    use crate::range::{Circle, Mask};
+
+    #[test]
+    fn tiles_match_the_whole_frame() {
+        let source = Noise { kind: NoiseKind::Perlin, scale: 2.0, seed: 3, octaves: 2 }.render(97, 61).unwrap();
+        let overlay = generate::solid(40, 30, Color::RGBA(255, 0, 0, 128)).unwrap();
+        let pipeline = EffectPipeline {
+            operations: vec![
+                Operation::Convolution { kernel: Kernel::generate_blur("blur", 5), mask: None },
+                Operation::NativeEdge { detector: EdgeDetector::Sobel, mask: None },
+                Operation::NativeDenoise {
+                    denoiser: Denoiser::Guided { radius: 2, eps: 0.01 },
+                    mask: Some(Mask::Circle(Circle { cx: 50, cy: 30, radius: 20 })),
+                },
+                Operation::Blend { x: 30, y: 20, frame2: overlay, alpha: 1.0, mode: BlendMode::Multiply },
+                Operation::NativeDraw {
+                    drawing: Drawing {
+                        shape: Shape::Circle { cx: 48.0, cy: 30.0, radius: 17.5 },
+                        fill: None,
+                        stroke: Some(Color::RGBA(0, 0, 255, 255)),
+                        stroke_width: 3.0,
+                        mode: BlendMode::Normal,
+                    },
+                },
+                Operation::NativeQuantize {
+                    quantize: Quantize { target: QuantizeTarget::Levels(32), dither: Dither::Bayer },
+                    mask: None,
+                },
+            ],
+        };
+        assert_eq!(pipeline.halo(), Some(7));
+
+        let mut whole = source.clone();
+        pipeline.execute(&mut whole).unwrap();
+        let tiled = pipeline.execute_tiled(&source, 16).unwrap();
+        compare::matches(&tiled, &whole, &Tolerance::max_diff(0.01)).unwrap();
+
+        let resize = EffectPipeline { operations: vec![Operation::NativeResize { width: 10, height: 10 }] };
+        assert!(matches!(resize.execute_tiled(&source, 16), Err(PipelineError::NotFeasible)));
+    }
+}
@@ -1,12 +1,83 @@
+use std::fmt;
+use std::rc::Rc;
+
 use crate::media::frame::{Color, Frame, FrameError, PixelData, Pos};
+use crate::pipeline::plane::gaussian_blur;
 use fontdue::{self, Font};
 
+/// Horizontal alignment of the lines of a text block.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Align {
+    Left,
+    Center,
+    Right,
+}
+
+impl Align {
+    pub fn from_name(name: &str) -> Option<Align> {
+        match name.to_lowercase().as_str() {
+            "left" => Some(Align::Left),
+            "center" | "centre" => Some(Align::Center),
+            "right" => Some(Align::Right),
+            _ => None,
+        }
+    }
+}
+
+/// Stroke drawn around the glyphs, underneath the fill.
+#[derive(Debug, Clone, Copy)]
+pub struct Outline {
+    pub width: f32,
+    pub color: Color,
+}
+
+/// Offset, optionally blurred copy of the text (fill and outline) drawn underneath it.
+#[derive(Debug, Clone, Copy)]
+pub struct Shadow {
+    pub dx: f32,
+    pub dy: f32,
+    /// Gaussian sigma in pixels; 0 gives a hard shadow.
+    pub blur: f32,
+    pub color: Color,
+}
+
+/// How `picturize` lays out and decorates the string.
+#[derive(Debug, Clone, Copy)]
+pub struct TextLayout {
+    /// Wrap width in pixels. Lines break between words, and inside a word that is
+    /// wider than the whole line. With a width set, lines align within it.
+    pub max_width: Option<f32>,
+    /// Multiplier on the font's line height.
+    pub line_spacing: f32,
+    pub align: Align,
+    /// Apply the font's kerning pairs between neighbouring glyphs.
+    pub kerning: bool,
+    pub outline: Option<Outline>,
+    pub shadow: Option<Shadow>,
+}
+
+impl Default for TextLayout {
+    fn default() -> Self {
+        Self {
+            max_width: None,
+            line_spacing: 1.0,
+            align: Align::Left,
+            kerning: true,
+            outline: None,
+            shadow: None,
+        }
+    }
+}
+
 pub struct Text {
     data: String,
-    font: Font,
+    font: Rc<Font>,
+    /// Tried in order for characters the primary font has no glyph for.
+    fallbacks: Vec<Rc<Font>>,
     size: f32,
     pos: Pos,
     color: Color,
+    layout: TextLayout,
 }
 
 #[derive(Debug)]
@@ -17,18 +88,45 @@ pub enum TextError {
     EmptyText,
 }
 
+impl fmt::Display for TextError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            TextError::FontNotFound => write!(f, "The font could not be found!"),
+            TextError::FrameCreationFailed(e) => write!(f, "Could not build the text frame: {e}"),
+            TextError::InvalidDimensions => write!(f, "The text has no size! Check the font size."),
+            TextError::EmptyText => write!(f, "The text is empty!"),
+        }
+    }
+}
+
+impl std::error::Error for TextError {}
+
+/// One rasterized glyph and the top-left corner it is drawn at, in layout space.
+struct PlacedGlyph {
+    x: f32,
+    y: f32,
+    width: usize,
+    height: usize,
+    bitmap: Vec<u8>,
+}
+
 impl Text {
-    pub fn new(data: &str, font: Font, size: f32, pos: Pos, color: Color) -> Self {
+    pub fn new(data: &str, font: impl Into<Rc<Font>>, size: f32, pos: Pos, color: Color) -> Self {
         Self {
             data: data.to_string(),
-            font,
+            font: font.into(),
+            fallbacks: Vec::new(),
             size,
             pos,
             color,
+            layout: TextLayout::default(),
         }
     }
-    pub fn set_font(&mut self, font: Font) {
-        self.font = font;
+    pub fn set_font(&mut self, font: impl Into<Rc<Font>>) {
+        self.font = font.into();
+    }
+    pub fn set_fallbacks(&mut self, fonts: Vec<Rc<Font>>) {
+        self.fallbacks = fonts;
     }
     pub fn set_position(&mut self, pos: &Pos) {
         self.pos = *pos;
@@ -42,105 +140,372 @@ impl Text {
     pub fn set_text(&mut self, content: &str) {
         self.data = content.to_string();
     }
+    pub fn set_layout(&mut self, layout: TextLayout) {
+        self.layout = layout;
+    }
+
+    /// Index of the font that draws `ch`: 0 is the primary font, then the fallbacks.
+    /// A character no font covers falls back to the primary font's missing-glyph box.
+    fn font_index(&self, ch: char) -> usize {
+        if ch.is_whitespace() || self.font.lookup_glyph_index(ch) != 0 {
+            return 0;
+        }
+        self.fallbacks
+            .iter()
+            .position(|font| font.lookup_glyph_index(ch) != 0)
+            .map_or(0, |i| i + 1)
+    }
+
+    fn font_at(&self, index: usize) -> &Font {
+        if index == 0 { &self.font } else { &self.fallbacks[index - 1] }
+    }
+
+    /// Advance width of one line, kerning included.
+    fn line_width(&self, line: &str) -> f32 {
+        let mut width = 0.0;
+        let mut prev = None;
+        for ch in line.chars() {
+            let index = self.font_index(ch);
+            width += self.kern(prev, ch, index) + self.font_at(index).metrics(ch, self.size).advance_width;
+            prev = Some((ch, index));
+        }
+        width
+    }
+
+    /// Kerning between `prev` and `ch`; pairs drawn from different fonts are not kerned.
+    fn kern(&self, prev: Option<(char, usize)>, ch: char, index: usize) -> f32 {
+        match prev {
+            Some((left, prev_index)) if self.layout.kerning && prev_index == index => {
+                self.font_at(index).horizontal_kern(left, ch, self.size).unwrap_or(0.0)
+            }
+            _ => 0.0,
+        }
+    }
+
+    /// Splits the string on newlines, then wraps each paragraph to `max_width`.
+    fn wrap_lines(&self) -> Vec<String> {
+        let mut lines = Vec::new();
+        for paragraph in self.data.split('\n').map(|p| p.trim_end_matches('\r')) {
+            let Some(max) = self.layout.max_width else {
+                lines.push(paragraph.to_string());
+                continue;
+            };
+
+            let mut line = String::new();
+            for word in paragraph.split(' ') {
+                let candidate = if line.is_empty() { word.to_string() } else { format!("{line} {word}") };
+                if line.is_empty() || self.line_width(&candidate) <= max {
+                    line = candidate;
+                } else {
+                    lines.push(std::mem::replace(&mut line, word.to_string()));
+                }
+
+                // A word wider than the line is broken at the last character that fits.
+                while line.chars().count() > 1 && self.line_width(&line) > max {
+                    let fits = (1..line.chars().count())
+                        .rev()
+                        .find(|&n| self.line_width(&line.chars().take(n).collect::<String>()) <= max)
+                        .unwrap_or(1);
+                    let head: String = line.chars().take(fits).collect();
+                    let tail: String = line.chars().skip(fits).collect();
+                    lines.push(head);
+                    line = tail;
+                }
+            }
+            lines.push(line);
+        }
+        lines
+    }
 
     pub fn picturize(&self) -> Result<Frame, TextError> {
         let font = &self.font;
-        let data = &self.data;
         let size = self.size;
-        let color = self.color;
+        let layout = &self.layout;
 
-        if data.is_empty() {
+        if self.data.is_empty() {
             return Err(TextError::EmptyText);
         }
 
-        // ── Measure pass ─────────────────────────────────────────────────────
-        let mut pen_x = 0.0f32;
-        let mut min_x = f32::MAX;
-        let mut max_x = f32::MIN;
-        let mut max_top = f32::MIN;
-        let mut min_bottom = f32::MAX;
+        // ── Layout pass ──────────────────────────────────────────────────────
+        let (ascent, descent, line_height) = match font.horizontal_line_metrics(size) {
+            Some(m) => (m.ascent, m.descent, m.new_line_size),
+            None => (size * 0.8, -size * 0.2, size * 1.2),
+        };
+        let line_height = line_height * layout.line_spacing.max(0.0);
+
+        let lines = self.wrap_lines();
+        let widths: Vec<f32> = lines.iter().map(|l| self.line_width(l)).collect();
+        let block_w = widths.iter().copied().fold(layout.max_width.unwrap_or(0.0), f32::max);
+        let block_h = (lines.len() - 1) as f32 * line_height + ascent - descent;
 
-        for ch in data.chars() {
-            let (metrics, _) = font.rasterize(ch, size);
-            let gx_min = pen_x + metrics.xmin as f32;
-            let gx_max = gx_min + metrics.width as f32;
-            min_x = min_x.min(gx_min);
-            max_x = max_x.max(gx_max);
-            max_top = max_top.max(metrics.ymin as f32 + metrics.height as f32);
-            min_bottom = min_bottom.min(metrics.ymin as f32);
-            pen_x += metrics.advance_width;
+        let mut glyphs = Vec::new();
+        for (i, line) in lines.iter().enumerate() {
+            let mut pen_x = match layout.align {
+                Align::Left => 0.0,
+                Align::Center => (block_w - widths[i]) / 2.0,
+                Align::Right => block_w - widths[i],
+            };
+            let baseline = ascent + i as f32 * line_height;
+            let mut prev = None;
+            for ch in line.chars() {
+                let index = self.font_index(ch);
+                pen_x += self.kern(prev, ch, index);
+                let (metrics, bitmap) = self.font_at(index).rasterize(ch, size);
+                if metrics.width > 0 && metrics.height > 0 {
+                    glyphs.push(PlacedGlyph {
+                        x: (pen_x + metrics.xmin as f32).round(),
+                        y: (baseline - (metrics.ymin as f32 + metrics.height as f32)).round(),
+                        width: metrics.width,
+                        height: metrics.height,
+                        bitmap,
+                    });
+                }
+                pen_x += metrics.advance_width;
+                prev = Some((ch, index));
+            }
         }
 
-        let width = (max_x - min_x).ceil() as u32;
-        let height = (max_top - min_bottom).ceil() as u32;
-        let baseline = max_top;
+        // The frame covers the line boxes and any ink hanging outside them, plus room
+        // for the outline and the shadow.
+        let (mut left, mut top, mut right, mut bottom) = (0.0f32, 0.0f32, block_w, block_h);
+        for g in &glyphs {
+            left = left.min(g.x);
+            top = top.min(g.y);
+            right = right.max(g.x + g.width as f32);
+            bottom = bottom.max(g.y + g.height as f32);
+        }
+        let stroke = layout.outline.map_or(0.0, |o| o.width.max(0.0));
+        let reach = |offset: f32| layout.shadow.map_or(0.0, |s| (3.0 * s.blur.max(0.0) + offset).max(0.0));
+        let (dx, dy) = layout.shadow.map_or((0.0, 0.0), |s| (s.dx, s.dy));
+        let pad_left = (stroke + reach(-dx)).ceil();
+        let pad_top = (stroke + reach(-dy)).ceil();
+        let width = ((right - left).ceil() + pad_left + (stroke + reach(dx)).ceil()) as usize;
+        let height = ((bottom - top).ceil() + pad_top + (stroke + reach(dy)).ceil()) as usize;
 
-        if width == 0 || height == 0 {
+        if width == 0 || height == 0 || block_w <= 0.0 {
             return Err(TextError::InvalidDimensions);
         }
 
-        // ── Allocate channel buffers ──────────────────────────────────────────
-        let pixel_count = width as usize * height as usize;
-        let mut r_buf = vec![0u8; pixel_count];
-        let mut g_buf = vec![0u8; pixel_count];
-        let mut b_buf = vec![0u8; pixel_count];
-        let mut a_buf = vec![0u8; pixel_count]; // only used for RGBA
-
         // ── Rasterize pass ───────────────────────────────────────────────────
-        let mut pen_x = 0.0f32;
-
-        for ch in data.chars() {
-            let (metrics, bitmap) = font.rasterize(ch, size);
-            let glyph_w = metrics.width as u32;
-            let glyph_h = metrics.height as u32;
-            let x_off = (pen_x + metrics.xmin as f32 - min_x).round() as i32;
-            let y_off = (baseline - (metrics.ymin as f32 + metrics.height as f32)).round() as i32;
-
-            for row in 0..glyph_h {
-                for col in 0..glyph_w {
-                    let coverage = bitmap[(row * glyph_w + col) as usize];
-                    if coverage == 0 {
-                        continue;
-                    }
-
-                    let dx = x_off + col as i32;
-                    let dy = y_off + row as i32;
-                    if dx < 0 || dy < 0 || dx >= width as i32 || dy >= height as i32 {
-                        continue;
-                    }
-
-                    let idx = dy as usize * width as usize + dx as usize;
-                    let alpha = coverage as f32 / 255.0;
-
-                    match color {
-                        Color::Gray(_) => {
-                            r_buf[idx] = r_buf[idx].max(coverage);
-                        }
-                        Color::RGB(_, _, _) => {
-                            r_buf[idx] = (color.r() as f32 * alpha) as u8;
-                            g_buf[idx] = (color.g() as f32 * alpha) as u8;
-                            b_buf[idx] = (color.b() as f32 * alpha) as u8;
-                        }
-                        Color::RGBA(_, _, _, _) => {
-                            r_buf[idx] = (color.r() as f32 * alpha) as u8;
-                            g_buf[idx] = (color.g() as f32 * alpha) as u8;
-                            b_buf[idx] = (color.b() as f32 * alpha) as u8;
-                            a_buf[idx] = coverage;
-                        }
-                    }
+        let mut fill = vec![0.0f32; width * height];
+        for g in &glyphs {
+            let x0 = (g.x - left + pad_left) as usize;
+            let y0 = (g.y - top + pad_top) as usize;
+            for row in 0..g.height {
+                for col in 0..g.width {
+                    let idx = (y0 + row) * width + x0 + col;
+                    fill[idx] = fill[idx].max(g.bitmap[row * g.width + col] as f32 / 255.0);
                 }
             }
+        }
 
-            pen_x += metrics.advance_width;
+        let silhouette = match layout.outline {
+            Some(outline) if outline.width > 0.0 => dilate(&fill, width, height, outline.width),
+            _ => fill.clone(),
+        };
+
+        // ── Composite: shadow, outline, fill ─────────────────────────────────
+        let mut layers: Vec<(Vec<f32>, Color)> = Vec::new();
+        if let Some(shadow) = layout.shadow {
+            let mut shifted = shift(&silhouette, width, height, shadow.dx.round() as isize, shadow.dy.round() as isize);
+            if shadow.blur > 0.0 {
+                shifted = gaussian_blur(&shifted, width, height, shadow.blur);
+            }
+            layers.push((shifted, shadow.color));
+        }
+        if let Some(outline) = layout.outline {
+            layers.push((silhouette, outline.color));
+        }
+        layers.push((fill, self.color));
+
+        // Premultiplied "over", back to front.
+        let mut out = [vec![0.0f32; width * height], vec![0.0; width * height], vec![0.0; width * height]];
+        let mut alpha = vec![0.0f32; width * height];
+        for (coverage, color) in &layers {
+            let [r, g, b, a] = color.channels_f32().map(|v| v / 255.0);
+            for i in 0..coverage.len() {
+                let la = coverage[i].clamp(0.0, 1.0) * a;
+                for (plane, c) in out.iter_mut().zip([r, g, b]) {
+                    plane[i] = c * la + plane[i] * (1.0 - la);
+                }
+                alpha[i] = la + alpha[i] * (1.0 - la);
+            }
         }
 
         // ── Build PixelData from color variant ────────────────────────────────
-        let pixel_data = match color {
-            Color::Gray(_) => PixelData::GRAY(r_buf),
-            Color::RGB(_, _, _) => PixelData::RGB(r_buf, g_buf, b_buf),
-            Color::RGBA(_, _, _, _) => PixelData::RGBA(r_buf, g_buf, b_buf, a_buf),
+        // Layouts without alpha get the text composited onto black.
+        let q = |v: f32| (v.clamp(0.0, 1.0) * 255.0).round() as u8;
+        let [r, g, b] = out;
+        let pixel_data = match self.color {
+            Color::Gray(_) => PixelData::GRAY((0..alpha.len()).map(|i| q(0.299 * r[i] + 0.587 * g[i] + 0.114 * b[i])).collect()),
+            Color::RGB(..) => PixelData::RGB(r.iter().map(|&v| q(v)).collect(), g.iter().map(|&v| q(v)).collect(), b.iter().map(|&v| q(v)).collect()),
+            // Straight alpha: colour is divided back out, so edges do not darken when
+            // the frame is blurred or composited.
+            Color::RGBA(..) | Color::RGBAF(..) => {
+                let straight = |plane: &[f32]| {
+                    plane.iter().zip(&alpha).map(|(&v, &a)| if a > 0.0 { q(v / a) } else { 0 }).collect::<Vec<u8>>()
+                };
+                PixelData::RGBA(straight(&r), straight(&g), straight(&b), alpha.iter().map(|&a| q(a)).collect())
+            }
+        };
+
+        Frame::new(width as u32, height as u32, pixel_data).map_err(TextError::FrameCreationFailed)
+    }
+}
+
+/// Grows a coverage mask by `radius` pixels with an anti-aliased round brush.
+fn dilate(mask: &[f32], width: usize, height: usize, radius: f32) -> Vec<f32> {
+    let reach = radius.ceil() as isize;
+    let brush: Vec<(isize, isize, f32)> = (-reach..=reach)
+        .flat_map(|dy| (-reach..=reach).map(move |dx| (dx, dy)))
+        .map(|(dx, dy)| (dx, dy, (radius + 0.5 - ((dx * dx + dy * dy) as f32).sqrt()).clamp(0.0, 1.0)))
+        .filter(|&(_, _, w)| w > 0.0)
+        .collect();
+
+    let mut out = mask.to_vec();
+    for y in 0..height as isize {
+        for x in 0..width as isize {
+            let v = mask[y as usize * width + x as usize];
+            if v <= 0.0 {
+                continue;
+            }
+            for &(dx, dy, w) in &brush {
+                let (tx, ty) = (x + dx, y + dy);
+                if tx < 0 || ty < 0 || tx >= width as isize || ty >= height as isize {
+                    continue;
+                }
+                let idx = ty as usize * width + tx as usize;
+                out[idx] = out[idx].max(v * w);
+            }
+        }
+    }
+    out
+}
+
+/// Moves a mask by whole pixels; what moves out of the frame is dropped.
+fn shift(mask: &[f32], width: usize, height: usize, dx: isize, dy: isize) -> Vec<f32> {
+    let mut out = vec![0.0; mask.len()];
+    for y in 0..height as isize {
+        for x in 0..width as isize {
+            let (sx, sy) = (x - dx, y - dy);
+            if sx >= 0 && sy >= 0 && sx < width as isize && sy < height as isize {
+                out[y as usize * width + x as usize] = mask[sy as usize * width + sx as usize];
+            }
+        }
+    }
+    out
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use fontdue::FontSettings;
+
+    /// DejaVu Sans Mono, checked in under `tests/fonts`.
+    fn mono() -> Font {
+        Font::from_bytes(&include_bytes!("../tests/fonts/DejaVuSansMono.ttf")[..], FontSettings::default()).unwrap()
+    }
+
+    const WHITE: Color = Color::RGBA(255, 255, 255, 255);
+
+    /// First and last inked column over `rows` of a rendered text frame.
+    fn ink_columns(frame: &Frame, rows: std::ops::Range<usize>) -> (usize, usize) {
+        let alpha = &frame.rgba_planes().unwrap()[3];
+        let width = frame.width() as usize;
+        let inked: Vec<usize> = rows.flat_map(|y| (0..width).filter(move |&x| alpha[y * width + x] > 0.0)).collect();
+        (*inked.iter().min().unwrap(), *inked.iter().max().unwrap())
+    }
+
+    #[test]
+    fn wrapped_lines_fit_the_width_and_outline_grows_the_frame() {
+        let mut text = Text::new("the quick brown fox jumps", mono(), 20.0, Pos(0, 0), WHITE);
+        let plain = text.picturize().unwrap();
+
+        let layout = TextLayout { max_width: Some(80.0), ..TextLayout::default() };
+        text.set_layout(layout);
+        let lines = text.wrap_lines();
+        assert!(lines.len() > 1);
+        assert!(lines.iter().all(|line| text.line_width(line) <= 80.0));
+        assert_eq!(lines.join(" "), "the quick brown fox jumps");
+
+        text.set_layout(TextLayout { outline: Some(Outline { width: 3.0, color: Color::RGBA(0, 0, 0, 255) }), ..TextLayout::default() });
+        let outlined = text.picturize().unwrap();
+        assert!(outlined.width() >= plain.width() + 6);
+        assert!(outlined.height() >= plain.height() + 6);
+    }
+
+    #[test]
+    fn lines_align_within_the_block_and_spacing_moves_them_apart() {
+        // Two lines of a monospaced font, the first half as wide as the second.
+        let mut text = Text::new("ab\nabcd", mono(), 20.0, Pos(0, 0), WHITE);
+        let line_height = mono().horizontal_line_metrics(20.0).unwrap().new_line_size;
+        let split = line_height.round() as usize;
+        let columns = |text: &Text| {
+            let frame = text.picturize().unwrap();
+            (ink_columns(&frame, 0..split), ink_columns(&frame, split..frame.height() as usize))
         };
 
-        Frame::new(width, height, pixel_data).map_err(TextError::FrameCreationFailed)
+        let ((first_left, _), (second_left, _)) = columns(&text);
+        assert!(first_left.abs_diff(second_left) <= 1);
+
+        text.set_layout(TextLayout { align: Align::Right, ..TextLayout::default() });
+        let ((_, first_right), (_, second_right)) = columns(&text);
+        assert!(first_right.abs_diff(second_right) <= 1);
+
+        text.set_layout(TextLayout { align: Align::Center, ..TextLayout::default() });
+        let ((first_left, first_right), (second_left, second_right)) = columns(&text);
+        assert!((first_left - second_left).abs_diff(second_right - first_right) <= 1);
+        assert!(first_left > second_left + 10);
+
+        let single = text.picturize().unwrap().height();
+        text.set_layout(TextLayout { line_spacing: 2.0, ..TextLayout::default() });
+        let double = text.picturize().unwrap().height();
+        assert!((double as f32 - single as f32 - line_height).abs() <= 1.0);
+    }
+
+    #[test]
+    fn shadow_is_offset_underneath_and_widens_the_frame() {
+        let mut text = Text::new("Hg", mono(), 20.0, Pos(0, 0), WHITE);
+        let plain = text.picturize().unwrap();
+        let shadow = Shadow { dx: 4.0, dy: 3.0, blur: 0.0, color: Color::RGBA(0, 0, 0, 255) };
+        text.set_layout(TextLayout { shadow: Some(shadow), ..TextLayout::default() });
+        let shadowed = text.picturize().unwrap();
+        assert_eq!((shadowed.width(), shadowed.height()), (plain.width() + 4, plain.height() + 3));
+
+        // The rows below the descender hold only shadow: inked, and black.
+        let [r, _, _, a] = shadowed.rgba_planes().unwrap();
+        let width = shadowed.width() as usize;
+        let below = plain.height() as usize * width..a.len();
+        assert!(a[below.clone()].iter().any(|&v| v > 0.5));
+        assert!(below.into_iter().all(|i| a[i] == 0.0 || r[i] == 0.0));
+        // Where the fill is, the fill stays on top.
+        let plain_alpha = &plain.rgba_planes().unwrap()[3];
+        let solid = plain_alpha.iter().position(|&v| v == 1.0).unwrap();
+        let (x, y) = (solid % plain.width() as usize, solid / plain.width() as usize);
+        assert_eq!(r[y * width + x], 1.0);
+
+        // A blurred shadow reaches three sigmas past its offset.
+        text.set_layout(TextLayout { shadow: Some(Shadow { blur: 2.0, ..shadow }), ..TextLayout::default() });
+        let blurred = text.picturize().unwrap();
+        assert_eq!(blurred.width(), plain.width() + 2 + 10);
+        assert_eq!(blurred.height(), plain.height() + 3 + 9);
+    }
+
+    #[test]
+    fn characters_missing_from_the_primary_font_use_the_fallback() {
+        // A font with nothing but `A`, from ttf-parser's test fonts.
+        let single = Font::from_bytes(&include_bytes!("../tests/fonts/SingleGlyph.ttf")[..], FontSettings::default()).unwrap();
+        let mono = Rc::new(mono());
+
+        let mut text = Text::new("AЖ", single, 20.0, Pos(0, 0), Color::RGBA(0, 0, 0, 255));
+        assert_eq!(text.font_index('Ж'), 0);
+        text.set_fallbacks(vec![Rc::clone(&mono)]);
+        assert_eq!(text.font_index('A'), 0);
+        assert_eq!(text.font_index('Ж'), 1);
+        assert_eq!(text.font_index(' '), 0);
+        // Each glyph advances by its own font's metrics, with no kerning across fonts.
+        let expected = text.font.metrics('A', 20.0).advance_width + mono.metrics('Ж', 20.0).advance_width;
+        assert!((text.line_width("AЖ") - expected).abs() < 1e-3);
+        assert!(text.picturize().is_ok());
     }
 }